@@ -58,6 +58,8 @@ class MergedModel(object):
         self.faces = []
         self.verts1 = []
         self.verts2 = []
+        self.colors1 = []
+        self.colors2 = []
 
         with open(fname, "r") as f:
             for line in f.readlines():
@@ -65,11 +67,16 @@ class MergedModel(object):
                 if len(a) < 4:
                     continue
                 if a[0] == "f":
-                    self.faces.append(list(map(lambda s: int(s) - 1, a[1:])))
+                    self.faces.append(
+                        list(map(lambda s: int(s.split("/")[0]) - 1, a[1:])))
                 elif a[0] == "v":
-                    self.verts1.append(list(map(float, a[1:])))
+                    self.verts1.append(list(map(float, a[1:4])))
+                    if len(a) == 7:
+                        self.colors1.append(list(map(float, a[4:7])))
                 elif a[0] == "u":
-                    self.verts2.append(list(map(float, a[1:])))
+                    self.verts2.append(list(map(float, a[1:4])))
+                    if len(a) == 7:
+                        self.colors2.append(list(map(float, a[4:7])))
 
     def interpolation(self, ratio):
         verts = []
@@ -80,6 +87,12 @@ class MergedModel(object):
             verts.append(new_v)
         return (verts, self.faces)
 
+    def color_interpolation(self, ratio):
+        colors = []
+        for (c1, c2) in zip(self.colors1, self.colors2):
+            colors.append([c1[i] + (c2[i] - c1[i]) * ratio for i in range(3)])
+        return colors
+
 
 def create_object(name, verts, faces):
     if name in bpy.data.objects:
@@ -114,6 +127,21 @@ if __name__ == "__main__":
                                            v.index, index=i) for i in range(3)])
     n = len(fcurves)
 
+    # blend the vertex colors of the merged model if it has any, instead of
+    # the color of the material
+    color_fcurves = []
+    if model.colors1:
+        mesh.color_attributes.new("Col", 'FLOAT_COLOR', 'POINT')
+        mat.use_nodes = True
+        attr = mat.node_tree.nodes.new("ShaderNodeVertexColor")
+        attr.layer_name = "Col"
+        mat.node_tree.links.new(
+            attr.outputs["Color"],
+            mat.node_tree.nodes["Principled BSDF"].inputs["Base Color"])
+        for v in mesh.vertices:
+            color_fcurves.append([action.fcurves.new(
+                'attributes["Col"].data[%d].color' % v.index, index=i) for i in range(3)])
+
     for i in range(0, FRAME_COUNT, FRAME_INC):
         ratio = i / FRAME_COUNT
         verts, _ = model.interpolation(ratio)
@@ -127,3 +155,7 @@ if __name__ == "__main__":
         for fcur, v in zip(fcurves, verts):
             for k in range(3):
                 fcur[k].keyframe_points.insert(i, v[k], options={'FAST'})
+
+        for fcur, c in zip(color_fcurves, model.color_interpolation(ratio)):
+            for k in range(3):
+                fcur[k].keyframe_points.insert(i, c[k], options={'FAST'})
@@ -0,0 +1,266 @@
+use std::collections::BTreeMap;
+
+use crate::{MergedModel, Model};
+
+/// Values of a per vertex attribute, `arity` consecutive values per vertex.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AttributeValues {
+    F32(Vec<f32>),
+    F64(Vec<f64>),
+}
+
+/// How the values of an attribute are combined between vertices and models.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AttributePolicy {
+    Blend,   // weighted average, e.g. skinning weights or occlusion
+    Nearest, // value with the largest weight, for categorical attributes
+}
+
+/// A named per vertex attribute such as skinning weights, ambient occlusion or
+/// masks, transferred through the merge and blended by the interpolation.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Attribute {
+    arity: usize,
+    values: AttributeValues,
+    policy: AttributePolicy,
+}
+
+impl Attribute {
+    pub fn new(arity: usize, values: AttributeValues, policy: AttributePolicy) -> Self {
+        let len = match &values {
+            AttributeValues::F32(v) => v.len(),
+            AttributeValues::F64(v) => v.len(),
+        };
+        assert!(
+            arity > 0 && len % arity == 0,
+            "Invalid attribute arity {}",
+            arity
+        );
+        Self {
+            arity,
+            values,
+            policy,
+        }
+    }
+
+    pub fn arity(&self) -> usize {
+        self.arity
+    }
+
+    pub fn policy(&self) -> AttributePolicy {
+        self.policy
+    }
+
+    pub fn values(&self) -> &AttributeValues {
+        &self.values
+    }
+
+    /// Number of vertices.
+    pub fn len(&self) -> usize {
+        match &self.values {
+            AttributeValues::F32(v) => v.len() / self.arity,
+            AttributeValues::F64(v) => v.len() / self.arity,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Values of vertex `i`.
+    pub fn get(&self, i: usize) -> Vec<f64> {
+        let range = i * self.arity..(i + 1) * self.arity;
+        match &self.values {
+            AttributeValues::F32(v) => v[range].iter().map(|&x| x as f64).collect(),
+            AttributeValues::F64(v) => v[range].to_vec(),
+        }
+    }
+
+    /// An attribute of the same type, arity and policy with the given values.
+    pub(crate) fn with_rows<I: IntoIterator<Item = Vec<f64>>>(&self, rows: I) -> Self {
+        let values = rows.into_iter().flatten();
+        let values = match &self.values {
+            AttributeValues::F32(_) => AttributeValues::F32(values.map(|x| x as f32).collect()),
+            AttributeValues::F64(_) => AttributeValues::F64(values.collect()),
+        };
+        Self::new(self.arity, values, self.policy)
+    }
+
+    /// Combination of the values of some vertices, by their weights.
+    pub(crate) fn mix(&self, weights: &[(usize, f64)]) -> Vec<f64> {
+        let values = weights
+            .iter()
+            .map(|&(i, w)| (self.get(i), w))
+            .collect::<Vec<_>>();
+        mix(self.policy, self.arity, &values)
+    }
+}
+
+/// Weighted average of `values`, or the one with the largest weight.
+pub(crate) fn mix(policy: AttributePolicy, arity: usize, values: &[(Vec<f64>, f64)]) -> Vec<f64> {
+    match policy {
+        AttributePolicy::Blend => values.iter().fold(vec![0.0; arity], |mut acc, (v, w)| {
+            for (a, x) in acc.iter_mut().zip(v) {
+                *a += x * w;
+            }
+            acc
+        }),
+        AttributePolicy::Nearest => values
+            .iter()
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map_or_else(|| vec![0.0; arity], |v| v.0.clone()),
+    }
+}
+
+impl Model {
+    /// Attaches an attribute with one entry per vertex, replacing any attribute
+    /// of the same name. Attributes are not written to OBJ files.
+    pub fn set_attribute(&mut self, name: &str, attribute: Attribute) {
+        assert_eq!(
+            attribute.len(),
+            self.nr_verts(),
+            "Attribute \"{}\" has {} entries for {} vertices",
+            name,
+            attribute.len(),
+            self.nr_verts()
+        );
+        self.attributes.insert(name.to_string(), attribute);
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&Attribute> {
+        self.attributes.get(name)
+    }
+
+    pub fn attributes(&self) -> &BTreeMap<String, Attribute> {
+        &self.attributes
+    }
+}
+
+impl MergedModel {
+    /// Values of an attribute on both models.
+    pub fn attribute_pair(&self, name: &str) -> Option<(&Attribute, &Attribute)> {
+        self.attribute_pairs.get(name).map(|p| (&p.0, &p.1))
+    }
+
+    /// Attributes blended by the ratio of each vertex, switching half way for
+    /// categorical ones.
+    pub(crate) fn interpolated_attributes(&self, ratios: &[f64]) -> BTreeMap<String, Attribute> {
+        self.attribute_pairs
+            .iter()
+            .map(|(name, (a, b))| {
+                let rows = ratios
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &r)| mix(a.policy, a.arity, &[(a.get(i), 1.0 - r), (b.get(i), r)]));
+                (name.clone(), a.with_rows(rows))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{cube, octahedron};
+    use crate::{merge, Config};
+
+    fn blend(values: Vec<f64>) -> Attribute {
+        Attribute::new(1, AttributeValues::F64(values), AttributePolicy::Blend)
+    }
+
+    #[test]
+    fn values_per_vertex() {
+        let a = Attribute::new(
+            2,
+            AttributeValues::F32(vec![1.0, 2.0, 3.0, 4.0]),
+            AttributePolicy::Nearest,
+        );
+        assert_eq!(a.len(), 2);
+        assert_eq!(a.get(1), [3.0, 4.0]);
+        let b = a.with_rows(vec![vec![5.0, 6.0]]);
+        assert_eq!(b.values(), &AttributeValues::F32(vec![5.0, 6.0]));
+        assert_eq!((b.arity(), b.policy()), (2, AttributePolicy::Nearest));
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid attribute arity")]
+    fn values_are_whole_vertices() {
+        Attribute::new(
+            2,
+            AttributeValues::F64(vec![1.0; 3]),
+            AttributePolicy::Blend,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "has 2 entries for 8 vertices")]
+    fn one_entry_per_vertex() {
+        cube().set_attribute("mask", blend(vec![0.0; 2]));
+    }
+
+    #[test]
+    fn mix_by_policy() {
+        let values = [(vec![1.0, 0.0], 0.25), (vec![0.0, 2.0], 0.75)];
+        assert_eq!(mix(AttributePolicy::Blend, 2, &values), [0.25, 1.5]);
+        assert_eq!(mix(AttributePolicy::Nearest, 2, &values), [0.0, 2.0]);
+        assert_eq!(mix(AttributePolicy::Nearest, 2, &[]), [0.0, 0.0]);
+        // NaN weights do not panic
+        let values = [(vec![1.0], f64::NAN), (vec![2.0], 0.5)];
+        assert_eq!(mix(AttributePolicy::Nearest, 1, &values).len(), 1);
+    }
+
+    #[test]
+    fn attributes_are_transferred_and_interpolated() {
+        let mut model1 = cube();
+        let x = model1.verts.iter().map(|v| v.x).collect();
+        model1.set_attribute("x", blend(x));
+        let mut model2 = octahedron();
+        model2.set_attribute("one", blend(vec![1.0; 6]));
+        let merged = merge(model1, model2, Config::default());
+
+        // linear values are kept across the faces of the cube
+        let (a, b) = merged.attribute_pair("x").unwrap();
+        let [p1, _] = merged.placements();
+        for (i, p) in merged.vert_pairs().iter().enumerate() {
+            assert!((a.get(i)[0] - p1.restore(p.0).x).abs() < 1e-9);
+        }
+        // the octahedron takes the values of the cube
+        assert_eq!(a, b);
+        let (a, b) = merged.attribute_pair("one").unwrap();
+        assert_eq!(a, b);
+        assert!(merged.attribute_pair("two").is_none());
+
+        let model = merged.interpolation(0.5);
+        assert_eq!(model.attribute("one").unwrap().len(), merged.nr_verts());
+        assert_eq!(model.attributes().len(), 2);
+    }
+
+    #[test]
+    fn categories_switch_half_way() {
+        let mut model1 = cube();
+        model1.set_attribute(
+            "id",
+            Attribute::new(
+                1,
+                AttributeValues::F64(vec![1.0; 8]),
+                AttributePolicy::Nearest,
+            ),
+        );
+        let mut model2 = cube();
+        model2.set_attribute(
+            "id",
+            Attribute::new(
+                1,
+                AttributeValues::F64(vec![2.0; 8]),
+                AttributePolicy::Nearest,
+            ),
+        );
+        let merged = merge(model1, model2, Config::default());
+        let id = |t| merged.interpolation(t).attribute("id").unwrap().get(0)[0];
+        assert_eq!(id(0.4), 1.0);
+        assert_eq!(id(0.6), 2.0);
+    }
+}
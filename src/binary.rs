@@ -0,0 +1,505 @@
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::{self, prelude::*, BufReader, BufWriter};
+
+use crate::attribute::{Attribute, AttributePolicy, AttributeValues};
+use crate::geo::{Face, Quaternion, Vertex};
+use crate::label::FaceLabel;
+use crate::placement::Placement;
+use crate::provenance::{Provenance, SourceLocation, VertexProvenance, VertexSource};
+use crate::stats::MergeStats;
+use crate::MergedModel;
+
+const MAGIC: &[u8; 4] = b"MRGM";
+const VERSION: u32 = 1;
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// Little endian encoding of the values of a merged model.
+struct Encoder<W> {
+    writer: W,
+}
+
+impl<W: Write> Encoder<W> {
+    fn u8(&mut self, x: u8) -> io::Result<()> {
+        self.writer.write_all(&[x])
+    }
+
+    fn u64(&mut self, x: usize) -> io::Result<()> {
+        self.writer.write_all(&(x as u64).to_le_bytes())
+    }
+
+    fn f64(&mut self, x: f64) -> io::Result<()> {
+        self.writer.write_all(&x.to_le_bytes())
+    }
+
+    fn vertex(&mut self, v: Vertex) -> io::Result<()> {
+        self.f64(v.x)?;
+        self.f64(v.y)?;
+        self.f64(v.z)
+    }
+
+    fn pairs(&mut self, pairs: &[(Vertex, Vertex)]) -> io::Result<()> {
+        self.u64(pairs.len())?;
+        for p in pairs {
+            self.vertex(p.0)?;
+            self.vertex(p.1)?;
+        }
+        Ok(())
+    }
+
+    fn faces(&mut self, faces: &[Face]) -> io::Result<()> {
+        self.u64(faces.len())?;
+        for f in faces {
+            self.u64(f.len())?;
+            for &i in f {
+                self.u64(i)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn string(&mut self, s: Option<&str>) -> io::Result<()> {
+        match s {
+            Some(s) => {
+                self.u8(1)?;
+                self.u64(s.len())?;
+                self.writer.write_all(s.as_bytes())
+            }
+            None => self.u8(0),
+        }
+    }
+
+    fn placement(&mut self, p: &Placement) -> io::Result<()> {
+        self.vertex(p.center)?;
+        self.f64(p.scale)?;
+        self.f64(p.rotation.w)?;
+        self.vertex(p.rotation.v)
+    }
+
+    fn attribute(&mut self, a: &Attribute) -> io::Result<()> {
+        self.u64(a.arity())?;
+        self.u8(match a.policy() {
+            AttributePolicy::Blend => 0,
+            AttributePolicy::Nearest => 1,
+        })?;
+        match a.values() {
+            AttributeValues::F32(v) => {
+                self.u8(0)?;
+                self.u64(v.len())?;
+                for x in v {
+                    self.writer.write_all(&x.to_le_bytes())?;
+                }
+            }
+            AttributeValues::F64(v) => {
+                self.u8(1)?;
+                self.u64(v.len())?;
+                for &x in v {
+                    self.f64(x)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn location(&mut self, l: &SourceLocation) -> io::Result<()> {
+        self.u64(l.face)?;
+        for &w in &l.barycentric {
+            self.f64(w)?;
+        }
+        Ok(())
+    }
+}
+
+struct Decoder<R> {
+    reader: R,
+}
+
+impl<R: Read> Decoder<R> {
+    fn bytes<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let mut bytes = [0; N];
+        self.reader.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.bytes::<1>()?[0])
+    }
+
+    fn u64(&mut self) -> io::Result<usize> {
+        (u64::from_le_bytes(self.bytes()?))
+            .try_into()
+            .map_err(|_| invalid("Length out of range"))
+    }
+
+    fn f64(&mut self) -> io::Result<f64> {
+        Ok(f64::from_le_bytes(self.bytes()?))
+    }
+
+    fn vertex(&mut self) -> io::Result<Vertex> {
+        Ok(Vertex::new(self.f64()?, self.f64()?, self.f64()?))
+    }
+
+    fn pairs(&mut self) -> io::Result<Vec<(Vertex, Vertex)>> {
+        let n = self.u64()?;
+        let mut pairs = Vec::new();
+        for _ in 0..n {
+            pairs.push((self.vertex()?, self.vertex()?));
+        }
+        Ok(pairs)
+    }
+
+    /// Faces with indices below `bound`.
+    fn faces(&mut self, bound: usize) -> io::Result<Vec<Face>> {
+        let n = self.u64()?;
+        let mut faces = Vec::new();
+        for _ in 0..n {
+            let m = self.u64()?;
+            let mut f = Vec::new();
+            for _ in 0..m {
+                let i = self.u64()?;
+                if i >= bound {
+                    return Err(invalid("Index out of range"));
+                }
+                f.push(i);
+            }
+            faces.push(f);
+        }
+        Ok(faces)
+    }
+
+    fn string(&mut self) -> io::Result<Option<String>> {
+        match self.u8()? {
+            0 => Ok(None),
+            1 => {
+                // read up to the length, without trusting it for allocation
+                let n = self.u64()? as u64;
+                let mut bytes = Vec::new();
+                (&mut self.reader).take(n).read_to_end(&mut bytes)?;
+                if bytes.len() as u64 != n {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+                String::from_utf8(bytes)
+                    .map(Some)
+                    .map_err(|_| invalid("Invalid string"))
+            }
+            _ => Err(invalid("Invalid string")),
+        }
+    }
+
+    fn placement(&mut self) -> io::Result<Placement> {
+        Ok(Placement {
+            center: self.vertex()?,
+            scale: self.f64()?,
+            rotation: Quaternion::new(self.f64()?, self.vertex()?),
+        })
+    }
+
+    fn attribute(&mut self, nr_verts: usize) -> io::Result<Attribute> {
+        let arity = self.u64()?;
+        let policy = match self.u8()? {
+            0 => AttributePolicy::Blend,
+            1 => AttributePolicy::Nearest,
+            _ => return Err(invalid("Invalid attribute policy")),
+        };
+        let values = match self.u8()? {
+            0 => {
+                let n = self.u64()?;
+                let mut v = Vec::new();
+                for _ in 0..n {
+                    v.push(f32::from_le_bytes(self.bytes()?));
+                }
+                AttributeValues::F32(v)
+            }
+            1 => {
+                let n = self.u64()?;
+                let mut v = Vec::new();
+                for _ in 0..n {
+                    v.push(self.f64()?);
+                }
+                AttributeValues::F64(v)
+            }
+            _ => return Err(invalid("Invalid attribute type")),
+        };
+        let len = match &values {
+            AttributeValues::F32(v) => v.len(),
+            AttributeValues::F64(v) => v.len(),
+        };
+        if arity == 0 || arity.checked_mul(nr_verts) != Some(len) {
+            return Err(invalid("Invalid attribute size"));
+        }
+        Ok(Attribute::new(arity, values, policy))
+    }
+
+    fn location(&mut self) -> io::Result<SourceLocation> {
+        Ok(SourceLocation {
+            face: self.u64()?,
+            barycentric: [self.f64()?, self.f64()?, self.f64()?],
+        })
+    }
+}
+
+impl MergedModel {
+    /// Writes the model in a compact binary format: a `MRGM` magic number and
+    /// a format version, followed by the little endian values of the model.
+    /// The statistics of the merge are not written.
+    pub fn write_binary(&self, writer: impl Write) -> io::Result<()> {
+        let mut e = Encoder { writer };
+        e.writer.write_all(MAGIC)?;
+        e.writer.write_all(&VERSION.to_le_bytes())?;
+
+        e.pairs(&self.vert_pairs)?;
+        e.faces(&self.faces)?;
+        for p in &self.placements {
+            e.placement(p)?;
+        }
+        e.u8(self.keep_coords as u8)?;
+        e.pairs(&self.texcoord_pairs)?;
+        e.faces(&self.face_texcoords)?;
+        e.pairs(&self.color_pairs)?;
+        e.pairs(&self.normal_pairs)?;
+        e.faces(&self.face_normals)?;
+
+        e.u64(self.attribute_pairs.len())?;
+        for (name, (a, b)) in &self.attribute_pairs {
+            e.string(Some(name))?;
+            e.attribute(a)?;
+            e.attribute(b)?;
+        }
+
+        for labels in &self.labels {
+            e.u64(labels.len())?;
+            for l in labels {
+                e.string(l.group.as_deref())?;
+                e.string(l.material.as_deref())?;
+            }
+        }
+        e.u64(self.face_labels.len())?;
+        for l in &self.face_labels {
+            e.u64(l.0)?;
+            e.u64(l.1)?;
+        }
+
+        e.u64(self.provenance.vertices.len())?;
+        for v in &self.provenance.vertices {
+            match v.source {
+                VertexSource::Model1(i) => {
+                    e.u8(1)?;
+                    e.u64(i)?;
+                }
+                VertexSource::Model2(i) => {
+                    e.u8(2)?;
+                    e.u64(i)?;
+                }
+                VertexSource::Intersection => e.u8(0)?,
+            }
+            e.location(&v.locations[0])?;
+            e.location(&v.locations[1])?;
+        }
+        e.u64(self.provenance.faces.len())?;
+        for f in &self.provenance.faces {
+            e.u64(f[0])?;
+            e.u64(f[1])?;
+        }
+        e.writer.flush()
+    }
+
+    pub fn read_binary(reader: impl Read) -> io::Result<Self> {
+        let mut d = Decoder { reader };
+        if &d.bytes::<4>()? != MAGIC {
+            return Err(invalid("Not a merged model"));
+        }
+        let version = u32::from_le_bytes(d.bytes()?);
+        if version != VERSION {
+            return Err(invalid(&format!(
+                "Unsupported merged model version {}",
+                version
+            )));
+        }
+
+        let vert_pairs = d.pairs()?;
+        let n = vert_pairs.len();
+        let faces = d.faces(n)?;
+        let placements = [d.placement()?, d.placement()?];
+        let keep_coords = match d.u8()? {
+            0 => false,
+            1 => true,
+            _ => return Err(invalid("Invalid coordinates flag")),
+        };
+        let texcoord_pairs = d.pairs()?;
+        let face_texcoords = d.faces(texcoord_pairs.len())?;
+        let color_pairs = d.pairs()?;
+        let normal_pairs = d.pairs()?;
+        let face_normals = d.faces(normal_pairs.len())?;
+
+        let mut attribute_pairs = BTreeMap::new();
+        for _ in 0..d.u64()? {
+            let name = d
+                .string()?
+                .ok_or_else(|| invalid("Missing attribute name"))?;
+            attribute_pairs.insert(name, (d.attribute(n)?, d.attribute(n)?));
+        }
+
+        let mut labels = [Vec::new(), Vec::new()];
+        for l in &mut labels {
+            for _ in 0..d.u64()? {
+                l.push(FaceLabel {
+                    group: d.string()?,
+                    material: d.string()?,
+                });
+            }
+        }
+        let mut face_labels = Vec::new();
+        for _ in 0..d.u64()? {
+            let l = (d.u64()?, d.u64()?);
+            if l.0 >= labels[0].len() || l.1 >= labels[1].len() {
+                return Err(invalid("Index out of range"));
+            }
+            face_labels.push(l);
+        }
+
+        let mut provenance = Provenance::default();
+        for _ in 0..d.u64()? {
+            let source = match d.u8()? {
+                0 => VertexSource::Intersection,
+                1 => VertexSource::Model1(d.u64()?),
+                2 => VertexSource::Model2(d.u64()?),
+                _ => return Err(invalid("Invalid vertex source")),
+            };
+            provenance.vertices.push(VertexProvenance {
+                source,
+                locations: [d.location()?, d.location()?],
+            });
+        }
+        for _ in 0..d.u64()? {
+            provenance.faces.push([d.u64()?, d.u64()?]);
+        }
+
+        // faces are triangles, with a value for each corner
+        let corners = |values: &[Face]| {
+            values.is_empty()
+                || values.len() == faces.len()
+                    && values.iter().zip(&faces).all(|(v, f)| v.len() == f.len())
+        };
+        if faces.iter().any(|f| f.len() != 3)
+            || !corners(&face_texcoords)
+            || !corners(&face_normals)
+            || !(color_pairs.is_empty() || color_pairs.len() == n)
+            || !(face_labels.is_empty() || face_labels.len() == faces.len())
+        {
+            return Err(invalid("Inconsistent merged model"));
+        }
+
+        Ok(MergedModel {
+            faces,
+            vert_pairs,
+            placements,
+            keep_coords,
+            texcoord_pairs,
+            face_texcoords,
+            color_pairs,
+            normal_pairs,
+            face_normals,
+            attribute_pairs,
+            labels,
+            face_labels,
+            provenance,
+            stats: MergeStats::default(),
+        })
+    }
+
+    pub fn save_binary(&self, filename: &str) -> io::Result<()> {
+        let file = File::create(filename)?;
+        self.write_binary(BufWriter::new(file))
+    }
+
+    pub fn load_binary(filename: &str) -> io::Result<Self> {
+        let file = File::open(filename)?;
+        Self::read_binary(BufReader::new(file))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::normal::NormalWeighting;
+    use crate::testing::{cube, octahedron};
+    use crate::{merge, Config};
+
+    /// A merged model with every optional value.
+    fn merged() -> MergedModel {
+        let mut model1 = cube();
+        model1.texcoords = model1.verts.iter().map(|&v| vec![v]).collect();
+        model1.face_texcoords = model1.faces.clone();
+        model1.colors = model1.verts.clone();
+        model1.compute_normals(NormalWeighting::Angle, 0.0);
+        let x = model1.verts.iter().map(|v| v.x as f32).collect();
+        model1.set_attribute(
+            "x",
+            Attribute::new(1, AttributeValues::F32(x), AttributePolicy::Blend),
+        );
+        model1.labels = vec![FaceLabel {
+            group: Some("cube".to_string()),
+            material: None,
+        }];
+        model1.face_labels = vec![0; model1.nr_faces()];
+        let config = Config {
+            keep_coords: true,
+            ..Config::default()
+        };
+        let mut merged = merge(model1, octahedron(), config);
+        // statistics are not written
+        merged.stats = MergeStats::default();
+        merged
+    }
+
+    fn write(model: &MergedModel) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        model.write_binary(&mut bytes).unwrap();
+        bytes
+    }
+
+    fn read_err(bytes: &[u8]) -> io::Error {
+        MergedModel::read_binary(bytes).unwrap_err()
+    }
+
+    #[test]
+    fn reads_what_it_writes() {
+        let merged = merged();
+        assert!(merged.keep_coords && !merged.provenance.is_empty());
+        assert!(!merged.normal_pairs.is_empty() && !merged.face_labels.is_empty());
+        let read = MergedModel::read_binary(&write(&merged)[..]).unwrap();
+        assert_eq!(read, merged);
+
+        let plain = merge(cube(), octahedron(), Config::default());
+        let mut read = MergedModel::read_binary(&write(&plain)[..]).unwrap();
+        read.stats = plain.stats.clone();
+        assert_eq!(read, plain);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        let bytes = write(&merged());
+        assert_eq!(&bytes[..4], MAGIC);
+        assert_eq!(read_err(b"MRG").kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(read_err(b"OBJ 1234").kind(), io::ErrorKind::InvalidData);
+        let mut other = bytes.clone();
+        other[4] = 2;
+        assert_eq!(read_err(&other).kind(), io::ErrorKind::InvalidData);
+        let truncated = &bytes[..bytes.len() - 1];
+        assert_eq!(read_err(truncated).kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn rejects_non_triangles() {
+        let mut merged = merge(cube(), octahedron(), Config::default());
+        let i = merged.faces[1][0];
+        merged.faces[0].push(i);
+        let err = read_err(&write(&merged));
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "Inconsistent merged model");
+    }
+}
@@ -0,0 +1,329 @@
+use std::str::FromStr;
+
+use crate::geo::EPS;
+use crate::{MergedModel, Model};
+
+const BEZIER_ITERATIONS: usize = 32;
+const PROJECTION_ITERATIONS: usize = 64;
+
+/// Timing curve mapping the animation time in `[0, 1]` to the morphing ratio.
+/// Outside `[0, 1]` the curve is continued with slope 1.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Easing {
+    Linear,
+    SmoothStep,
+    CubicBezier(f64, f64, f64, f64), // control points `(x1, y1)`, `(x2, y2)` as in CSS
+    Table(Vec<f64>),                 // samples at uniform times, linearly interpolated
+}
+
+impl Easing {
+    pub fn ease_in() -> Self {
+        Easing::CubicBezier(0.42, 0.0, 1.0, 1.0)
+    }
+
+    pub fn ease_out() -> Self {
+        Easing::CubicBezier(0.0, 0.0, 0.58, 1.0)
+    }
+
+    pub fn ease_in_out() -> Self {
+        Easing::CubicBezier(0.42, 0.0, 0.58, 1.0)
+    }
+
+    pub fn apply(&self, t: f64) -> f64 {
+        match self {
+            Easing::Linear => t,
+            _ if t < 0.0 => self.apply(0.0) + t,
+            _ if t > 1.0 => self.apply(1.0) + t - 1.0,
+            Easing::SmoothStep => t * t * (3.0 - 2.0 * t),
+            Easing::CubicBezier(x1, y1, x2, y2) => {
+                let bezier = |p1: f64, p2: f64, s: f64| {
+                    3.0 * (1.0 - s) * (1.0 - s) * s * p1 + 3.0 * (1.0 - s) * s * s * p2 + s * s * s
+                };
+                // x(s) is monotonic for x1, x2 in [0, 1], find s with x(s) = t
+                let (mut lo, mut hi) = (0.0, 1.0);
+                for _ in 0..BEZIER_ITERATIONS {
+                    let mid = (lo + hi) / 2.0;
+                    if bezier(*x1, *x2, mid) < t {
+                        lo = mid;
+                    } else {
+                        hi = mid;
+                    }
+                }
+                bezier(*y1, *y2, (lo + hi) / 2.0)
+            }
+            Easing::Table(values) => match values.len() {
+                0 => t,
+                1 => values[0],
+                n => {
+                    let x = t * (n - 1) as f64;
+                    let i = (x.floor() as usize).min(n - 2);
+                    values[i] + (values[i + 1] - values[i]) * (x - i as f64)
+                }
+            },
+        }
+    }
+}
+
+impl FromStr for Easing {
+    type Err = String;
+
+    /// Parses `linear`, `smoothstep`, `ease-in`, `ease-out`, `ease-in-out`,
+    /// `bezier:<x1>,<y1>,<x2>,<y2>` or `table:<v0>,<v1>,...`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, args) = match s.find(':') {
+            Some(i) => (&s[..i], Some(&s[i + 1..])),
+            None => (s, None),
+        };
+        let values = args
+            .unwrap_or("")
+            .split(',')
+            .filter(|v| !v.trim().is_empty())
+            .map(|v| v.trim().parse::<f64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Invalid easing \"{}\": {}", s, e))?;
+
+        match (name, values.len()) {
+            ("linear", 0) => Ok(Easing::Linear),
+            ("smoothstep", 0) => Ok(Easing::SmoothStep),
+            ("ease-in", 0) => Ok(Easing::ease_in()),
+            ("ease-out", 0) => Ok(Easing::ease_out()),
+            ("ease-in-out", 0) => Ok(Easing::ease_in_out()),
+            ("bezier", 4) => {
+                if values[0] < -EPS
+                    || values[0] > 1.0 + EPS
+                    || values[2] < -EPS
+                    || values[2] > 1.0 + EPS
+                {
+                    return Err(format!(
+                        "Invalid easing \"{}\": x1 and x2 must be in [0, 1]",
+                        s
+                    ));
+                }
+                Ok(Easing::CubicBezier(
+                    values[0], values[1], values[2], values[3],
+                ))
+            }
+            ("table", n) if n > 0 => Ok(Easing::Table(values)),
+            _ => Err(format!("Invalid easing \"{}\"", s)),
+        }
+    }
+}
+
+/// What to do with ratios outside `[0, 1]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Extrapolation {
+    Clamp,
+    Allow,
+    Reject,
+}
+
+impl Extrapolation {
+    /// The ratio to use, `None` if it is rejected.
+    pub fn apply(&self, ratio: f64) -> Option<f64> {
+        if (0.0..=1.0).contains(&ratio) {
+            return Some(ratio);
+        }
+        match self {
+            Extrapolation::Clamp => Some(ratio.clamp(0.0, 1.0)),
+            Extrapolation::Allow => Some(ratio),
+            Extrapolation::Reject => None,
+        }
+    }
+
+    /// The weights to use, `None` if they are rejected. Clamping moves the
+    /// weights to the nearest ones in `[0, 1]` with the same sum, so that the
+    /// blend is not scaled.
+    pub fn apply_weights(&self, weights: &[f64]) -> Option<Vec<f64>> {
+        if weights.iter().all(|w| (0.0..=1.0).contains(w)) {
+            return Some(weights.to_vec());
+        }
+        match self {
+            Extrapolation::Clamp => Some(project_weights(weights)),
+            Extrapolation::Allow => Some(weights.to_vec()),
+            Extrapolation::Reject => None,
+        }
+    }
+}
+
+/// Euclidean projection of the weights onto those in `[0, 1]` with the same sum
+/// (clamped to the possible sums), i.e. `clamp(w - tau, 0, 1)` for the shift
+/// `tau` found by bisection.
+fn project_weights(weights: &[f64]) -> Vec<f64> {
+    let shifted = |tau: f64| weights.iter().map(move |w| (w - tau).clamp(0.0, 1.0));
+    let sum = weights.iter().sum::<f64>().clamp(0.0, weights.len() as f64);
+    let (mut lo, mut hi) = weights
+        .iter()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &w| {
+            (lo.min(w - 1.0), hi.max(w))
+        });
+    for _ in 0..PROJECTION_ITERATIONS {
+        let mid = (lo + hi) / 2.0;
+        if shifted(mid).sum::<f64>() > sum {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    shifted((lo + hi) / 2.0).collect()
+}
+
+impl FromStr for Extrapolation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "clamp" => Ok(Extrapolation::Clamp),
+            "allow" => Ok(Extrapolation::Allow),
+            "reject" => Ok(Extrapolation::Reject),
+            _ => Err(format!("Invalid extrapolation mode \"{}\"", s)),
+        }
+    }
+}
+
+impl MergedModel {
+    /// Interpolation at animation time `t`, with the ratio given by `easing`.
+    pub fn eased_interpolation(&self, t: f64, easing: &Easing) -> Model {
+        self.interpolation(easing.apply(t))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn easings() -> Vec<Easing> {
+        vec![
+            Easing::Linear,
+            Easing::SmoothStep,
+            Easing::ease_in(),
+            Easing::ease_out(),
+            Easing::ease_in_out(),
+            Easing::CubicBezier(0.2, -0.5, 0.8, 1.5),
+            Easing::Table(vec![0.0, 0.1, 0.5, 1.0]),
+        ]
+    }
+
+    #[test]
+    fn endpoints_are_kept() {
+        for easing in easings() {
+            assert!(easing.apply(0.0).abs() < 1e-6, "{:?}", easing);
+            assert!((easing.apply(1.0) - 1.0).abs() < 1e-6, "{:?}", easing);
+        }
+    }
+
+    #[test]
+    fn monotonic_curves_are_monotonic() {
+        for easing in easings() {
+            if let Easing::CubicBezier(_, y1, _, y2) = easing {
+                if !(0.0..=1.0).contains(&y1) || !(0.0..=1.0).contains(&y2) {
+                    continue;
+                }
+            }
+            let values = (0..=100).map(|i| easing.apply(i as f64 / 100.0));
+            let values = values.collect::<Vec<_>>();
+            assert!(
+                values.windows(2).all(|w| w[1] >= w[0] - EPS),
+                "{:?}",
+                easing
+            );
+        }
+    }
+
+    #[test]
+    fn curves_shape_the_ratio() {
+        assert!(Easing::ease_in().apply(0.25) < 0.25);
+        assert!(Easing::ease_out().apply(0.25) > 0.25);
+        assert!((Easing::ease_in_out().apply(0.5) - 0.5).abs() < 1e-6);
+        assert!((Easing::SmoothStep.apply(0.25) - 0.15625).abs() < EPS);
+        let table = Easing::Table(vec![0.0, 0.1, 0.5, 1.0]);
+        assert!((table.apply(0.5) - 0.3).abs() < EPS);
+        assert_eq!(Easing::Table(vec![0.7]).apply(0.2), 0.7);
+    }
+
+    #[test]
+    fn curves_continue_with_slope_one() {
+        for easing in easings() {
+            assert!((easing.apply(-0.5) + 0.5).abs() < 1e-6, "{:?}", easing);
+            assert!((easing.apply(1.25) - 1.25).abs() < 1e-6, "{:?}", easing);
+        }
+    }
+
+    #[test]
+    fn parsing() {
+        assert_eq!("linear".parse(), Ok(Easing::Linear));
+        assert_eq!("smoothstep".parse(), Ok(Easing::SmoothStep));
+        assert_eq!("ease-in-out".parse(), Ok(Easing::ease_in_out()));
+        assert_eq!(
+            "bezier:0.1, 0.2,0.3,0.4".parse(),
+            Ok(Easing::CubicBezier(0.1, 0.2, 0.3, 0.4))
+        );
+        assert_eq!(
+            "table:0,0.5,1".parse(),
+            Ok(Easing::Table(vec![0.0, 0.5, 1.0]))
+        );
+        for s in [
+            "cubic",
+            "linear:1",
+            "bezier:0.1,0.2,0.3",
+            "bezier:1.5,0,0.5,1",
+            "bezier:a,0,0.5,1",
+            "table:",
+        ] {
+            assert!(s.parse::<Easing>().is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn extrapolation_modes() {
+        for mode in [
+            Extrapolation::Clamp,
+            Extrapolation::Allow,
+            Extrapolation::Reject,
+        ] {
+            assert_eq!(mode.apply(0.3), Some(0.3));
+            assert_eq!(mode.apply(1.0), Some(1.0));
+        }
+        assert_eq!(Extrapolation::Clamp.apply(1.5), Some(1.0));
+        assert_eq!(Extrapolation::Clamp.apply(-0.5), Some(0.0));
+        assert_eq!(Extrapolation::Allow.apply(-0.5), Some(-0.5));
+        assert_eq!(Extrapolation::Reject.apply(1.5), None);
+        assert_eq!("allow".parse(), Ok(Extrapolation::Allow));
+        assert!("wrap".parse::<Extrapolation>().is_err());
+    }
+
+    fn assert_weights(weights: Option<Vec<f64>>, expected: &[f64]) {
+        let weights = weights.unwrap();
+        assert_eq!(weights.len(), expected.len());
+        for (w, e) in weights.iter().zip(expected) {
+            assert!((w - e).abs() < 1e-9, "{:?} {:?}", weights, expected);
+        }
+    }
+
+    #[test]
+    fn clamped_weights_keep_their_sum() {
+        let clamp = Extrapolation::Clamp;
+        assert_weights(clamp.apply_weights(&[1.2, -0.2]), &[1.0, 0.0]);
+        assert_weights(clamp.apply_weights(&[0.7, 0.7, -0.4]), &[0.5, 0.5, 0.0]);
+        assert_weights(clamp.apply_weights(&[-0.5, 0.5, 1.5]), &[0.0, 0.5, 1.0]);
+        // sums other than 1 are kept as far as possible
+        assert_weights(clamp.apply_weights(&[1.5, 0.5]), &[1.0, 1.0]);
+        assert_weights(clamp.apply_weights(&[1.3, 0.1, 0.1]), &[1.0, 0.25, 0.25]);
+        assert_weights(clamp.apply_weights(&[0.3, 0.2]), &[0.3, 0.2]);
+        for weights in [[2.0, -0.5, -0.5], [0.2, -0.1, 0.9], [-3.0, 5.0, -1.0]] {
+            let clamped = clamp.apply_weights(&weights).unwrap();
+            assert!(clamped.iter().all(|w| (0.0..=1.0).contains(w)));
+            assert!((clamped.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn weights_outside_may_be_allowed_or_rejected() {
+        let weights = [1.5, -0.5];
+        assert_weights(Extrapolation::Allow.apply_weights(&weights), &weights);
+        assert_eq!(Extrapolation::Reject.apply_weights(&weights), None);
+        assert_weights(
+            Extrapolation::Reject.apply_weights(&[0.5, 0.5]),
+            &[0.5, 0.5],
+        );
+    }
+}
@@ -0,0 +1,378 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::f64::consts::PI;
+
+use crate::geo::{vertex_neighbors, Vertex, EPS};
+use crate::Model;
+
+const GEODESIC_SAMPLES: usize = 32;
+const HEAT_STEPS: usize = 12;
+const EXTREMA_RING: usize = 2;
+const MIN_FEATURE_RANK: f64 = 0.8;
+const MAX_FEATURES: usize = 12;
+const MAX_MATCH_COST: f64 = 0.4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeatureKind {
+    Curvature,  // extremum of the angle-deficit Gaussian curvature
+    Extremity,  // maximum of the average geodesic distance
+    HeatKernel, // peak of the (random walk) heat kernel signature
+}
+
+#[derive(Debug, Clone)]
+pub struct Feature {
+    pub index: usize,
+    pub kind: FeatureKind,
+    pub dir: Vertex,          // unit direction from the model center
+    pub descriptor: [f64; 3], // ranks of curvature, geodesic distance and heat kernel
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FeaturePair {
+    pub index1: usize,
+    pub index2: usize,
+    pub cost: f64,
+}
+
+#[derive(PartialEq)]
+struct State(f64, usize);
+
+impl Eq for State {}
+
+impl Ord for State {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.0.partial_cmp(&self.0).unwrap_or(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for State {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Dijkstra distances along mesh edges from `source`.
+pub(crate) fn geodesic_distances(
+    verts: &[Vertex],
+    neighbors: &[Vec<usize>],
+    source: usize,
+) -> Vec<f64> {
+    let mut dist = vec![f64::INFINITY; verts.len()];
+    let mut heap = BinaryHeap::new();
+    dist[source] = 0.0;
+    heap.push(State(0.0, source));
+    while let Some(State(d, u)) = heap.pop() {
+        if d > dist[u] {
+            continue;
+        }
+        for &v in &neighbors[u] {
+            let nd = d + Vertex::dist(verts[u], verts[v]);
+            if nd < dist[v] {
+                dist[v] = nd;
+                heap.push(State(nd, v));
+            }
+        }
+    }
+    dist
+}
+
+fn gaussian_curvature(model: &Model) -> Vec<f64> {
+    let n = model.nr_verts();
+    let mut angles = vec![0.0; n];
+    let mut areas = vec![0.0; n];
+    for f in &model.faces {
+        let area = ((model.verts[f[1]] - model.verts[f[0]])
+            * (model.verts[f[2]] - model.verts[f[0]]))
+            .len()
+            / 2.0;
+        for i in 0..3 {
+            let v = model.verts[f[i]];
+            let a = model.verts[f[(i + 1) % 3]] - v;
+            let b = model.verts[f[(i + 2) % 3]] - v;
+            angles[f[i]] += Vertex::angle(a, b);
+            areas[f[i]] += area / 3.0;
+        }
+    }
+    (0..n)
+        .map(|i| {
+            if areas[i] < EPS {
+                0.0
+            } else {
+                (2.0 * PI - angles[i]) / areas[i]
+            }
+        })
+        .collect()
+}
+
+fn average_geodesic_distance(model: &Model, neighbors: &[Vec<usize>]) -> Vec<f64> {
+    let n = model.nr_verts();
+    let mut sum = vec![0.0; n];
+    let mut min_dist = vec![f64::INFINITY; n];
+    let mut source = 0;
+    // farthest point sampling of the sources
+    for _ in 0..GEODESIC_SAMPLES.min(n) {
+        let dist = geodesic_distances(&model.verts, neighbors, source);
+        for i in 0..n {
+            if dist[i].is_finite() {
+                sum[i] += dist[i];
+            }
+            min_dist[i] = min_dist[i].min(dist[i]);
+        }
+        source = (0..n)
+            .filter(|&i| min_dist[i].is_finite())
+            .max_by(|&i, &j| min_dist[i].partial_cmp(&min_dist[j]).unwrap())
+            .unwrap_or(0);
+    }
+    sum
+}
+
+fn heat_kernel_signature(model: &Model, neighbors: &[Vec<usize>]) -> Vec<f64> {
+    let n = model.nr_verts();
+    let weights = (0..n)
+        .map(|i| {
+            let w = neighbors[i]
+                .iter()
+                .map(|&j| 1.0 / Vertex::dist(model.verts[i], model.verts[j]).max(EPS))
+                .collect::<Vec<_>>();
+            let sum: f64 = w.iter().sum();
+            w.iter().map(|x| x / sum).collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    // return probability of a lazy random walk, diffused locally from every vertex
+    let mut prob = vec![0.0; n];
+    let mut next = vec![0.0; n];
+    let mut stamp = vec![usize::MAX; n];
+    let mut signature = vec![0.0; n];
+    for (x, sig) in signature.iter_mut().enumerate() {
+        let mut active = vec![x];
+        stamp[x] = x;
+        prob[x] = 1.0;
+        for _ in 0..HEAT_STEPS {
+            for a in 0..active.len() {
+                let u = active[a];
+                next[u] += prob[u] / 2.0;
+                for (k, &v) in neighbors[u].iter().enumerate() {
+                    if stamp[v] != x {
+                        stamp[v] = x;
+                        active.push(v);
+                    }
+                    next[v] += prob[u] / 2.0 * weights[u][k];
+                }
+            }
+            for &u in &active {
+                prob[u] = next[u];
+                next[u] = 0.0;
+            }
+        }
+        *sig = prob[x];
+        for &u in &active {
+            prob[u] = 0.0;
+        }
+    }
+    signature
+}
+
+fn ranks(values: &[f64]) -> Vec<f64> {
+    let n = values.len();
+    let mut order = (0..n).collect::<Vec<_>>();
+    order.sort_by(|&i, &j| values[i].partial_cmp(&values[j]).unwrap_or(Ordering::Equal));
+    let mut ranks = vec![0.0; n];
+    for (r, &i) in order.iter().enumerate() {
+        ranks[i] = r as f64 / (n.max(2) - 1) as f64;
+    }
+    ranks
+}
+
+fn local_maxima(values: &[f64], neighbors: &[Vec<usize>]) -> Vec<usize> {
+    let mut maxima = (0..values.len())
+        .filter(|&i| {
+            let mut ring = vec![i];
+            let mut frontier = vec![i];
+            for _ in 0..EXTREMA_RING {
+                let mut next = Vec::new();
+                for &u in &frontier {
+                    for &v in &neighbors[u] {
+                        if !ring.contains(&v) {
+                            ring.push(v);
+                            next.push(v);
+                        }
+                    }
+                }
+                frontier = next;
+            }
+            ring.iter()
+                .all(|&j| values[j] < values[i] || (values[j] == values[i] && j >= i))
+        })
+        .collect::<Vec<_>>();
+    maxima.sort_by(|&i, &j| values[j].partial_cmp(&values[i]).unwrap_or(Ordering::Equal));
+    maxima
+}
+
+/// Detects salient points of a model: curvature extrema, geodesic extremities
+/// and heat kernel signature peaks.
+pub fn detect_features(model: &Model) -> Vec<Feature> {
+    let neighbors = vertex_neighbors(&model.faces, model.nr_verts());
+    let center = model.center();
+
+    let curvature = gaussian_curvature(model);
+    let abs_curvature = curvature.iter().map(|k| k.abs()).collect::<Vec<_>>();
+    let agd = average_geodesic_distance(model, &neighbors);
+    let hks = heat_kernel_signature(model, &neighbors);
+    let descriptors = [ranks(&curvature), ranks(&agd), ranks(&hks)];
+
+    let mut features = Vec::<Feature>::new();
+    for &(kind, values) in &[
+        (FeatureKind::Curvature, &abs_curvature),
+        (FeatureKind::Extremity, &agd),
+        (FeatureKind::HeatKernel, &hks),
+    ] {
+        let rank = ranks(values);
+        for i in local_maxima(values, &neighbors)
+            .into_iter()
+            .filter(|&i| rank[i] >= MIN_FEATURE_RANK)
+            .take(MAX_FEATURES)
+        {
+            if features.iter().any(|f| f.index == i) {
+                continue;
+            }
+            features.push(Feature {
+                index: i,
+                kind,
+                dir: (model.verts[i] - center).unit(),
+                descriptor: [descriptors[0][i], descriptors[1][i], descriptors[2][i]],
+            });
+        }
+    }
+    features
+}
+
+/// Suggests vertex pairs between two models by matching their salient points.
+pub fn match_features(model1: &Model, model2: &Model) -> Vec<FeaturePair> {
+    let features1 = detect_features(model1);
+    let features2 = detect_features(model2);
+
+    let mut candidates = Vec::new();
+    for f1 in &features1 {
+        for f2 in &features2 {
+            let desc = (0..3)
+                .map(|k| (f1.descriptor[k] - f2.descriptor[k]).powi(2))
+                .sum::<f64>()
+                .sqrt()
+                / 3f64.sqrt();
+            let angle = Vertex::angle(f1.dir, f2.dir) / PI;
+            let cost = (desc + angle) / 2.0;
+            if cost < MAX_MATCH_COST {
+                candidates.push(FeaturePair {
+                    index1: f1.index,
+                    index2: f2.index,
+                    cost,
+                });
+            }
+        }
+    }
+    candidates.sort_by(|a, b| a.cost.partial_cmp(&b.cost).unwrap_or(Ordering::Equal));
+
+    let mut pairs = Vec::<FeaturePair>::new();
+    for c in candidates {
+        if pairs
+            .iter()
+            .all(|p| p.index1 != c.index1 && p.index2 != c.index2)
+        {
+            pairs.push(c);
+        }
+    }
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::ProjectionModel;
+    use crate::testing::{cube, octahedron, sample};
+    use crate::{merge_all, merge_with_pairs, Config, MergeError};
+
+    #[test]
+    fn geodesic_distances_follow_edges() {
+        let model = cube();
+        let neighbors = vertex_neighbors(&model.faces, model.nr_verts());
+        let dist = geodesic_distances(&model.verts, &neighbors, 0);
+        assert_eq!(dist[0], 0.0);
+        assert!((dist[1] - 2.0).abs() < EPS);
+        assert!((dist[2] - 8f64.sqrt()).abs() < EPS);
+        assert!((dist[6] - (2.0 + 8f64.sqrt())).abs() < EPS);
+    }
+
+    #[test]
+    fn features_are_distinct_vertices() {
+        let model = sample("egg.obj");
+        let features = detect_features(&model);
+        assert!(!features.is_empty());
+        for (k, f) in features.iter().enumerate() {
+            assert!(f.index < model.nr_verts());
+            assert!(features[..k].iter().all(|g| g.index != f.index));
+            assert!((f.dir.len() - 1.0).abs() < EPS);
+            assert!(f.descriptor.iter().all(|d| (0.0..=1.0).contains(d)));
+        }
+    }
+
+    #[test]
+    fn same_models_match_same_vertices() {
+        let model = sample("egg.obj");
+        let pairs = match_features(&model, &model);
+        assert!(!pairs.is_empty());
+        for p in &pairs {
+            assert_eq!(p.index1, p.index2);
+            assert!(p.cost.abs() < EPS);
+        }
+    }
+
+    #[test]
+    fn align_to_overlays_pairs() {
+        // the second octahedron is turned a quarter around z
+        let model1 = ProjectionModel::new(octahedron());
+        let mut verts = octahedron().verts;
+        for v in &mut verts {
+            *v = Vertex::new(-v.y, v.x, v.z);
+        }
+        let mut model2 = ProjectionModel::new(Model::new(verts, octahedron().faces));
+        let pairs = [(0, 3), (2, 0), (4, 4)]
+            .iter()
+            .map(|&(index1, index2)| FeaturePair {
+                index1,
+                index2,
+                cost: 0.0,
+            })
+            .collect::<Vec<_>>();
+        model2.align_to(&model1, &pairs);
+        for p in &pairs {
+            let (a, b) = (
+                model1.sphere_verts()[p.index1],
+                model2.sphere_verts()[p.index2],
+            );
+            assert!(Vertex::angle(a, b) < 1e-6);
+        }
+    }
+
+    #[test]
+    fn out_of_range_pairs_are_rejected() {
+        let pair = |index1, index2| FeaturePair {
+            index1,
+            index2,
+            cost: 0.0,
+        };
+        let pairs = [pair(0, 0), pair(1, 8)];
+        let result = merge_with_pairs(cube(), cube(), &pairs, Config::default());
+        assert_eq!(result.err(), Some(MergeError::InvalidPair(1)));
+        let result = merge_with_pairs(octahedron(), cube(), &[pair(6, 0)], Config::default());
+        assert_eq!(result.err(), Some(MergeError::InvalidPair(0)));
+
+        let pairs = [vec![pair(0, 0)], vec![pair(7, 6)]];
+        let result = merge_all(
+            vec![cube(), cube(), octahedron()],
+            &pairs,
+            Config::default(),
+        );
+        assert_eq!(result.err(), Some(MergeError::InvalidPair(0)));
+    }
+}
@@ -0,0 +1,223 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::feature::geodesic_distances;
+use crate::geo::{vertex_neighbors, Vertex, EPS};
+use crate::MergedModel;
+
+/// Delay of each merged vertex, measured on the first model, deciding when it
+/// starts to morph. Delays are normalized to `[0, 1]`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RatioField {
+    Direction(Vertex), // sweep along the direction
+    Distance(Vertex),  // euclidean distance from the seed point
+    Geodesic(Vertex),  // distance along edges from the vertex nearest to the seed point
+    Values(Vec<f64>),  // explicit delay of each merged vertex
+}
+
+/// A number of values, one per merged vertex or model, that does not match
+/// the number expected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CountMismatch {
+    pub what: &'static str,
+    pub expected: usize,
+    pub found: usize,
+}
+
+impl fmt::Display for CountMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Expected {} {}, got {}",
+            self.expected, self.what, self.found
+        )
+    }
+}
+
+impl std::error::Error for CountMismatch {}
+
+fn normalize(values: Vec<f64>) -> Vec<f64> {
+    let finite = values.iter().copied().filter(|d| d.is_finite());
+    let min = finite.clone().fold(f64::INFINITY, f64::min);
+    let max = finite.fold(f64::NEG_INFINITY, f64::max);
+    if max - min < EPS {
+        return vec![0.0; values.len()];
+    }
+    values
+        .iter()
+        .map(|d| {
+            if d.is_finite() {
+                (d - min) / (max - min)
+            } else {
+                1.0
+            }
+        })
+        .collect()
+}
+
+impl RatioField {
+    /// Fails if explicit delays are not given for each merged vertex.
+    pub fn delays(&self, model: &MergedModel) -> Result<Vec<f64>, CountMismatch> {
+        let verts = model.vert_pairs.iter().map(|p| p.0).collect::<Vec<_>>();
+        Ok(match self {
+            RatioField::Direction(dir) => normalize(verts.iter().map(|v| v.dot(*dir)).collect()),
+            RatioField::Distance(seed) => {
+                normalize(verts.iter().map(|&v| Vertex::dist(v, *seed)).collect())
+            }
+            RatioField::Geodesic(seed) => {
+                let source = (0..verts.len()).min_by(|&i, &j| {
+                    Vertex::dist(verts[i], *seed).total_cmp(&Vertex::dist(verts[j], *seed))
+                });
+                match source {
+                    Some(source) => {
+                        let neighbors = vertex_neighbors(&model.faces, verts.len());
+                        normalize(geodesic_distances(&verts, &neighbors, source))
+                    }
+                    None => Vec::new(),
+                }
+            }
+            RatioField::Values(values) => {
+                if values.len() != verts.len() {
+                    return Err(CountMismatch {
+                        what: "delays",
+                        expected: verts.len(),
+                        found: values.len(),
+                    });
+                }
+                values.iter().map(|d| d.clamp(0.0, 1.0)).collect()
+            }
+        })
+    }
+
+    /// Local ratio of each merged vertex at time `t`. Each vertex morphs during
+    /// a window of length `1 - spread`, starting at `spread` times its delay.
+    pub fn ratios(
+        &self,
+        model: &MergedModel,
+        t: f64,
+        spread: f64,
+    ) -> Result<Vec<f64>, CountMismatch> {
+        let spread = spread.clamp(0.0, 1.0 - EPS);
+        Ok(self
+            .delays(model)?
+            .iter()
+            .map(|d| ((t - spread * d) / (1.0 - spread)).clamp(0.0, 1.0))
+            .collect())
+    }
+}
+
+impl FromStr for RatioField {
+    type Err = String;
+
+    /// Parses `dir:<x>,<y>,<z>`, `seed:<x>,<y>,<z>` or `geodesic:<x>,<y>,<z>`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || format!("Invalid ratio field \"{}\"", s);
+        let i = s.find(':').ok_or_else(err)?;
+        let values = s[i + 1..]
+            .split(',')
+            .map(|v| v.trim().parse::<f64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| err())?;
+        if values.len() != 3 {
+            return Err(err());
+        }
+        let v = Vertex::new(values[0], values[1], values[2]);
+        match &s[..i] {
+            "dir" => Ok(RatioField::Direction(v)),
+            "seed" => Ok(RatioField::Distance(v)),
+            "geodesic" => Ok(RatioField::Geodesic(v)),
+            _ => Err(err()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::cube;
+
+    fn merged_cube() -> MergedModel {
+        let cube = cube();
+        let vert_pairs = cube.verts.iter().map(|&v| (v, v)).collect();
+        MergedModel::new(vert_pairs, cube.faces)
+    }
+
+    fn corner() -> Vertex {
+        Vertex::new(-1.0, -1.0, -1.0)
+    }
+
+    #[test]
+    fn delays_are_normalized() {
+        let model = merged_cube();
+        for field in [
+            RatioField::Direction(Vertex::new(1.0, 1.0, 1.0)),
+            RatioField::Distance(corner()),
+            RatioField::Geodesic(corner()),
+        ] {
+            let delays = field.delays(&model).unwrap();
+            assert_eq!(delays.len(), 8);
+            assert!(delays[0].abs() < EPS, "{:?}", field);
+            assert!((delays[6] - 1.0).abs() < EPS, "{:?}", field);
+            assert!(delays.iter().all(|d| (0.0..=1.0).contains(d)));
+        }
+        let delays = RatioField::Geodesic(corner()).delays(&model).unwrap();
+        assert!((delays[1] - 2.0 / (2.0 + 8f64.sqrt())).abs() < EPS);
+        let delays = RatioField::Direction(Vertex::new(0.0, 0.0, 1.0))
+            .delays(&model)
+            .unwrap();
+        assert_eq!(delays, [0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn explicit_delays_are_checked() {
+        let model = merged_cube();
+        let values = vec![-1.0, 0.0, 0.5, 1.0, 2.0, 0.0, 0.0, 0.0];
+        let delays = RatioField::Values(values).delays(&model).unwrap();
+        assert_eq!(&delays[..5], [0.0, 0.0, 0.5, 1.0, 1.0]);
+        assert_eq!(
+            RatioField::Values(vec![0.0; 7]).ratios(&model, 0.5, 0.5),
+            Err(CountMismatch {
+                what: "delays",
+                expected: 8,
+                found: 7
+            })
+        );
+    }
+
+    #[test]
+    fn geodesic_seed_may_be_nan() {
+        let field = RatioField::Geodesic(Vertex::new(f64::NAN, 0.0, 0.0));
+        assert_eq!(field.delays(&merged_cube()).unwrap().len(), 8);
+    }
+
+    #[test]
+    fn ratios_follow_delays() {
+        let model = merged_cube();
+        let field = RatioField::Direction(Vertex::new(0.0, 0.0, 1.0));
+        assert_eq!(field.ratios(&model, 0.0, 0.5).unwrap(), [0.0; 8]);
+        assert_eq!(field.ratios(&model, 1.0, 0.5).unwrap(), [1.0; 8]);
+        assert_eq!(field.ratios(&model, 0.3, 0.0).unwrap(), [0.3; 8]);
+        let ratios = field.ratios(&model, 0.5, 0.5).unwrap();
+        assert_eq!(ratios[0], 1.0);
+        assert_eq!(ratios[4], 0.0);
+    }
+
+    #[test]
+    fn parsing() {
+        assert_eq!(
+            "dir:0,0,1".parse(),
+            Ok(RatioField::Direction(Vertex::new(0.0, 0.0, 1.0)))
+        );
+        assert_eq!(
+            "seed:1, 2, 3".parse(),
+            Ok(RatioField::Distance(Vertex::new(1.0, 2.0, 3.0)))
+        );
+        assert_eq!(
+            "geodesic:0,-1,0".parse(),
+            Ok(RatioField::Geodesic(Vertex::new(0.0, -1.0, 0.0)))
+        );
+        for s in ["dir", "dir:0,1", "dir:0,1,2,3", "seed:a,0,0", "wave:0,0,1"] {
+            assert!(s.parse::<RatioField>().is_err(), "{}", s);
+        }
+    }
+}
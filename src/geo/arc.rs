@@ -42,6 +42,16 @@ impl Arc {
         }
     }
 
+    /// The point inside the arc closest to `v`, at the same radius as the arc.
+    pub fn closest(&self, v: Vertex) -> Option<Vertex> {
+        let norm = (self.a * self.b).unit();
+        let p = v - norm * v.dot(norm);
+        if p.len() < EPS || !self.planar_contains(p) {
+            return None;
+        }
+        Some(p * (self.a.len() / p.len()))
+    }
+
     pub fn intersect(a: &Arc, b: &Arc) -> ArcIntersectionResult {
         let ab = a.a * a.b;
         let ba = -ab;
@@ -54,7 +64,7 @@ impl Arc {
             }
 
             let mut res = Vec::new();
-            for (v, id) in vec![(a.a, a.a_id), (a.b, a.b_id)] {
+            for (v, id) in [(a.a, a.a_id), (a.b, a.b_id)] {
                 if v == b.a {
                     res.push((id, 0.0))
                 } else if v == b.b {
@@ -110,7 +120,7 @@ impl Arc {
             return T1(a.b_id, k);
         } else if let Some(k) = a.contains(b.a) {
             return T2(b.a_id, k);
-        } else if let Some(k) = a.contains(b.a) {
+        } else if let Some(k) = a.contains(b.b) {
             return T2(b.b_id, k);
         }
 
@@ -138,3 +148,64 @@ impl Arc {
         N
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quarter() -> Arc {
+        Arc::new(Vertex::new(1.0, 0.0, 0.0), Vertex::new(0.0, 1.0, 0.0), 0, 1)
+    }
+
+    #[test]
+    fn first_arc_touching_second() {
+        let a = Arc::new(
+            Vertex::new(0.0, 0.0, 1.0),
+            Vertex::new(1.0, 1.0, 0.0).unit(),
+            2,
+            3,
+        );
+        match Arc::intersect(&a, &quarter()) {
+            T1(3, k) => assert!((k - 0.5).abs() < EPS),
+            res => panic!("{:?}", res),
+        }
+    }
+
+    #[test]
+    fn second_arc_touching_first_at_either_endpoint() {
+        let mid = Vertex::new(1.0, 1.0, 0.0).unit();
+        let pole = Vertex::new(0.0, 0.0, 1.0);
+        match Arc::intersect(&quarter(), &Arc::new(mid, pole, 2, 3)) {
+            T2(2, k) => assert!((k - 0.5).abs() < EPS),
+            res => panic!("{:?}", res),
+        }
+        match Arc::intersect(&quarter(), &Arc::new(pole, mid, 2, 3)) {
+            T2(3, k) => assert!((k - 0.5).abs() < EPS),
+            res => panic!("{:?}", res),
+        }
+    }
+
+    #[test]
+    fn crossing_and_disjoint_arcs() {
+        let cross = Arc::new(
+            Vertex::new(1.0, 1.0, 1.0).unit(),
+            Vertex::new(1.0, 1.0, -1.0).unit(),
+            2,
+            3,
+        );
+        match Arc::intersect(&quarter(), &cross) {
+            X(v, k) => {
+                assert!(Vertex::angle(v, Vertex::new(1.0, 1.0, 0.0)) < 1e-6);
+                assert!((k - 0.5).abs() < 1e-6);
+            }
+            res => panic!("{:?}", res),
+        }
+        let far = Arc::new(
+            Vertex::new(-1.0, 0.0, 1.0).unit(),
+            Vertex::new(0.0, -1.0, 1.0).unit(),
+            2,
+            3,
+        );
+        assert!(matches!(Arc::intersect(&quarter(), &far), N));
+    }
+}
@@ -2,7 +2,7 @@ use super::{Vertex, EPS};
 
 pub type Face = Vec<usize>;
 
-pub fn check_order(face: &Face, verts: &Vec<Vertex>, center: Vertex) -> bool {
+pub fn check_order(face: &[usize], verts: &[Vertex], center: Vertex) -> bool {
     let n = face.len();
     if n < 3 {
         return false;
@@ -11,8 +11,28 @@ pub fn check_order(face: &Face, verts: &Vec<Vertex>, center: Vertex) -> bool {
     Vertex::det(face[0] - center, face[1] - center, face[2] - center) > EPS
 }
 
-pub fn adjust_order(face: &mut Face, verts: &Vec<Vertex>, center: Vertex) {
-    if !check_order(face, verts, center) {
+/// Reverses the face if it is not counterclockwise around `center`, returning
+/// whether it did.
+pub fn adjust_order(face: &mut Face, verts: &[Vertex], center: Vertex) -> bool {
+    let reverse = !check_order(face, verts, center);
+    if reverse {
         face.reverse();
     }
+    reverse
+}
+
+pub fn vertex_neighbors(faces: &[Face], nr_verts: usize) -> Vec<Vec<usize>> {
+    let mut neighbors = vec![Vec::new(); nr_verts];
+    for f in faces {
+        for i in 0..f.len() {
+            let (a, b) = (f[i], f[(i + 1) % f.len()]);
+            if !neighbors[a].contains(&b) {
+                neighbors[a].push(b);
+            }
+            if !neighbors[b].contains(&a) {
+                neighbors[b].push(a);
+            }
+        }
+    }
+    neighbors
 }
@@ -0,0 +1,190 @@
+use super::{Vertex, EPS};
+
+use std::ops;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Matrix3 {
+    pub m: [[f64; 3]; 3],
+}
+
+impl Matrix3 {
+    pub fn new(m: [[f64; 3]; 3]) -> Self {
+        Self { m }
+    }
+
+    pub fn zero() -> Self {
+        Self::new([[0.0; 3]; 3])
+    }
+
+    pub fn identity() -> Self {
+        Self::new([[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]])
+    }
+
+    pub fn from_cols(a: Vertex, b: Vertex, c: Vertex) -> Self {
+        Self::new([[a.x, b.x, c.x], [a.y, b.y, c.y], [a.z, b.z, c.z]])
+    }
+
+    /// `a * b^T`
+    pub fn outer(a: Vertex, b: Vertex) -> Self {
+        Self::new([
+            [a.x * b.x, a.x * b.y, a.x * b.z],
+            [a.y * b.x, a.y * b.y, a.y * b.z],
+            [a.z * b.x, a.z * b.y, a.z * b.z],
+        ])
+    }
+
+    pub fn col(&self, j: usize) -> Vertex {
+        Vertex::new(self.m[0][j], self.m[1][j], self.m[2][j])
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut t = Self::zero();
+        for i in 0..3 {
+            for j in 0..3 {
+                t.m[i][j] = self.m[j][i];
+            }
+        }
+        t
+    }
+
+    pub fn det(&self) -> f64 {
+        Vertex::det(self.col(0), self.col(1), self.col(2))
+    }
+
+    pub fn inverse(&self) -> Option<Self> {
+        let det = self.det();
+        if det.abs() < EPS {
+            return None;
+        }
+        let (a, b, c) = (self.col(0), self.col(1), self.col(2));
+        // rows of the inverse are the cross products of the columns
+        let (r0, r1, r2) = (b * c, c * a, a * b);
+        Some(Self::new([
+            [r0.x / det, r0.y / det, r0.z / det],
+            [r1.x / det, r1.y / det, r1.z / det],
+            [r2.x / det, r2.y / det, r2.z / det],
+        ]))
+    }
+
+    /// Eigenvalues and eigenvectors (as columns) of a symmetric matrix, using
+    /// Jacobi rotations. Eigenvalues are sorted in descending order.
+    pub fn symmetric_eigen(&self) -> ([f64; 3], Self) {
+        let mut a = self.m;
+        let mut v = Self::identity().m;
+        for _ in 0..50 {
+            let off = a[0][1].abs() + a[0][2].abs() + a[1][2].abs();
+            if off < EPS {
+                break;
+            }
+            for &(p, q) in &[(0, 1), (0, 2), (1, 2)] {
+                if a[p][q].abs() < EPS * EPS {
+                    continue;
+                }
+                let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                let c = 1.0 / (t * t + 1.0).sqrt();
+                let s = t * c;
+                for row in &mut a {
+                    let (akp, akq) = (row[p], row[q]);
+                    row[p] = c * akp - s * akq;
+                    row[q] = s * akp + c * akq;
+                }
+                let (rp, rq) = (a[p], a[q]);
+                for k in 0..3 {
+                    a[p][k] = c * rp[k] - s * rq[k];
+                    a[q][k] = s * rp[k] + c * rq[k];
+                }
+                for row in &mut v {
+                    let (vkp, vkq) = (row[p], row[q]);
+                    row[p] = c * vkp - s * vkq;
+                    row[q] = s * vkp + c * vkq;
+                }
+            }
+        }
+
+        let mut order = [0, 1, 2];
+        order.sort_by(|&i, &j| a[j][j].partial_cmp(&a[i][i]).unwrap());
+        let vecs = Self::new(v);
+        let vals = [
+            a[order[0]][order[0]],
+            a[order[1]][order[1]],
+            a[order[2]][order[2]],
+        ];
+        let vecs = Self::from_cols(vecs.col(order[0]), vecs.col(order[1]), vecs.col(order[2]));
+        (vals, vecs)
+    }
+
+    /// The rotation `R` closest to this matrix, i.e. maximizing `trace(R^T * M)`.
+    /// Returns `None` if the matrix has rank less than 2.
+    pub fn nearest_rotation(&self) -> Option<Self> {
+        let (vals, v) = (self.transpose() * *self).symmetric_eigen();
+        if vals[1] < EPS {
+            return None;
+        }
+        let u1 = (*self * v.col(0)).unit();
+        let u2 = (*self * v.col(1)).unit();
+        let u = Self::from_cols(u1, u2, u1 * u2);
+        let mut s = Self::identity();
+        s.m[2][2] = v.det().signum();
+        Some(u * s * v.transpose())
+    }
+}
+
+impl ops::Add for Matrix3 {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        let mut res = self;
+        for i in 0..3 {
+            for j in 0..3 {
+                res.m[i][j] += rhs.m[i][j];
+            }
+        }
+        res
+    }
+}
+
+impl ops::Mul<f64> for Matrix3 {
+    type Output = Self;
+
+    fn mul(self, rhs: f64) -> Self::Output {
+        let mut res = self;
+        for row in &mut res.m {
+            for x in row {
+                *x *= rhs;
+            }
+        }
+        res
+    }
+}
+
+impl ops::Mul<Vertex> for Matrix3 {
+    type Output = Vertex;
+
+    fn mul(self, rhs: Vertex) -> Self::Output {
+        let row = |i: usize| Vertex::new(self.m[i][0], self.m[i][1], self.m[i][2]).dot(rhs);
+        Vertex::new(row(0), row(1), row(2))
+    }
+}
+
+impl ops::Mul<Matrix3> for Matrix3 {
+    type Output = Self;
+
+    fn mul(self, rhs: Matrix3) -> Self::Output {
+        let mut res = Self::zero();
+        for i in 0..3 {
+            for j in 0..3 {
+                for k in 0..3 {
+                    res.m[i][j] += self.m[i][k] * rhs.m[k][j];
+                }
+            }
+        }
+        res
+    }
+}
+
+impl ops::AddAssign for Matrix3 {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
@@ -1,11 +1,17 @@
 mod arc;
 mod face;
+mod matrix;
+mod quaternion;
 mod triangle;
+mod triangulate;
 mod vertex;
 
 pub const EPS: f64 = 1e-9;
 
 pub use arc::{Arc, ArcIntersectionResult};
-pub use face::{adjust_order, check_order, Face};
+pub use face::{adjust_order, vertex_neighbors, Face};
+pub use matrix::Matrix3;
+pub use quaternion::Quaternion;
 pub use triangle::Triangle;
+pub use triangulate::triangulate;
 pub use vertex::Vertex;
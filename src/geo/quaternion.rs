@@ -0,0 +1,158 @@
+use super::{Matrix3, Vertex, EPS};
+
+/// Unit quaternion `w + v` representing a rotation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Quaternion {
+    pub w: f64,
+    pub v: Vertex,
+}
+
+impl Quaternion {
+    pub fn new(w: f64, v: Vertex) -> Self {
+        Self { w, v }
+    }
+
+    pub fn identity() -> Self {
+        Self::new(1.0, Vertex::new(0.0, 0.0, 0.0))
+    }
+
+    pub fn dot(self, rhs: Self) -> f64 {
+        self.w * rhs.w + self.v.dot(rhs.v)
+    }
+
+    fn normalize(self) -> Self {
+        let len = self.dot(self).sqrt();
+        Self::new(self.w / len, self.v / len)
+    }
+
+    pub fn from_matrix(r: Matrix3) -> Self {
+        let m = r.m;
+        let trace = m[0][0] + m[1][1] + m[2][2];
+        let q = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            Self::new(
+                s / 4.0,
+                Vertex::new(m[2][1] - m[1][2], m[0][2] - m[2][0], m[1][0] - m[0][1]) / s,
+            )
+        } else if m[0][0] > m[1][1] && m[0][0] > m[2][2] {
+            let s = (1.0 + m[0][0] - m[1][1] - m[2][2]).sqrt() * 2.0;
+            Self::new(
+                (m[2][1] - m[1][2]) / s,
+                Vertex::new(s / 4.0, (m[0][1] + m[1][0]) / s, (m[0][2] + m[2][0]) / s),
+            )
+        } else if m[1][1] > m[2][2] {
+            let s = (1.0 + m[1][1] - m[0][0] - m[2][2]).sqrt() * 2.0;
+            Self::new(
+                (m[0][2] - m[2][0]) / s,
+                Vertex::new((m[0][1] + m[1][0]) / s, s / 4.0, (m[1][2] + m[2][1]) / s),
+            )
+        } else {
+            let s = (1.0 + m[2][2] - m[0][0] - m[1][1]).sqrt() * 2.0;
+            Self::new(
+                (m[1][0] - m[0][1]) / s,
+                Vertex::new((m[0][2] + m[2][0]) / s, (m[1][2] + m[2][1]) / s, s / 4.0),
+            )
+        };
+        q.normalize()
+    }
+
+    pub fn to_matrix(self) -> Matrix3 {
+        let (w, x, y, z) = (self.w, self.v.x, self.v.y, self.v.z);
+        Matrix3::new([
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - w * z),
+                2.0 * (x * z + w * y),
+            ],
+            [
+                2.0 * (x * y + w * z),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - w * x),
+            ],
+            [
+                2.0 * (x * z - w * y),
+                2.0 * (y * z + w * x),
+                1.0 - 2.0 * (x * x + y * y),
+            ],
+        ])
+    }
+
+    /// Spherical linear interpolation along the shorter arc.
+    pub fn slerp(a: Self, b: Self, t: f64) -> Self {
+        let mut b = b;
+        let mut cos = a.dot(b);
+        if cos < 0.0 {
+            b = Self::new(-b.w, -b.v);
+            cos = -cos;
+        }
+        let (ka, kb) = if cos > 1.0 - EPS {
+            (1.0 - t, t)
+        } else {
+            let theta = cos.acos();
+            let sin = theta.sin();
+            (((1.0 - t) * theta).sin() / sin, (t * theta).sin() / sin)
+        };
+        Self::new(a.w * ka + b.w * kb, a.v * ka + b.v * kb).normalize()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    fn rotation(axis: Vertex, angle: f64) -> Quaternion {
+        Quaternion::new((angle / 2.0).cos(), axis.unit() * (angle / 2.0).sin())
+    }
+
+    fn assert_same_rotation(a: Quaternion, b: Quaternion) {
+        assert!(a.dot(b).abs() > 1.0 - 1e-9, "{:?} {:?}", a, b);
+    }
+
+    #[test]
+    fn matrix_round_trip() {
+        // the large angles take the other branches of from_matrix
+        for (axis, angle) in [
+            (Vertex::new(0.0, 0.0, 1.0), 0.3),
+            (Vertex::new(1.0, 2.0, 3.0), 1.2),
+            (Vertex::new(1.0, 0.0, 0.0), PI),
+            (Vertex::new(0.0, 1.0, 0.1), 3.0),
+            (Vertex::new(0.1, 0.2, 1.0), -3.0),
+        ] {
+            let q = rotation(axis, angle);
+            let r = q.to_matrix();
+            assert!((r.det() - 1.0).abs() < 1e-9);
+            let rtr = r.transpose() * r;
+            for i in 0..3 {
+                for j in 0..3 {
+                    let id = if i == j { 1.0 } else { 0.0 };
+                    assert!((rtr.m[i][j] - id).abs() < 1e-9);
+                }
+            }
+            assert_same_rotation(Quaternion::from_matrix(r), q);
+        }
+    }
+
+    #[test]
+    fn matrix_rotates_around_axis() {
+        let r = rotation(Vertex::new(0.0, 0.0, 1.0), PI / 2.0).to_matrix();
+        let v = r * Vertex::new(1.0, 0.0, 0.0);
+        assert!(Vertex::dist(v, Vertex::new(0.0, 1.0, 0.0)) < 1e-9);
+    }
+
+    #[test]
+    fn slerp_interpolates_angles() {
+        let axis = Vertex::new(1.0, 1.0, 0.0);
+        let a = Quaternion::identity();
+        let b = rotation(axis, PI / 2.0);
+        assert_same_rotation(Quaternion::slerp(a, b, 0.0), a);
+        assert_same_rotation(Quaternion::slerp(a, b, 1.0), b);
+        assert_same_rotation(Quaternion::slerp(a, b, 0.5), rotation(axis, PI / 4.0));
+        assert_same_rotation(Quaternion::slerp(a, b, 0.25), rotation(axis, PI / 8.0));
+        // both signs of a quaternion are the same rotation
+        let minus_b = Quaternion::new(-b.w, -b.v);
+        assert_same_rotation(Quaternion::slerp(a, minus_b, 0.5), rotation(axis, PI / 4.0));
+        assert_same_rotation(Quaternion::slerp(b, b, 0.3), b);
+    }
+}
@@ -22,7 +22,23 @@ impl Triangle {
         let c = self.c - v;
         let area = self.norm().len();
         let sum = (a * b).len() + (b * c).len() + (c * a).len();
-        return (area - sum).abs() < EPS;
+        (area - sum).abs() < EPS
+    }
+
+    /// Barycentric coordinates of the point where the ray from the origin
+    /// through `v` meets the triangle's plane, `None` if it does not.
+    pub fn central_barycentric(&self, v: Vertex) -> Option<[f64; 3]> {
+        let (a, b, c) = (self.a, self.b, self.c);
+        let w = [
+            Vertex::det(v, b, c),
+            Vertex::det(a, v, c),
+            Vertex::det(a, b, v),
+        ];
+        let sum = w[0] + w[1] + w[2];
+        if sum.abs() < EPS || Vertex::det(a, b, c) * sum < 0.0 {
+            return None;
+        }
+        Some([w[0] / sum, w[1] / sum, w[2] / sum])
     }
 
     pub fn intersect(&self, a: Vertex, b: Vertex) -> Option<Vertex> {
@@ -40,4 +56,91 @@ impl Triangle {
             None
         }
     }
+
+    /// Whether the segment `pq` passes through the interior of the triangle.
+    pub fn crosses(&self, p: Vertex, q: Vertex) -> bool {
+        let (a, b, c) = (self.a, self.b, self.c);
+        let (dp, dq) = (
+            Vertex::det(b - a, c - a, p - a),
+            Vertex::det(b - a, c - a, q - a),
+        );
+        if dp * dq >= 0.0 {
+            return false;
+        }
+        let s1 = Vertex::det(a - p, b - p, q - p);
+        let s2 = Vertex::det(b - p, c - p, q - p);
+        let s3 = Vertex::det(c - p, a - p, q - p);
+        (s1 > 0.0 && s2 > 0.0 && s3 > 0.0) || (s1 < 0.0 && s2 < 0.0 && s3 < 0.0)
+    }
+
+    /// Whether the triangles cross each other, coplanar overlaps excluded.
+    pub fn intersects(&self, other: &Triangle) -> bool {
+        let edges = |t: &Triangle| [(t.a, t.b), (t.b, t.c), (t.c, t.a)];
+        edges(other).iter().any(|&(p, q)| self.crosses(p, q))
+            || edges(self).iter().any(|&(p, q)| other.crosses(p, q))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit() -> Triangle {
+        Triangle::new(
+            Vertex::new(0.0, 0.0, 0.0),
+            Vertex::new(1.0, 0.0, 0.0),
+            Vertex::new(0.0, 1.0, 0.0),
+        )
+    }
+
+    #[test]
+    fn segments_crossing_the_interior() {
+        let t = unit();
+        let (p, q) = (Vertex::new(0.2, 0.2, -1.0), Vertex::new(0.2, 0.2, 1.0));
+        assert!(t.crosses(p, q));
+        assert!(t.crosses(q, p));
+        // outside, not reaching the plane, and through an edge
+        assert!(!t.crosses(Vertex::new(0.8, 0.8, -1.0), Vertex::new(0.8, 0.8, 1.0)));
+        assert!(!t.crosses(p, Vertex::new(0.2, 0.2, -0.5)));
+        assert!(!t.crosses(Vertex::new(0.5, 0.0, -1.0), Vertex::new(0.5, 0.0, 1.0)));
+    }
+
+    #[test]
+    fn triangles_crossing_each_other() {
+        let t = unit();
+        let through = Triangle::new(
+            Vertex::new(0.2, 0.2, -1.0),
+            Vertex::new(0.2, 0.2, 1.0),
+            Vertex::new(2.0, 2.0, 0.0),
+        );
+        assert!(t.intersects(&through));
+        assert!(through.intersects(&t));
+        let above = Triangle::new(
+            Vertex::new(0.0, 0.0, 1.0),
+            Vertex::new(1.0, 0.0, 1.0),
+            Vertex::new(0.0, 1.0, 1.0),
+        );
+        assert!(!t.intersects(&above));
+        // coplanar overlaps are not crossings
+        let shifted = Triangle::new(
+            Vertex::new(0.2, 0.2, 0.0),
+            Vertex::new(1.2, 0.2, 0.0),
+            Vertex::new(0.2, 1.2, 0.0),
+        );
+        assert!(!t.intersects(&shifted));
+    }
+
+    #[test]
+    fn central_barycentric_coordinates() {
+        let t = Triangle::new(
+            Vertex::new(1.0, 0.0, 1.0),
+            Vertex::new(0.0, 1.0, 1.0),
+            Vertex::new(-1.0, -1.0, 1.0),
+        );
+        let w = t.central_barycentric(Vertex::new(0.0, 0.0, 2.0)).unwrap();
+        assert!((w[0] - 1.0 / 3.0).abs() < EPS && (w[1] - 1.0 / 3.0).abs() < EPS);
+        let w = t.central_barycentric(Vertex::new(2.0, 0.0, 2.0)).unwrap();
+        assert!((w[0] - 1.0).abs() < EPS);
+        assert!(t.central_barycentric(Vertex::new(0.0, 0.0, -1.0)).is_none());
+    }
 }
@@ -0,0 +1,322 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::{Face, Vertex, EPS};
+
+const MAX_FLIP_ROUNDS: usize = 10;
+
+/// Orientation of the triangle `abc` seen from the origin, normalized to be
+/// independent of the distances to the origin.
+fn orient(verts: &[Vertex], a: usize, b: usize, c: usize) -> f64 {
+    let (a, b, c) = (verts[a], verts[b], verts[c]);
+    Vertex::det(a, b, c) / (a.len() * b.len() * c.len()).max(EPS)
+}
+
+/// Whether `d` lies inside the circumcircle of the spherical triangle `abc`.
+fn in_circle(verts: &[Vertex], a: usize, b: usize, c: usize, d: usize) -> bool {
+    let (a, b, c, d) = (verts[a], verts[b], verts[c], verts[d]);
+    let r3 = a.len() * b.len() * c.len();
+    Vertex::det(b - a, c - a, d - a) / r3.max(EPS) > EPS
+}
+
+fn min_angle(verts: &[Vertex], a: usize, b: usize, c: usize) -> f64 {
+    let (a, b, c) = (verts[a], verts[b], verts[c]);
+    Vertex::angle(b - a, c - a)
+        .min(Vertex::angle(c - b, a - b))
+        .min(Vertex::angle(a - c, b - c))
+}
+
+fn nr_inverted(surfaces: &[&[Vertex]], tris: &[[usize; 3]]) -> usize {
+    surfaces
+        .iter()
+        .map(|s| {
+            tris.iter()
+                .filter(|t| orient(s, t[0], t[1], t[2]) < -EPS)
+                .count()
+        })
+        .sum()
+}
+
+/// Removes repeated vertices and spikes left by dangling edges.
+fn cleanup(poly: &mut Face) {
+    loop {
+        let m = poly.len();
+        if m < 3 {
+            return;
+        }
+        if let Some(i) = (0..m).find(|&i| poly[i] == poly[(i + 1) % m]) {
+            poly.remove(i);
+        } else if let Some(i) = (0..m).find(|&i| poly[(i + m - 1) % m] == poly[(i + 1) % m]) {
+            // spike `x a x`
+            let j = (i + 1) % m;
+            poly.remove(i.max(j));
+            poly.remove(i.min(j));
+        } else {
+            return;
+        }
+    }
+}
+
+fn ear_clip(mut poly: Face, verts: &[Vertex]) -> Vec<[usize; 3]> {
+    let mut tris = Vec::new();
+    while poly.len() > 3 {
+        let m = poly.len();
+        let mut best: Option<(bool, f64, usize)> = None;
+        for i in 0..m {
+            let (a, b, c) = (poly[(i + m - 1) % m], poly[i], poly[(i + 1) % m]);
+            if a == b || b == c || c == a {
+                continue;
+            }
+            let o = orient(verts, a, b, c);
+            if o < -EPS {
+                continue;
+            }
+            let inside = poly.iter().any(|&p| {
+                p != a
+                    && p != b
+                    && p != c
+                    && orient(verts, a, b, p) > EPS
+                    && orient(verts, b, c, p) > EPS
+                    && orient(verts, c, a, p) > EPS
+            });
+            if inside {
+                continue;
+            }
+            // prefer strictly convex ears, then the best shaped ones
+            let key = (o > EPS, min_angle(verts, a, b, c), i);
+            if best.is_none_or(|b| (key.0, key.1) > (b.0, b.1)) {
+                best = Some(key);
+            }
+        }
+        match best {
+            Some((_, _, i)) => {
+                tris.push([poly[(i + m - 1) % m], poly[i], poly[(i + 1) % m]]);
+                poly.remove(i);
+            }
+            None => break,
+        }
+    }
+
+    // what remains is degenerate, fan it so that no hole is left
+    for i in 1..poly.len() - 1 {
+        let t = [poly[0], poly[i], poly[i + 1]];
+        if t[0] != t[1] && t[1] != t[2] && t[2] != t[0] {
+            tris.push(t);
+        }
+    }
+    tris
+}
+
+fn flip_edges(
+    tris: &mut [[usize; 3]],
+    boundary: &BTreeSet<(usize, usize)>,
+    verts: &[Vertex],
+    surfaces: &[&[Vertex]],
+) {
+    for _ in 0..MAX_FLIP_ROUNDS * tris.len() {
+        let mut edge_map = BTreeMap::new();
+        for (k, t) in tris.iter().enumerate() {
+            for i in 0..3 {
+                edge_map.insert((t[i], t[(i + 1) % 3]), k);
+            }
+        }
+
+        let mut flipped = false;
+        for k1 in 0..tris.len() {
+            for i in 0..3 {
+                // triangle `abc` with the edge `c -> a`, its neighbor `acd`
+                let t1 = tris[k1];
+                let (a, b, c) = (t1[(i + 1) % 3], t1[(i + 2) % 3], t1[i]);
+                if boundary.contains(&(a.min(c), a.max(c))) {
+                    continue;
+                }
+                let k2 = match edge_map.get(&(a, c)) {
+                    Some(&k2) if k2 != k1 => k2,
+                    _ => continue,
+                };
+                let t2 = tris[k2];
+                let d = t2[0] + t2[1] + t2[2] - a - c;
+                if d == b || orient(verts, a, b, d) < EPS || orient(verts, b, c, d) < EPS {
+                    continue;
+                }
+
+                let old = [[a, b, c], [a, c, d]];
+                let new = [[a, b, d], [b, c, d]];
+                let (bad_old, bad_new) = (nr_inverted(surfaces, &old), nr_inverted(surfaces, &new));
+                if bad_new < bad_old || (bad_new == bad_old && in_circle(verts, a, b, c, d)) {
+                    tris[k1] = new[0];
+                    tris[k2] = new[1];
+                    flipped = true;
+                    break;
+                }
+            }
+            if flipped {
+                break;
+            }
+        }
+        if !flipped {
+            return;
+        }
+    }
+}
+
+fn area_sign(poly: &[usize], verts: &[Vertex]) -> f64 {
+    let mut norm = Vertex::new(0.0, 0.0, 0.0);
+    let mut center = Vertex::new(0.0, 0.0, 0.0);
+    for i in 0..poly.len() {
+        norm += verts[poly[i]] * verts[poly[(i + 1) % poly.len()]];
+        center += verts[poly[i]];
+    }
+    norm.dot(center)
+}
+
+/// Splits a polygon pinched at repeated vertices into simple loops.
+fn split_loops(poly: Face) -> Vec<Face> {
+    for i in 0..poly.len() {
+        for j in i + 1..poly.len() {
+            if poly[i] == poly[j] {
+                let inner = poly[i..j].to_vec();
+                let mut outer = poly[j..].to_vec();
+                outer.extend_from_slice(&poly[..i]);
+                let mut loops = split_loops(inner);
+                loops.append(&mut split_loops(outer));
+                return loops;
+            }
+        }
+    }
+    vec![poly]
+}
+
+/// Triangulates a polygon lying on the sphere by ear clipping, followed by
+/// Delaunay edge flips that also avoid triangles inverted on any of `surfaces`.
+/// Resulting triangles are counterclockwise seen from outside the sphere.
+pub fn triangulate(face: &Face, verts: &[Vertex], surfaces: &[&[Vertex]]) -> Vec<Face> {
+    let mut poly = face.clone();
+    cleanup(&mut poly);
+    if poly.len() < 3 {
+        return Vec::new();
+    }
+    if area_sign(&poly, verts) < 0.0 {
+        poly.reverse();
+    }
+
+    let boundary = (0..poly.len())
+        .map(|i| {
+            let (a, b) = (poly[i], poly[(i + 1) % poly.len()]);
+            (a.min(b), a.max(b))
+        })
+        .collect::<BTreeSet<_>>();
+    let mut tris = Vec::new();
+    for mut l in split_loops(poly) {
+        // loops running backwards enclose no area of this face
+        cleanup(&mut l);
+        if l.len() >= 3 && area_sign(&l, verts) > 0.0 {
+            tris.append(&mut ear_clip(l, verts));
+        }
+    }
+    flip_edges(&mut tris, &boundary, verts, surfaces);
+    tris.iter().map(|t| t.to_vec()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Points of the plane z = 1 projected onto the unit sphere, so that
+    /// counterclockwise polygons in the plane are counterclockwise seen from
+    /// outside the sphere.
+    fn project(points: &[[f64; 2]]) -> Vec<Vertex> {
+        points
+            .iter()
+            .map(|p| Vertex::new(p[0], p[1], 1.0).unit())
+            .collect()
+    }
+
+    fn check(tris: &[Face], poly: &[usize], verts: &[Vertex]) {
+        assert_eq!(tris.len(), poly.len() - 2);
+        for t in tris {
+            assert_eq!(t.len(), 3);
+            assert!(orient(verts, t[0], t[1], t[2]) > EPS);
+        }
+        // each boundary edge is used once, in the direction of the polygon
+        for i in 0..poly.len() {
+            let (a, b) = (poly[i], poly[(i + 1) % poly.len()]);
+            let uses = tris
+                .iter()
+                .filter(|t| (0..3).any(|j| t[j] == a && t[(j + 1) % 3] == b))
+                .count();
+            assert_eq!(uses, 1);
+        }
+    }
+
+    #[test]
+    fn convex_polygon() {
+        let verts = project(&[
+            [0.5, 0.0],
+            [0.25, 0.4],
+            [-0.25, 0.4],
+            [-0.5, 0.0],
+            [-0.25, -0.4],
+            [0.25, -0.4],
+        ]);
+        let poly = (0..6).collect::<Face>();
+        check(&triangulate(&poly, &verts, &[]), &poly, &verts);
+    }
+
+    #[test]
+    fn non_convex_polygon() {
+        // an L shape
+        let verts = project(&[
+            [0.0, 0.0],
+            [0.4, 0.0],
+            [0.4, 0.1],
+            [0.1, 0.1],
+            [0.1, 0.4],
+            [0.0, 0.4],
+        ]);
+        let poly = (0..6).collect::<Face>();
+        check(&triangulate(&poly, &verts, &[]), &poly, &verts);
+    }
+
+    #[test]
+    fn clockwise_polygon_is_reversed() {
+        let verts = project(&[[0.0, 0.0], [0.0, 0.3], [0.3, 0.3], [0.3, 0.0]]);
+        let tris = triangulate(&vec![0, 1, 2, 3], &verts, &[]);
+        check(&tris, &[3, 2, 1, 0], &verts);
+    }
+
+    #[test]
+    fn spikes_are_removed() {
+        // the edge 1 -> 4 is dangling into the square
+        let verts = project(&[[0.0, 0.0], [0.3, 0.0], [0.3, 0.3], [0.0, 0.3], [0.15, 0.1]]);
+        let tris = triangulate(&vec![0, 1, 4, 1, 2, 3], &verts, &[]);
+        check(&tris, &[0, 1, 2, 3], &verts);
+        assert!(triangulate(&vec![0, 1, 0], &verts, &[]).is_empty());
+    }
+
+    #[test]
+    fn flips_to_delaunay_diagonal() {
+        // a flat rhombus, the short diagonal is 1 -> 3
+        let verts = project(&[[-0.4, 0.0], [0.0, -0.05], [0.4, 0.0], [0.0, 0.05]]);
+        let poly = vec![0, 1, 2, 3];
+        let tris = triangulate(&poly, &verts, &[]);
+        check(&tris, &poly, &verts);
+        for t in &tris {
+            assert!(t.contains(&1) && t.contains(&3));
+        }
+    }
+
+    #[test]
+    fn flips_to_avoid_inversions_on_surfaces() {
+        // the rhombus is not convex at vertex 2 on the surface, so that the
+        // short diagonal 1 -> 3 inverts a triangle there
+        let verts = project(&[[-0.4, 0.0], [0.0, -0.05], [0.4, 0.0], [0.0, 0.05]]);
+        let surface = project(&[[-1.0, 0.0], [0.0, -1.0], [-0.5, 0.0], [0.0, 1.0]]);
+        let poly = vec![0, 1, 2, 3];
+        let tris = triangulate(&poly, &verts, &[&surface]);
+        check(&tris, &poly, &verts);
+        for t in &tris {
+            assert!(t.contains(&0) && t.contains(&2));
+            assert!(orient(&surface, t[0], t[1], t[2]) > EPS);
+        }
+    }
+}
@@ -3,6 +3,7 @@ use super::EPS;
 use std::{cmp::Ordering, ops};
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Vertex {
     pub x: f64,
     pub y: f64,
@@ -47,14 +48,20 @@ impl Vertex {
         (a - b).len()
     }
 
+    pub fn angle(a: Self, b: Self) -> f64 {
+        a.unit().dot(b.unit()).clamp(-1.0, 1.0).acos()
+    }
+
     pub fn project_to_sphere(self, center: Self, radius: f64) -> Self {
         let dir = self - center;
         dir * (radius / dir.len())
     }
 
-    pub fn bounding_box(verts: &Vec<Self>) -> (Self, Self) {
-        let mut bbox = (Self::new(std::f64::MAX, std::f64::MAX, std::f64::MAX),
-        Self::new(std::f64::MIN, std::f64::MIN, std::f64::MIN));
+    pub fn bounding_box(verts: &[Self]) -> (Self, Self) {
+        let mut bbox = (
+            Self::new(f64::MAX, f64::MAX, f64::MAX),
+            Self::new(f64::MIN, f64::MIN, f64::MIN),
+        );
         for v in verts {
             if v.x < bbox.0.x {
                 bbox.0.x = v.x
@@ -90,6 +97,18 @@ impl Vertex {
     }
 }
 
+impl From<[f64; 3]> for Vertex {
+    fn from(v: [f64; 3]) -> Self {
+        Self::new(v[0], v[1], v[2])
+    }
+}
+
+impl From<Vertex> for [f64; 3] {
+    fn from(v: Vertex) -> Self {
+        [v.x, v.y, v.z]
+    }
+}
+
 impl Ord for Vertex {
     fn cmp(&self, other: &Self) -> Ordering {
         (self.x, self.y, self.z)
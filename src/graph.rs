@@ -17,7 +17,6 @@ pub struct EdgeList(BTreeSet<Edge>);
 
 #[derive(Debug)]
 pub struct GraphEdge {
-    pub from: usize,
     pub to: usize,
     pub oppo: WeakGraphEdge,
     pub next: WeakGraphEdge,
@@ -28,7 +27,6 @@ pub struct GraphEdge {
 pub struct Graph {
     edges: Vec<Vec<RcGraphEdge>>,
     index_map: Vec<usize>,
-    nr_nodes: usize,
     unique_edges: EdgeList,
 }
 
@@ -73,9 +71,8 @@ impl Edge {
 }
 
 impl GraphEdge {
-    pub fn new(from: usize, to: usize) -> Self {
+    pub fn new(to: usize) -> Self {
         Self {
-            from,
             to,
             oppo: Weak::new(),
             next: Weak::new(),
@@ -101,14 +98,13 @@ impl EdgeList {
 }
 
 impl Graph {
-    pub fn new<T: Ord>(nodes: &Vec<T>) -> Self {
+    pub fn new<T: Ord>(nodes: &[T]) -> Self {
         let n = nodes.len();
         let mut index_map = Vec::with_capacity(n);
         let mut map = BTreeMap::<&T, usize>::new();
 
         // unique vertices
-        for i in 0..n {
-            let v = &nodes[i];
+        for (i, v) in nodes.iter().enumerate() {
             let id = if let Some(&id) = map.get(&v) { id } else { i };
             map.insert(v, id);
             index_map.push(id);
@@ -117,7 +113,6 @@ impl Graph {
         Self {
             edges: vec![Vec::new(); n],
             index_map,
-            nr_nodes: n,
             unique_edges: EdgeList::new(),
         }
     }
@@ -127,8 +122,8 @@ impl Graph {
         if !self.unique_edges.add(from, to) {
             return;
         }
-        let e1 = Rc::new(RefCell::new(GraphEdge::new(from, to)));
-        let e2 = Rc::new(RefCell::new(GraphEdge::new(to, from)));
+        let e1 = Rc::new(RefCell::new(GraphEdge::new(to)));
+        let e2 = Rc::new(RefCell::new(GraphEdge::new(from)));
         e1.borrow_mut().oppo = Rc::downgrade(&e2);
         e2.borrow_mut().oppo = Rc::downgrade(&e1);
         self.edges[from].push(e1);
@@ -0,0 +1,131 @@
+use super::{linear_verts, solve_edges};
+use crate::geo::{Matrix3, Quaternion, Vertex, EPS};
+use crate::MergedModel;
+
+/// Matrix mapping the unit axes to the edges and the (scaled) normal of a
+/// triangle. `None` if the triangle is degenerate.
+fn frame(e1: Vertex, e2: Vertex) -> Option<Matrix3> {
+    let n = e1 * e2;
+    if n.len() < EPS {
+        return None;
+    }
+    Some(Matrix3::from_cols(e1, e2, n / n.len().sqrt()))
+}
+
+/// Affine map of a triangle at ratio `t`, the rotation interpolated by slerp
+/// and the remaining stretch linearly. `None` if the triangle is degenerate.
+fn blended_map(src: [Vertex; 3], dst: [Vertex; 3], t: f64) -> Option<Matrix3> {
+    let s = frame(src[1] - src[0], src[2] - src[0])?;
+    let d = frame(dst[1] - dst[0], dst[2] - dst[0])?;
+    let a = d * s.inverse()?;
+    let r = a.nearest_rotation()?;
+    let stretch = r.transpose() * a;
+    let rot = Quaternion::slerp(Quaternion::identity(), Quaternion::from_matrix(r), t);
+    Some(rot.to_matrix() * (Matrix3::identity() * (1.0 - t) + stretch * t))
+}
+
+/// Positions whose edges best match the edges of each triangle transformed by
+/// its blended map.
+pub(super) fn interpolate(model: &MergedModel, ratios: &[f64]) -> Vec<Vertex> {
+    let mut edges = Vec::new();
+    for f in model.faces.iter().filter(|f| f.len() == 3) {
+        let t = (ratios[f[0]] + ratios[f[1]] + ratios[f[2]]) / 3.0;
+        let src = [0, 1, 2].map(|i| model.vert_pairs[f[i]].0);
+        let dst = [0, 1, 2].map(|i| model.vert_pairs[f[i]].1);
+        let map = blended_map(src, dst, t);
+        for k in 0..3 {
+            let (i, j) = (k, (k + 1) % 3);
+            let e = match map {
+                Some(m) => m * (src[j] - src[i]),
+                None => (src[j] - src[i]) * (1.0 - t) + (dst[j] - dst[i]) * t,
+            };
+            edges.push((f[i], f[j], e));
+        }
+    }
+    solve_edges(linear_verts(model, ratios), &edges)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::cube;
+    use crate::InterpolationMethod;
+
+    /// Turns `v` by `angle` around the z axis.
+    fn turn(v: Vertex, angle: f64) -> Vertex {
+        let (sin, cos) = angle.sin_cos();
+        Vertex::new(v.x * cos - v.y * sin, v.x * sin + v.y * cos, v.z)
+    }
+
+    fn edge_lengths(verts: &[Vertex], faces: &[Vec<usize>]) -> Vec<f64> {
+        faces
+            .iter()
+            .flat_map(|f| (0..3).map(move |i| Vertex::dist(verts[f[i]], verts[f[(i + 1) % 3]])))
+            .collect()
+    }
+
+    #[test]
+    fn blended_map_ends_at_both_triangles() {
+        let src = [
+            Vertex::new(0.0, 0.0, 0.0),
+            Vertex::new(1.0, 0.0, 0.0),
+            Vertex::new(0.0, 1.0, 0.0),
+        ];
+        let dst = [
+            Vertex::new(1.0, 1.0, 1.0),
+            Vertex::new(1.0, 3.0, 1.0),
+            Vertex::new(0.0, 1.0, 2.0),
+        ];
+        for (t, target) in [(0.0, src), (1.0, dst)] {
+            let map = blended_map(src, dst, t).unwrap();
+            for i in 1..3 {
+                let e = map * (src[i] - src[0]);
+                assert!(Vertex::dist(e, target[i] - target[0]) < 1e-9);
+            }
+        }
+        let flat = [src[0], src[1], src[1] * 2.0];
+        assert!(blended_map(flat, dst, 0.5).is_none());
+    }
+
+    #[test]
+    fn rotations_keep_edge_lengths() {
+        let cube = cube();
+        let angle = std::f64::consts::FRAC_PI_2;
+        let vert_pairs = cube.verts.iter().map(|&v| (v, turn(v, angle))).collect();
+        let model = MergedModel::new(vert_pairs, cube.faces.clone());
+        let lengths = edge_lengths(&cube.verts, &cube.faces);
+
+        let ratios = vec![0.5; cube.nr_verts()];
+        let arap = model.interpolation_with_method(&ratios, InterpolationMethod::Arap);
+        for (a, b) in edge_lengths(&arap.verts, &cube.faces).iter().zip(&lengths) {
+            assert!((a - b).abs() < 1e-6);
+        }
+        for (v, w) in arap.verts.iter().zip(&cube.verts) {
+            assert!(Vertex::dist(*v, turn(*w, angle / 2.0)) < 1e-6);
+        }
+
+        // linear interpolation shrinks the cube
+        let linear = model.interpolation_with_method(&ratios, InterpolationMethod::Linear);
+        let shrunk = edge_lengths(&linear.verts, &cube.faces);
+        assert!(shrunk.iter().zip(&lengths).any(|(a, b)| a < &(b - 0.1)));
+    }
+
+    #[test]
+    fn ends_at_both_models() {
+        let cube = cube();
+        let vert_pairs = cube
+            .verts
+            .iter()
+            .map(|&v| (v, turn(v * 1.5, 0.7) + Vertex::new(0.0, 0.0, v.x * 0.3)))
+            .collect::<Vec<_>>();
+        let model = MergedModel::new(vert_pairs.clone(), cube.faces);
+        for (ratio, end) in [(0.0, 0), (1.0, 1)] {
+            let ratios = vec![ratio; model.nr_verts()];
+            let result = model.interpolation_with_method(&ratios, InterpolationMethod::Arap);
+            for (v, p) in result.verts.iter().zip(&vert_pairs) {
+                let target = if end == 0 { p.0 } else { p.1 };
+                assert!(Vertex::dist(*v, target) < 1e-6);
+            }
+        }
+    }
+}
@@ -0,0 +1,287 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::f64::consts::PI;
+
+use super::{linear_verts, solve_edges};
+use crate::geo::{Matrix3, Quaternion, Vertex, EPS};
+use crate::MergedModel;
+
+const FRAME_SWEEPS: usize = 20;
+const SLIVER_RATIO: f64 = 0.1;
+
+/// Whether twice the area of the triangle is tiny compared to its longest edge
+/// squared, making its normal unreliable.
+fn is_sliver(p: [Vertex; 3]) -> bool {
+    let (a, b, c) = (p[1] - p[0], p[2] - p[1], p[0] - p[2]);
+    let longest = a.len2().max(b.len2()).max(c.len2());
+    (a * c).len() <= SLIVER_RATIO * longest
+}
+
+/// Orthonormal frame of a triangle, the first axis along its first edge and the
+/// last one along its normal. `None` for slivers, whose normals are unreliable.
+fn world_frame(p: [Vertex; 3]) -> Option<Matrix3> {
+    if is_sliver(p) {
+        return None;
+    }
+    let x = (p[1] - p[0]).unit();
+    let n = ((p[1] - p[0]) * (p[2] - p[0])).unit();
+    Some(Matrix3::from_cols(x, n * x, n))
+}
+
+/// Corners of a triangle with edge lengths `l[k]` (from corner `k` to `k + 1`)
+/// in its own frame.
+fn local_coords(l: [f64; 3]) -> [Vertex; 3] {
+    let x = (l[0] * l[0] + l[2] * l[2] - l[1] * l[1]) / (2.0 * l[0]).max(EPS);
+    let y = (l[2] * l[2] - x * x).max(0.0).sqrt();
+    [
+        Vertex::new(0.0, 0.0, 0.0),
+        Vertex::new(l[0], 0.0, 0.0),
+        Vertex::new(x, y, 0.0),
+    ]
+}
+
+/// Angle rotating the normal `n1` to `n2` around the edge direction `e`.
+fn bend_angle(n1: Vertex, n2: Vertex, e: Vertex) -> f64 {
+    (n1 * n2).dot(e).atan2(n1.dot(n2))
+}
+
+fn rotation_x(theta: f64) -> Matrix3 {
+    let (s, c) = theta.sin_cos();
+    Matrix3::new([[1.0, 0.0, 0.0], [0.0, c, -s], [0.0, s, c]])
+}
+
+/// Frame of the edge `e` in local coordinates of a triangle.
+fn edge_frame(e: Vertex) -> Matrix3 {
+    let z = Vertex::new(0.0, 0.0, 1.0);
+    Matrix3::from_cols(e, z * e, z)
+}
+
+struct Triangle {
+    verts: [usize; 3],
+    src: Matrix3,
+    dst: Matrix3,
+    local: [Vertex; 3],
+}
+
+/// Interpolates edge lengths and dihedral angles, then recovers the frame of
+/// each triangle by least squares over adjacent pairs and the positions from
+/// the edges expressed in these frames.
+pub(super) fn interpolate(model: &MergedModel, ratios: &[f64]) -> Vec<Vertex> {
+    let pairs = &model.vert_pairs;
+    let edge_ratio = |i: usize, j: usize| (ratios[i] + ratios[j]) / 2.0;
+    let mut edges = Vec::new();
+    let mut tris = Vec::new();
+    for f in model.faces.iter().filter(|f| f.len() == 3) {
+        let verts = [f[0], f[1], f[2]];
+        let src = world_frame(verts.map(|i| pairs[i].0));
+        let dst = world_frame(verts.map(|i| pairs[i].1));
+        let lengths = [0, 1, 2].map(|k| {
+            let (i, j) = (verts[k], verts[(k + 1) % 3]);
+            let t = edge_ratio(i, j);
+            Vertex::dist(pairs[i].0, pairs[j].0) * (1.0 - t)
+                + Vertex::dist(pairs[i].1, pairs[j].1) * t
+        });
+        match (src, dst) {
+            (Some(src), Some(dst)) => tris.push(Triangle {
+                verts,
+                src,
+                dst,
+                local: local_coords(lengths),
+            }),
+            _ => {
+                // slivers just follow the linear blend of their edges
+                for k in 0..3 {
+                    let (i, j) = (verts[k], verts[(k + 1) % 3]);
+                    let t = edge_ratio(i, j);
+                    let e = (pairs[j].0 - pairs[i].0) * (1.0 - t) + (pairs[j].1 - pairs[i].1) * t;
+                    edges.push((i, j, e));
+                }
+            }
+        }
+    }
+
+    // transition `T` between adjacent triangles, such that `F_g = F_f * T`
+    let mut edge_tris = BTreeMap::new();
+    for (k, tri) in tris.iter().enumerate() {
+        for c in 0..3 {
+            let (i, j) = (tri.verts[c], tri.verts[(c + 1) % 3]);
+            edge_tris
+                .entry((i.min(j), i.max(j)))
+                .or_insert_with(Vec::new)
+                .push(k);
+        }
+    }
+    let mut adjacent = vec![Vec::new(); tris.len()];
+    for (&(u, v), faces) in &edge_tris {
+        for (a, &f) in faces.iter().enumerate() {
+            for &g in &faces[a + 1..] {
+                let (tf, tg) = (&tris[f], &tris[g]);
+                let local_edge = |tri: &Triangle| {
+                    let pos = |w: usize| tri.local[tri.verts.iter().position(|&x| x == w).unwrap()];
+                    (pos(v) - pos(u)).unit()
+                };
+                let angle =
+                    |sf: Matrix3, sg: Matrix3, e: Vertex| bend_angle(sf.col(2), sg.col(2), e);
+                let a1 = angle(tf.src, tg.src, (pairs[v].0 - pairs[u].0).unit());
+                let a2 = angle(tf.dst, tg.dst, (pairs[v].1 - pairs[u].1).unit());
+                let mut delta = a2 - a1;
+                if delta > PI {
+                    delta -= 2.0 * PI;
+                } else if delta < -PI {
+                    delta += 2.0 * PI;
+                }
+                let theta = a1 + delta * edge_ratio(u, v);
+                let t = edge_frame(local_edge(tf))
+                    * rotation_x(theta)
+                    * edge_frame(local_edge(tg)).transpose();
+                adjacent[f].push((g, t));
+                adjacent[g].push((f, t.transpose()));
+            }
+        }
+    }
+
+    // propagate frames from the largest triangle of each component, then
+    // average them with their neighbors
+    let area = |tri: &Triangle| {
+        let p = tri.verts.map(|i| pairs[i].0);
+        ((p[1] - p[0]) * (p[2] - p[0])).len()
+    };
+    let mut order = (0..tris.len()).collect::<Vec<_>>();
+    order.sort_by(|&a, &b| area(&tris[b]).total_cmp(&area(&tris[a])));
+    let mut frames: Vec<Option<Matrix3>> = vec![None; tris.len()];
+    let mut anchor = vec![false; tris.len()];
+    for &s in &order {
+        if frames[s].is_some() {
+            continue;
+        }
+        let tri = &tris[s];
+        let t = tri.verts.iter().map(|&i| ratios[i]).sum::<f64>() / 3.0;
+        let rot = Quaternion::from_matrix(tri.dst * tri.src.transpose());
+        frames[s] = Some(Quaternion::slerp(Quaternion::identity(), rot, t).to_matrix() * tri.src);
+        anchor[s] = true;
+        let mut queue = VecDeque::new();
+        queue.push_back(s);
+        while let Some(f) = queue.pop_front() {
+            for &(g, t) in &adjacent[f] {
+                if frames[g].is_none() {
+                    frames[g] = Some(frames[f].unwrap() * t);
+                    queue.push_back(g);
+                }
+            }
+        }
+    }
+    for _ in 0..FRAME_SWEEPS {
+        for g in 0..tris.len() {
+            if anchor[g] || adjacent[g].is_empty() {
+                continue;
+            }
+            let sum = adjacent[g].iter().fold(Matrix3::zero(), |acc, &(f, t)| {
+                acc + frames[f].unwrap() * t.transpose()
+            });
+            if let Some(r) = sum.nearest_rotation() {
+                frames[g] = Some(r);
+            }
+        }
+    }
+
+    for (tri, frame) in tris.iter().zip(&frames) {
+        let frame = frame.unwrap();
+        for k in 0..3 {
+            let (a, b) = (k, (k + 1) % 3);
+            edges.push((
+                tri.verts[a],
+                tri.verts[b],
+                frame * (tri.local[b] - tri.local[a]),
+            ));
+        }
+    }
+    solve_edges(linear_verts(model, ratios), &edges)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::cube;
+    use crate::{InterpolationMethod, Model};
+
+    fn interpolate_pairs(
+        vert_pairs: Vec<(Vertex, Vertex)>,
+        faces: Vec<Vec<usize>>,
+        t: f64,
+    ) -> Model {
+        let model = MergedModel::new(vert_pairs, faces);
+        let ratios = vec![t; model.nr_verts()];
+        model.interpolation_with_method(&ratios, InterpolationMethod::Intrinsic)
+    }
+
+    #[test]
+    fn slivers_are_detected() {
+        let o = Vertex::new(0.0, 0.0, 0.0);
+        let x = Vertex::new(1.0, 0.0, 0.0);
+        assert!(!is_sliver([o, x, Vertex::new(0.5, 0.8, 0.0)]));
+        assert!(is_sliver([o, x, Vertex::new(0.5, 0.01, 0.0)]));
+        assert!(is_sliver([o, x, x * 2.0]));
+        assert!(is_sliver([o, o, x]));
+        assert!(world_frame([o, x, x * 3.0]).is_none());
+    }
+
+    #[test]
+    fn local_coords_keep_edge_lengths() {
+        let l = [3.0, 4.0, 5.0];
+        let p = local_coords(l);
+        for k in 0..3 {
+            assert!((Vertex::dist(p[k], p[(k + 1) % 3]) - l[k]).abs() < 1e-9);
+        }
+        assert!(p[2].y > 0.0);
+    }
+
+    #[test]
+    fn bend_angles_are_signed() {
+        let e = Vertex::new(1.0, 0.0, 0.0);
+        let n = Vertex::new(0.0, 0.0, 1.0);
+        let turned = Vertex::new(0.0, -1.0, 1.0).unit();
+        assert!((bend_angle(n, turned, e) - PI / 4.0).abs() < 1e-9);
+        assert!((bend_angle(turned, n, e) + PI / 4.0).abs() < 1e-9);
+        assert!(bend_angle(n, n, e).abs() < 1e-9);
+    }
+
+    #[test]
+    fn ends_at_both_models() {
+        let cube = cube();
+        let vert_pairs = cube
+            .verts
+            .iter()
+            .map(|&v| (v, Vertex::new(v.x * 2.0, v.y - v.z * 0.3, v.z + 1.0)))
+            .collect::<Vec<_>>();
+        for (t, end) in [(0.0, false), (1.0, true)] {
+            let result = interpolate_pairs(vert_pairs.clone(), cube.faces.clone(), t);
+            for (v, p) in result.verts.iter().zip(&vert_pairs) {
+                assert!(Vertex::dist(*v, if end { p.1 } else { p.0 }) < 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn hinge_bends_halfway() {
+        // two triangles sharing the edge 0 -> 1 along x, flat then folded up
+        let verts = [
+            Vertex::new(0.0, 0.0, 0.0),
+            Vertex::new(1.0, 0.0, 0.0),
+            Vertex::new(0.5, 1.0, 0.0),
+            Vertex::new(0.5, -1.0, 0.0),
+        ];
+        let folded = [verts[0], verts[1], verts[2], Vertex::new(0.5, 0.0, 1.0)];
+        let vert_pairs = verts.iter().copied().zip(folded.iter().copied()).collect();
+        let faces = vec![vec![0, 1, 2], vec![1, 0, 3]];
+        let result = interpolate_pairs(vert_pairs, faces, 0.5);
+        let p = &result.verts;
+        // edge lengths are kept and the wings are at 135 degrees
+        assert!((Vertex::dist(p[0], p[3]) - Vertex::dist(verts[0], verts[3])).abs() < 1e-6);
+        let axis = (p[1] - p[0]).unit();
+        let wing = |v: Vertex| {
+            let d = v - p[0];
+            (d - axis * d.dot(axis)).unit()
+        };
+        let angle = wing(p[2]).dot(wing(p[3])).acos();
+        assert!((angle - PI * 3.0 / 4.0).abs() < 1e-6, "{}", angle);
+    }
+}
@@ -0,0 +1,156 @@
+use super::linear_verts;
+use crate::geo::{Face, Vertex, EPS};
+use crate::sparse::SparseMatrix;
+use crate::MergedModel;
+
+const ANCHORS: usize = 16;
+const ANCHOR_WEIGHT: f64 = 1.0;
+const REGULARIZATION: f64 = 1e-6;
+
+/// Area weighted vertex normals.
+fn vertex_normals(verts: &[Vertex], faces: &[Face]) -> Vec<Vertex> {
+    let mut normals = vec![Vertex::new(0.0, 0.0, 0.0); verts.len()];
+    for f in faces.iter().filter(|f| f.len() == 3) {
+        let n = (verts[f[1]] - verts[f[0]]) * (verts[f[2]] - verts[f[0]]);
+        for &i in f {
+            normals[i] += n;
+        }
+    }
+    normals
+        .iter()
+        .map(|&n| if n.len() > EPS { n.unit() } else { n })
+        .collect()
+}
+
+/// Normal component of the differential coordinates, normalized by the total
+/// weight of each row so that it does not depend on the size of the triangles.
+fn mean_curvatures(verts: &[Vertex], faces: &[Face]) -> Vec<f64> {
+    let laplacian = SparseMatrix::cotangent_laplacian(verts, faces);
+    let delta = laplacian.mul(verts);
+    let diagonal = laplacian.diagonal();
+    vertex_normals(verts, faces)
+        .iter()
+        .zip(delta.iter().zip(&diagonal))
+        .map(|(n, (d, &w))| if w > EPS { d.dot(*n) / w } else { 0.0 })
+        .collect()
+}
+
+/// Vertices spread over the model by farthest point sampling.
+fn anchors(verts: &[Vertex]) -> Vec<usize> {
+    let mut res = Vec::new();
+    let mut dist = vec![f64::INFINITY; verts.len()];
+    let mut next = 0;
+    while res.len() < ANCHORS.min(verts.len()) {
+        res.push(next);
+        for (i, d) in dist.iter_mut().enumerate() {
+            *d = d.min(Vertex::dist(verts[i], verts[next]));
+        }
+        next = (0..verts.len())
+            .max_by(|&i, &j| dist[i].total_cmp(&dist[j]))
+            .unwrap();
+    }
+    res
+}
+
+/// Solves for positions whose differential coordinates are those of the linear
+/// blend with the normal components, i.e. the surface details, blended between
+/// both models. A few anchor vertices keep their linear positions.
+pub(super) fn interpolate(model: &MergedModel, ratios: &[f64]) -> Vec<Vertex> {
+    let linear = linear_verts(model, ratios);
+    if linear.is_empty() {
+        return linear;
+    }
+    let verts1 = model.vert_pairs.iter().map(|p| p.0).collect::<Vec<_>>();
+    let verts2 = model.vert_pairs.iter().map(|p| p.1).collect::<Vec<_>>();
+    let curvatures1 = mean_curvatures(&verts1, &model.faces);
+    let curvatures2 = mean_curvatures(&verts2, &model.faces);
+
+    let mut laplacian = SparseMatrix::cotangent_laplacian(&linear, &model.faces);
+    let mut rhs = laplacian.mul(&linear);
+    let diagonal = laplacian.diagonal();
+    let normals = vertex_normals(&linear, &model.faces);
+    for i in 0..linear.len() {
+        if diagonal[i] > EPS {
+            let target = curvatures1[i] + (curvatures2[i] - curvatures1[i]) * ratios[i];
+            let current = rhs[i].dot(normals[i]) / diagonal[i];
+            rhs[i] += normals[i] * ((target - current) * diagonal[i]);
+        }
+    }
+
+    let mean = diagonal.iter().sum::<f64>() / diagonal.len() as f64;
+    for i in 0..linear.len() {
+        laplacian.add(i, i, REGULARIZATION * mean);
+        rhs[i] += linear[i] * (REGULARIZATION * mean);
+    }
+    for i in anchors(&linear) {
+        laplacian.add(i, i, ANCHOR_WEIGHT * mean);
+        rhs[i] += linear[i] * (ANCHOR_WEIGHT * mean);
+    }
+    match laplacian.cholesky() {
+        Some(factor) => factor.solve(&rhs),
+        None => laplacian.solve_cg(&rhs, linear),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{octahedron, sample};
+    use crate::InterpolationMethod;
+
+    #[test]
+    fn flat_vertices_have_no_curvature() {
+        // a square fan around the center vertex 0
+        let verts = [
+            Vertex::new(0.0, 0.0, 0.0),
+            Vertex::new(1.0, 0.0, 0.0),
+            Vertex::new(0.0, 1.0, 0.0),
+            Vertex::new(-1.0, 0.0, 0.0),
+            Vertex::new(0.0, -1.0, 0.0),
+        ];
+        let faces = (1..5).map(|i| vec![0, i, i % 4 + 1]).collect::<Vec<_>>();
+        assert!(mean_curvatures(&verts, &faces)[0].abs() < EPS);
+
+        let mut bent = verts;
+        bent[0].z = 0.5;
+        assert!(mean_curvatures(&bent, &faces)[0].abs() > 0.1);
+    }
+
+    #[test]
+    fn convex_vertices_curve_the_same_way() {
+        let model = octahedron();
+        let curvatures = mean_curvatures(&model.verts, &model.faces);
+        assert!(curvatures.iter().all(|&c| c < -EPS) || curvatures.iter().all(|&c| c > EPS));
+        assert!(curvatures.iter().all(|&c| (c - curvatures[0]).abs() < 1e-9));
+    }
+
+    #[test]
+    fn anchors_are_spread() {
+        let model = sample("egg.obj");
+        let spread = anchors(&model.verts);
+        assert_eq!(spread.len(), ANCHORS);
+        assert_eq!(spread[0], 0);
+        for (k, &a) in spread.iter().enumerate() {
+            assert!(!spread[..k].contains(&a));
+        }
+        assert_eq!(anchors(&model.verts[..3]).len(), 3);
+    }
+
+    #[test]
+    fn ends_at_both_models() {
+        let model = sample("egg.obj");
+        let vert_pairs = model
+            .verts
+            .iter()
+            .map(|&v| (v, Vertex::new(v.x * 1.2, v.y, v.z * 0.8 + v.x * v.x)))
+            .collect::<Vec<_>>();
+        let merged = MergedModel::new(vert_pairs.clone(), model.faces);
+        for (t, end) in [(0.0, false), (1.0, true)] {
+            let ratios = vec![t; merged.nr_verts()];
+            let result = merged.interpolation_with_method(&ratios, InterpolationMethod::Laplacian);
+            for (v, p) in result.verts.iter().zip(&vert_pairs) {
+                assert!(Vertex::dist(*v, if end { p.1 } else { p.0 }) < 1e-6);
+            }
+        }
+    }
+}
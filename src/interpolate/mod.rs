@@ -0,0 +1,97 @@
+mod arap;
+mod intrinsic;
+mod laplacian;
+
+use std::str::FromStr;
+
+use crate::geo::Vertex;
+use crate::sparse::SparseMatrix;
+use crate::{MergedModel, Model};
+
+/// How the in-between positions are computed from `vert_pairs`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterpolationMethod {
+    Linear,
+    Arap,      // as-rigid-as-possible, blending rotation and stretch of each triangle
+    Intrinsic, // blending edge lengths and dihedral angles
+    Laplacian, // blending the surface details of differential coordinates
+}
+
+impl FromStr for InterpolationMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "linear" => Ok(InterpolationMethod::Linear),
+            "arap" => Ok(InterpolationMethod::Arap),
+            "intrinsic" => Ok(InterpolationMethod::Intrinsic),
+            "laplacian" => Ok(InterpolationMethod::Laplacian),
+            _ => Err(format!("Invalid interpolation method \"{}\"", s)),
+        }
+    }
+}
+
+fn linear_verts(model: &MergedModel, ratios: &[f64]) -> Vec<Vertex> {
+    debug_assert_eq!(ratios.len(), model.vert_pairs.len());
+    model
+        .vert_pairs
+        .iter()
+        .zip(ratios)
+        .map(|((v1, v2), ratio)| *v1 + (*v2 - *v1) * *ratio)
+        .collect()
+}
+
+/// Positions whose edges best match the edge vectors `(i, j, v_j - v_i)` in the
+/// least squares sense, with the same centroid as `linear`.
+fn solve_edges(linear: Vec<Vertex>, edges: &[(usize, usize, Vertex)]) -> Vec<Vertex> {
+    let n = linear.len();
+    let mut laplacian = SparseMatrix::new(n);
+    let mut rhs = vec![Vertex::new(0.0, 0.0, 0.0); n];
+    for &(i, j, e) in edges {
+        laplacian.add_edge(i, j, 1.0);
+        rhs[i] -= e;
+        rhs[j] += e;
+    }
+
+    let mut verts = laplacian.solve_cg(&rhs, linear.clone());
+    let shift = linear
+        .iter()
+        .zip(&verts)
+        .fold(Vertex::new(0.0, 0.0, 0.0), |acc, (&a, &b)| acc + a - b)
+        / n.max(1) as f64;
+    for v in &mut verts {
+        *v += shift;
+    }
+    verts
+}
+
+impl MergedModel {
+    /// Interpolation with a separate ratio for each merged vertex, using `method`.
+    pub fn interpolation_with_method(&self, ratios: &[f64], method: InterpolationMethod) -> Model {
+        let mut new_verts = match method {
+            InterpolationMethod::Linear => linear_verts(self, ratios),
+            InterpolationMethod::Arap => arap::interpolate(self, ratios),
+            InterpolationMethod::Intrinsic => intrinsic::interpolate(self, ratios),
+            InterpolationMethod::Laplacian => laplacian::interpolate(self, ratios),
+        };
+        self.restore_coords(&mut new_verts, ratios);
+
+        let mut model = Model::new(new_verts, self.faces.clone());
+        model.colors = self
+            .color_pairs
+            .iter()
+            .zip(ratios)
+            .map(|(c, &r)| c.0 + (c.1 - c.0) * r)
+            .collect();
+        model.texcoords = self.texcoord_pairs.iter().map(|t| vec![t.0, t.1]).collect();
+        model.face_texcoords = self.face_texcoords.clone();
+        model.normals = self.interpolated_normals(ratios);
+        model.face_normals = self.face_normals.clone();
+        model.attributes = self.interpolated_attributes(ratios);
+        let (labels, face_labels) = self.interpolated_labels(ratios);
+        model.labels = labels;
+        model.face_labels = face_labels;
+        model.orient();
+        model
+    }
+}
@@ -0,0 +1,118 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::geo::{Triangle, Vertex, EPS};
+use crate::Model;
+
+impl Model {
+    /// Number of pairs of faces crossing each other. Faces sharing a vertex are
+    /// not checked against each other.
+    pub fn self_intersections(&self) -> usize {
+        let mut tris = Vec::new();
+        for (k, f) in self.faces.iter().enumerate() {
+            for i in 1..f.len().saturating_sub(1) {
+                tris.push((k, [f[0], f[i], f[i + 1]]));
+            }
+        }
+        let (lo, hi) = Vertex::bounding_box(&self.verts);
+        let size = (hi - lo).max();
+        if tris.is_empty() || size < EPS {
+            return 0;
+        }
+
+        // uniform grid with about as many occupied cells as triangles
+        let res = (tris.len() as f64).sqrt().ceil();
+        let cell = |v: Vertex| {
+            let c = |x: f64, l: f64| (((x - l) / size * res) as usize).min(res as usize - 1);
+            (c(v.x, lo.x), c(v.y, lo.y), c(v.z, lo.z))
+        };
+        let mut grid = HashMap::new();
+        let mut first_cells = Vec::with_capacity(tris.len());
+        for (t, (_, tri)) in tris.iter().enumerate() {
+            let (b0, b1) = Vertex::bounding_box(&tri.map(|i| self.verts[i]));
+            let (c0, c1) = (cell(b0), cell(b1));
+            first_cells.push(c0);
+            for x in c0.0..=c1.0 {
+                for y in c0.1..=c1.1 {
+                    for z in c0.2..=c1.2 {
+                        grid.entry((x, y, z)).or_insert_with(Vec::new).push(t);
+                    }
+                }
+            }
+        }
+
+        let mut crossing = BTreeSet::new();
+        for (&c, cell_tris) in &grid {
+            for (a, &t1) in cell_tris.iter().enumerate() {
+                for &t2 in &cell_tris[a + 1..] {
+                    let ((f1, v1), (f2, v2)) = (tris[t1], tris[t2]);
+                    if f1 == f2 || v1.iter().any(|i| v2.contains(i)) {
+                        continue;
+                    }
+                    // check each pair only in the first cell they share
+                    let (c1, c2) = (first_cells[t1], first_cells[t2]);
+                    if (c1.0.max(c2.0), c1.1.max(c2.1), c1.2.max(c2.2)) != c {
+                        continue;
+                    }
+                    let tri = |v: [usize; 3]| {
+                        Triangle::new(self.verts[v[0]], self.verts[v[1]], self.verts[v[2]])
+                    };
+                    if tri(v1).intersects(&tri(v2)) {
+                        crossing.insert((f1.min(f2), f1.max(f2)));
+                    }
+                }
+            }
+        }
+        crossing.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{cube, octahedron, sample};
+    use crate::{Model, Vertex};
+
+    #[test]
+    fn closed_models_do_not_intersect() {
+        assert_eq!(cube().self_intersections(), 0);
+        assert_eq!(octahedron().self_intersections(), 0);
+        assert_eq!(sample("egg.obj").self_intersections(), 0);
+    }
+
+    #[test]
+    fn crossing_faces_are_counted_once() {
+        // the cube together with an octahedron poking out of each of its faces
+        let cube = cube();
+        let octahedron = octahedron();
+        let mut verts = cube.verts.clone();
+        verts.extend(octahedron.verts.iter().map(|&v| v * 1.5));
+        let mut faces = cube.faces.clone();
+        faces.extend(
+            octahedron
+                .faces
+                .iter()
+                .map(|f| f.iter().map(|&i| i + 8).collect()),
+        );
+        let count = Model::new(verts, faces).self_intersections();
+        // each face of the octahedron crosses the 3 cube faces around its corner
+        assert!(count > 0);
+
+        let verts = [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [0.2, 0.2, -1.0],
+            [0.2, 0.2, 1.0],
+            [2.0, 2.0, 0.0],
+        ];
+        let model = Model::from_slices(&verts, &[[0, 1, 2], [3, 4, 5]]);
+        assert_eq!(model.self_intersections(), 1);
+        let model = Model::from_slices(&verts, &[[0, 1, 2], [0, 4, 5]]);
+        assert_eq!(model.self_intersections(), 0);
+    }
+
+    #[test]
+    fn flat_models_have_no_intersections() {
+        let model = Model::new(vec![Vertex::new(1.0, 1.0, 1.0); 3], vec![vec![0, 1, 2]]);
+        assert_eq!(model.self_intersections(), 0);
+    }
+}
@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+
+use crate::{MergedModel, Model};
+
+/// Group (`g`) and material (`usemtl`) of a face in an OBJ file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FaceLabel {
+    pub group: Option<String>,
+    pub material: Option<String>,
+}
+
+/// Labels of the faces as indices into a table of distinct labels.
+#[derive(Debug, Default)]
+pub(crate) struct LabelTable {
+    pub(crate) labels: Vec<FaceLabel>,
+    index: HashMap<FaceLabel, usize>,
+}
+
+impl LabelTable {
+    pub(crate) fn intern(&mut self, label: FaceLabel) -> usize {
+        let labels = &mut self.labels;
+        *self.index.entry(label).or_insert_with_key(|label| {
+            labels.push(label.clone());
+            labels.len() - 1
+        })
+    }
+}
+
+/// Label of faces lying in faces of several models: the groups of all of them
+/// and the material of the first one having a material.
+pub(crate) fn combine_labels(
+    tables: &[Vec<FaceLabel>],
+    face_labels: &[Vec<usize>],
+) -> (Vec<FaceLabel>, Vec<usize>) {
+    let mut table = LabelTable::default();
+    let face_labels = face_labels
+        .iter()
+        .map(|l| {
+            let labels = l.iter().zip(tables).map(|(&i, t)| &t[i]);
+            let mut groups = labels
+                .clone()
+                .filter_map(|l| l.group.as_deref())
+                .collect::<Vec<_>>();
+            groups.dedup();
+            table.intern(FaceLabel {
+                group: if groups.is_empty() {
+                    None
+                } else {
+                    Some(groups.join(" "))
+                },
+                material: labels.filter_map(|l| l.material.clone()).next(),
+            })
+        })
+        .collect();
+    (table.labels, face_labels)
+}
+
+impl Model {
+    /// Label of face `k`, `None` if the model has no groups or materials.
+    pub fn face_label(&self, k: usize) -> Option<&FaceLabel> {
+        self.face_labels.get(k).map(|&l| &self.labels[l])
+    }
+}
+
+impl MergedModel {
+    /// Labels of the faces of both models containing face `k`.
+    pub fn face_labels(&self, k: usize) -> Option<(&FaceLabel, &FaceLabel)> {
+        self.face_labels
+            .get(k)
+            .map(|&(l1, l2)| (&self.labels[0][l1], &self.labels[1][l2]))
+    }
+
+    /// Labels of the interpolated faces, taken from the model nearest to the
+    /// average ratio of their vertices.
+    pub(crate) fn interpolated_labels(&self, ratios: &[f64]) -> (Vec<FaceLabel>, Vec<usize>) {
+        if self.face_labels.is_empty() {
+            return (Vec::new(), Vec::new());
+        }
+        let n = self.labels[0].len();
+        let face_labels = self
+            .faces
+            .iter()
+            .zip(&self.face_labels)
+            .map(|(f, &(l1, l2))| {
+                let r = f.iter().map(|&i| ratios[i]).sum::<f64>() / f.len() as f64;
+                if r < 0.5 {
+                    l1
+                } else {
+                    n + l2
+                }
+            })
+            .collect();
+        (self.labels.concat(), face_labels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{cube, octahedron, temp_path};
+    use crate::{merge, Config};
+
+    fn label(group: Option<&str>, material: Option<&str>) -> FaceLabel {
+        FaceLabel {
+            group: group.map(String::from),
+            material: material.map(String::from),
+        }
+    }
+
+    /// The model with its first faces in group `a` and the others in group `b`
+    /// with material `m`.
+    fn labeled(mut model: Model, split: usize) -> Model {
+        model.labels = vec![label(Some("a"), None), label(Some("b"), Some("m"))];
+        model.face_labels = (0..model.nr_faces())
+            .map(|k| (k >= split) as usize)
+            .collect();
+        model
+    }
+
+    #[test]
+    fn labels_are_interned() {
+        let mut table = LabelTable::default();
+        assert_eq!(table.intern(label(Some("a"), None)), 0);
+        assert_eq!(table.intern(label(Some("a"), Some("m"))), 1);
+        assert_eq!(table.intern(label(Some("a"), None)), 0);
+        assert_eq!(table.labels.len(), 2);
+    }
+
+    #[test]
+    fn combined_labels_keep_all_groups() {
+        let tables = [
+            vec![label(Some("a"), None), label(None, None)],
+            vec![label(Some("b"), Some("m")), label(Some("a"), Some("n"))],
+        ];
+        let (labels, face_labels) = combine_labels(&tables, &[vec![0, 0], vec![1, 1], vec![0, 1]]);
+        let face = |k: usize| &labels[face_labels[k]];
+        assert_eq!(face(0), &label(Some("a b"), Some("m")));
+        assert_eq!(face(1), &label(Some("a"), Some("n")));
+        // the same group is not repeated
+        assert_eq!(face(2), &label(Some("a"), Some("n")));
+    }
+
+    #[test]
+    fn labels_are_loaded_and_saved() {
+        let path = temp_path("labels.obj");
+        let content = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 1 1 0\n\
+                       f 1 2 3\ng top\nusemtl red\nf 2 4 3\ng\nf 3 2 1\n";
+        std::fs::write(&path, content).unwrap();
+        let model = Model::load(&path).unwrap();
+        assert_eq!(model.face_label(0), Some(&label(None, None)));
+        assert_eq!(model.face_label(1), Some(&label(Some("top"), Some("red"))));
+        assert_eq!(model.face_label(2), Some(&label(None, Some("red"))));
+
+        model.save(&path).unwrap();
+        let saved = Model::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let mut faces = (0..model.nr_faces())
+            .map(|k| {
+                (
+                    &model.faces[k],
+                    model.face_label(k).unwrap().material.clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+        let mut saved_faces = (0..saved.nr_faces())
+            .map(|k| {
+                (
+                    &saved.faces[k],
+                    saved.face_label(k).unwrap().material.clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+        faces.sort();
+        saved_faces.sort();
+        assert_eq!(faces, saved_faces);
+
+        assert_eq!(cube().face_label(0), None);
+    }
+
+    #[test]
+    fn labels_are_transferred_and_interpolated() {
+        let merged = merge(
+            labeled(cube(), 6),
+            labeled(octahedron(), 4),
+            Config::default(),
+        );
+        assert_eq!(merged.face_labels.len(), merged.nr_faces());
+        let (l1, l2) = merged.face_labels(0).unwrap();
+        assert!(l1.group.is_some() && l2.group.is_some());
+        assert!(merged.face_labels(merged.nr_faces()).is_none());
+
+        for (ratio, end) in [(0.4, false), (0.6, true)] {
+            let model = merged.interpolation(ratio);
+            for k in 0..merged.nr_faces() {
+                let (l1, l2) = merged.face_labels(k).unwrap();
+                assert_eq!(model.face_label(k), Some(if end { l2 } else { l1 }));
+            }
+        }
+        // unlabeled models give unlabeled results
+        let merged = merge(cube(), octahedron(), Config::default());
+        assert!(merged.face_labels(0).is_none());
+        assert_eq!(merged.interpolation(0.5).face_label(0), None);
+    }
+}
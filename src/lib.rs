@@ -1,21 +1,105 @@
+mod attribute;
+mod binary;
+mod easing;
+mod feature;
+mod field;
 mod geo;
 mod graph;
+mod interpolate;
+mod intersect;
+mod label;
+mod locate;
+mod merger;
 mod model;
+mod multi;
+mod normal;
+mod placement;
+mod ply;
+mod provenance;
+mod refine;
+mod simplify;
+mod sparse;
+mod spline;
+mod stats;
+#[cfg(test)]
+mod testing;
 
-pub use geo::Vertex;
+pub use attribute::{Attribute, AttributePolicy, AttributeValues};
+pub use easing::{Easing, Extrapolation};
+pub use feature::{detect_features, match_features, Feature, FeatureKind, FeaturePair};
+pub use field::{CountMismatch, RatioField};
+pub use geo::{Face, Quaternion, Vertex};
+pub use interpolate::InterpolationMethod;
+pub use label::FaceLabel;
+pub use merger::{CancelToken, Cancelled, Intermediate, MergeError, Merger, Stage};
 pub use model::{MergedModel, Model};
+pub use multi::MultiMergedModel;
+pub use normal::NormalWeighting;
+pub use placement::Placement;
+pub use provenance::{Provenance, SourceLocation, VertexProvenance, VertexSource};
+pub use spline::Spline;
+pub use stats::{IntersectionCounts, MergeStats};
 
+use merger::{check_pairs, Hooks};
 use model::ProjectionModel;
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct Config {
     pub edge_only: bool,
     pub sphere_only: bool,
     pub scale: bool,
+    /// Write the outputs in the original coordinates of the models. Only the
+    /// saved models and the interpolations are restored: `vert_pairs()` stay
+    /// centered and normalized, and `placements()` map them back.
+    pub keep_coords: bool,
+    pub snap_tolerance: f64, // angular tolerance in radians, 0 to disable snapping
 }
 
 pub fn merge(model1: Model, model2: Model, config: Config) -> MergedModel {
-    let model1 = ProjectionModel::new(model1);
-    let model2 = ProjectionModel::new(model2);
-    MergedModel::merge(model1, model2, config)
+    merge_with_pairs(model1, model2, &[], config).expect("Merge failed without pairs")
+}
+
+/// Merges two models after warping the sphere embedding of `model2` so that the
+/// vertex pairs (e.g. suggested by `match_features`) are overlaid. Fails if a
+/// pair is out of range.
+pub fn merge_with_pairs(
+    model1: Model,
+    model2: Model,
+    pairs: &[FeaturePair],
+    config: Config,
+) -> Result<MergedModel, MergeError> {
+    Merger::new(model1, model2)
+        .config(config)
+        .pairs(pairs)
+        .merge()
+}
+
+/// Merges several models into a common mesh. The sphere embedding of each model
+/// is first warped to align it with the previous one, by `pairs[k - 1]` for
+/// model `k` if given. Fails if a pair is out of range.
+pub fn merge_all(
+    models: Vec<Model>,
+    pairs: &[Vec<FeaturePair>],
+    config: Config,
+) -> Result<MultiMergedModel, MergeError> {
+    for (k, pairs) in pairs.iter().enumerate() {
+        if let [model1, model2, ..] = &models[k.min(models.len())..] {
+            check_pairs(pairs, model1, model2)?;
+        }
+    }
+    let mut hooks = Hooks::default();
+    hooks.start_stage(Stage::Projection);
+    let mut models = models
+        .into_iter()
+        .map(ProjectionModel::new)
+        .collect::<Vec<_>>();
+    for (k, pairs) in pairs
+        .iter()
+        .enumerate()
+        .take(models.len().saturating_sub(1))
+    {
+        let (done, rest) = models.split_at_mut(k + 1);
+        rest[0].align_to(&done[k], pairs);
+    }
+    Ok(MultiMergedModel::merge(&models, config, &mut hooks))
 }
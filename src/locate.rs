@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+
+use crate::geo::{Face, EPS};
+
+const MAX_WALK_STEPS: usize = 1000;
+
+/// Faces across the edges of a triangle mesh, to locate points by walking
+/// from face to face instead of testing every face.
+#[derive(Debug, Clone)]
+pub(crate) struct Adjacency {
+    adjacent: Vec<[usize; 3]>, // face across the edge opposite each corner
+}
+
+impl Adjacency {
+    pub(crate) fn new(faces: &[Face]) -> Self {
+        let key = |a: usize, b: usize| (a.min(b), a.max(b));
+        let mut edge_faces = HashMap::<(usize, usize), Vec<usize>>::new();
+        for (k, f) in faces.iter().enumerate() {
+            for i in 0..3 {
+                edge_faces
+                    .entry(key(f[i], f[(i + 1) % 3]))
+                    .or_default()
+                    .push(k);
+            }
+        }
+        let adjacent = faces
+            .iter()
+            .enumerate()
+            .map(|(k, f)| {
+                let mut adj = [usize::MAX; 3];
+                for (i, a) in adj.iter_mut().enumerate() {
+                    let e = key(f[(i + 1) % 3], f[(i + 2) % 3]);
+                    if let Some(&other) = edge_faces[&e].iter().find(|&&o| o != k) {
+                        *a = other;
+                    }
+                }
+                adj
+            })
+            .collect();
+        Self { adjacent }
+    }
+
+    /// The face across the edge of face `k` opposite `corner`, if any.
+    pub(crate) fn across(&self, k: usize, corner: usize) -> Option<usize> {
+        Some(self.adjacent[k][corner]).filter(|&o| o != usize::MAX)
+    }
+
+    /// The face containing a point, given its barycentric coordinates on each
+    /// face, walking from face `hint` and falling back to the face nearest to
+    /// the point. The coordinates are only positive on the containing face.
+    pub(crate) fn locate(
+        &self,
+        hint: usize,
+        barycentric: impl Fn(usize) -> Option<[f64; 3]>,
+    ) -> Option<(usize, [f64; 3])> {
+        let mut k = hint;
+        for _ in 0..MAX_WALK_STEPS.min(self.adjacent.len()) {
+            let w = match barycentric(k) {
+                Some(w) => w,
+                None => break,
+            };
+            let i = (0..3)
+                .min_by(|&i, &j| w[i].partial_cmp(&w[j]).unwrap())
+                .unwrap();
+            if w[i] > -EPS {
+                return Some((k, w));
+            }
+            match self.across(k, i) {
+                Some(other) => k = other,
+                None => break,
+            }
+        }
+        self.nearest(barycentric)
+    }
+
+    /// The face with the largest smallest barycentric coordinate of a point,
+    /// for points falling between faces.
+    pub(crate) fn nearest(
+        &self,
+        barycentric: impl Fn(usize) -> Option<[f64; 3]>,
+    ) -> Option<(usize, [f64; 3])> {
+        let min = |w: &[f64; 3]| w[0].min(w[1]).min(w[2]);
+        (0..self.adjacent.len())
+            .filter_map(|k| barycentric(k).map(|w| (k, w)))
+            .max_by(|(_, a), (_, b)| min(a).partial_cmp(&min(b)).unwrap())
+    }
+}
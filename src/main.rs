@@ -1,19 +1,242 @@
 use std::path::Path;
 
-use clap::{App, Arg};
-use morphing::{Config, Model};
+use std::fs::File;
+use std::io::{self, prelude::*, BufReader};
+use std::process;
+use std::{fmt, str::FromStr};
+
+use clap::{App, Arg, ArgMatches};
+use log::{Level, Log, Metadata, Record};
+use morphing::{
+    Config, Easing, Extrapolation, FeaturePair, InterpolationMethod, MergeStats, Model,
+    MultiMergedModel, NormalWeighting, RatioField, Spline,
+};
+
+const REMESH_ITERATIONS: usize = 5;
+
+/// Writes log messages to stderr.
+struct StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!(
+                "{}: {}",
+                record.level().as_str().to_lowercase(),
+                record.args()
+            );
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: StderrLogger = StderrLogger;
+
+fn init_logger(verbosity: u64) {
+    let level = match verbosity {
+        0 => Level::Warn,
+        1 => Level::Info,
+        _ => Level::Debug,
+    };
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(level.to_level_filter());
+}
+
+fn print_stats(stats: &MergeStats, format: &str) {
+    if format == "json" {
+        println!("{}", stats.to_json());
+    } else {
+        print!("{}", stats);
+    }
+}
+
+/// Loads the delay of each of the `nr_verts` merged vertices.
+fn load_delays(filename: &str, nr_verts: usize) -> io::Result<Vec<f64>> {
+    let reader = BufReader::new(File::open(filename)?);
+    let mut delays = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        delays.push(line.parse::<f64>().map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid delay \"{}\"", line),
+            )
+        })?);
+    }
+    if delays.len() != nr_verts {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Expected {} delays, got {}", nr_verts, delays.len()),
+        ));
+    }
+    Ok(delays)
+}
+
+/// Loads 1-based vertex pairs, checked against the vertex counts of the
+/// models.
+fn load_pairs(filename: &str, nr_verts: (usize, usize)) -> io::Result<Vec<FeaturePair>> {
+    let reader = BufReader::new(File::open(filename)?);
+    let mut pairs = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        let vals = line.split_whitespace().collect::<Vec<_>>();
+        if vals.len() < 2 || vals[0].starts_with('#') {
+            continue;
+        }
+        let index = |s: &str, n: usize| match s.parse::<usize>() {
+            Ok(i) if i > 0 && i <= n => Ok(i - 1),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid vertex pair \"{}\"", line),
+            )),
+        };
+        pairs.push(FeaturePair {
+            index1: index(vals[0], nr_verts.0)?,
+            index2: index(vals[1], nr_verts.1)?,
+            cost: 0.0,
+        });
+    }
+    Ok(pairs)
+}
+
+fn exit_with_error(msg: &str) -> ! {
+    eprintln!("error: {}", msg);
+    process::exit(1)
+}
+
+fn load_model(filename: &str) -> Model {
+    Model::load(filename).unwrap_or_else(|e| {
+        exit_with_error(&format!("Cannot open model file \"{}\": {}", filename, e))
+    })
+}
+
+/// Applies the extrapolation mode to all ratios, returns whether some of them
+/// are still outside `[0, 1]`.
+fn extrapolate(ratios: &mut [f64], mode: Extrapolation) -> bool {
+    let mut clamped = false;
+    for r in ratios.iter_mut() {
+        let new = mode.apply(*r).unwrap_or_else(|| {
+            exit_with_error(&format!(
+                "Ratio {} is outside [0, 1], use \"--extrapolation allow\" to extrapolate",
+                r
+            ))
+        });
+        clamped |= new != *r;
+        *r = new;
+    }
+    if clamped {
+        log::warn!("Ratios outside [0, 1] clamped");
+    }
+    ratios.iter().any(|r| !(0.0..=1.0).contains(r))
+}
+
+/// Applies the extrapolation mode to the weights of the models, returns
+/// whether some of them are still outside `[0, 1]`.
+fn extrapolate_weights(weights: &mut Vec<f64>, mode: Extrapolation) -> bool {
+    let new = mode.apply_weights(weights).unwrap_or_else(|| {
+        exit_with_error("Weights are outside [0, 1], use \"--extrapolation allow\" to extrapolate")
+    });
+    if new != *weights {
+        log::warn!("Weights outside [0, 1] clamped, keeping their sum");
+        *weights = new;
+    }
+    weights.iter().any(|w| !(0.0..=1.0).contains(w))
+}
+
+fn save_result(
+    mut model: Model,
+    output: &str,
+    check: bool,
+    normals: Option<(NormalWeighting, f64)>,
+) {
+    if let Some((weighting, crease)) = normals {
+        model.compute_normals(weighting, crease);
+    }
+    if check {
+        let count = model.self_intersections();
+        if count > 0 {
+            log::warn!("{} pairs of faces intersect in the result", count);
+        } else {
+            log::info!("No self-intersections in the result");
+        }
+    }
+    model.save(output).unwrap();
+}
+
+/// The value of the argument `name` if given, exiting with an error if it
+/// cannot be parsed.
+fn parse_arg<T>(matches: &ArgMatches, name: &str) -> Option<T>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    matches.value_of(name).map(|s| {
+        s.parse().unwrap_or_else(|e| {
+            exit_with_error(&format!(
+                "Invalid value \"{}\" for --{}: {}",
+                s,
+                name.replace('_', "-"),
+                e
+            ))
+        })
+    })
+}
+
+/// Weights given with `--weights`, one for each of the `nr_models` models.
+fn parse_weights(matches: &ArgMatches, nr_models: usize) -> Option<Vec<f64>> {
+    let weights = matches.values_of("weights").map(|values| {
+        values
+            .map(|w| {
+                w.parse::<f64>()
+                    .unwrap_or_else(|_| exit_with_error(&format!("Invalid weight \"{}\"", w)))
+            })
+            .collect::<Vec<_>>()
+    })?;
+    if weights.len() != nr_models {
+        exit_with_error(&format!(
+            "Expected {} weights, one per model, got {}",
+            nr_models,
+            weights.len()
+        ));
+    }
+    Some(weights)
+}
 
 fn main() {
     let matches = App::new("morphing")
         .arg(
             Arg::with_name("obj1")
                 .required(true)
-                .help("Model file 1 (*.obj)"),
+                .help("Model file 1 (*.obj or *.ply)"),
         )
         .arg(
             Arg::with_name("obj2")
                 .required(true)
-                .help("Model file 2 (*.obj)"),
+                .help("Model file 2 (*.obj or *.ply)"),
+        )
+        .arg(
+            Arg::with_name("more")
+                .multiple(true)
+                .conflicts_with_all(&[
+                    "pairs",
+                    "simplify",
+                    "max_error",
+                    "remesh",
+                    "subdivide",
+                    "wave",
+                    "delays",
+                    "world",
+                ])
+                .help("Further model files, to merge all models into a common mesh"),
         )
         .arg(
             Arg::with_name("output")
@@ -30,6 +253,92 @@ fn main() {
                 .default_value("0.5")
                 .help("Morphing ratio"),
         )
+        .arg(
+            Arg::with_name("easing")
+                .long("easing")
+                .takes_value(true)
+                .default_value("linear")
+                .help(
+                    "Timing curve applied to the ratio: linear, smoothstep, ease-in, ease-out, \
+                     ease-in-out, bezier:<x1>,<y1>,<x2>,<y2> or table:<v0>,<v1>,...",
+                ),
+        )
+        .arg(
+            Arg::with_name("weights")
+                .long("weights")
+                .takes_value(true)
+                .require_delimiter(true)
+                .allow_hyphen_values(true)
+                .conflicts_with_all(&["wave", "delays"])
+                .help("Blend the models with one weight per model instead of interpolating"),
+        )
+        .arg(
+            Arg::with_name("extrapolation")
+                .long("extrapolation")
+                .takes_value(true)
+                .possible_values(&["clamp", "allow", "reject"])
+                .default_value("clamp")
+                .help("What to do with ratios or weights outside [0, 1]"),
+        )
+        .arg(
+            Arg::with_name("world")
+                .long("world")
+                .conflicts_with("weights")
+                .help("Interpolate the positions, sizes and orientations of the models too"),
+        )
+        .arg(
+            Arg::with_name("crease")
+                .long("crease")
+                .takes_value(true)
+                .help("Compute the normals of the result with this crease angle in degrees, 0 for flat and 180 for smooth shading, instead of interpolating the normals of the models"),
+        )
+        .arg(
+            Arg::with_name("normal_weighting")
+                .long("normal-weighting")
+                .takes_value(true)
+                .possible_values(&["area", "angle"])
+                .default_value("angle")
+                .help("Weight of the faces around a vertex when computing normals"),
+        )
+        .arg(
+            Arg::with_name("check")
+                .long("check")
+                .help("Check the result for self-intersections, always done when extrapolating"),
+        )
+        .arg(
+            Arg::with_name("spline")
+                .long("spline")
+                .takes_value(true)
+                .possible_values(&["linear", "catmull-rom", "b-spline"])
+                .default_value("catmull-rom")
+                .help("Curve through the models when merging more than two"),
+        )
+        .arg(
+            Arg::with_name("method")
+                .long("method")
+                .takes_value(true)
+                .possible_values(&["linear", "arap", "intrinsic", "laplacian"])
+                .conflicts_with_all(&["more", "weights"])
+                .help("Interpolation method of two models without weights, linear by default"),
+        )
+        .arg(Arg::with_name("wave").long("wave").takes_value(true).help(
+            "Morph vertices with delays along dir:<x>,<y>,<z>, by distance from \
+                     seed:<x>,<y>,<z> or by geodesic distance from geodesic:<x>,<y>,<z>",
+        ))
+        .arg(
+            Arg::with_name("delays")
+                .long("delays")
+                .takes_value(true)
+                .conflicts_with("wave")
+                .help("Delays file, one value in [0, 1] per merged vertex"),
+        )
+        .arg(
+            Arg::with_name("spread")
+                .long("spread")
+                .takes_value(true)
+                .default_value("0.5")
+                .help("Fraction of the morph over which the delays are spread"),
+        )
         .arg(
             Arg::with_name("edge_only")
                 .long("edge")
@@ -42,27 +351,211 @@ fn main() {
                 .short("p")
                 .help("All vertices are on the shpere, do not project back to the origin model"),
         )
+        .arg(
+            Arg::with_name("snap")
+                .long("snap")
+                .takes_value(true)
+                .default_value("0")
+                .help("Snap vertices closer than this angle (in degrees) on the sphere"),
+        )
+        .arg(
+            Arg::with_name("simplify")
+                .long("simplify")
+                .takes_value(true)
+                .help("Simplify the merged model down to this number of faces"),
+        )
+        .arg(
+            Arg::with_name("max_error")
+                .long("max-error")
+                .takes_value(true)
+                .help("Maximum quadric error allowed when simplifying the merged model"),
+        )
+        .arg(
+            Arg::with_name("subdivide")
+                .long("subdivide")
+                .takes_value(true)
+                .help("Levels of Loop subdivision applied to the merged model"),
+        )
+        .arg(
+            Arg::with_name("remesh")
+                .long("remesh")
+                .takes_value(true)
+                .help("Remesh the merged model isotropically with this edge length"),
+        )
+        .arg(
+            Arg::with_name("auto")
+                .long("auto")
+                .short("a")
+                .help("Match salient points automatically and align the models before merging"),
+        )
+        .arg(
+            Arg::with_name("pairs")
+                .long("pairs")
+                .takes_value(true)
+                .conflicts_with("auto")
+                .help("Vertex pairs file to align the models, one \"<id1> <id2>\" per line"),
+        )
         .arg(
             Arg::with_name("scale")
                 .long("scale")
                 .short("s")
                 .help("Scale models to the same scale"),
         )
+        .arg(
+            Arg::with_name("keep_coords")
+                .long("keep-coords")
+                .conflicts_with_all(&["scale", "world"])
+                .help("Write the results in the original coordinates of the models instead of centered and normalized"),
+        )
+        .arg(
+            Arg::with_name("stats")
+                .long("stats")
+                .takes_value(true)
+                .possible_values(&["table", "json"])
+                .help("Print statistics of the merge as a table or JSON"),
+        )
+        .arg(
+            Arg::with_name("verbose")
+                .long("verbose")
+                .short("v")
+                .multiple(true)
+                .help("Log more details, repeat for debug messages"),
+        )
         .get_matches();
 
+    init_logger(matches.occurrences_of("verbose"));
+
     let ratio = matches.value_of("ratio").unwrap().parse().unwrap();
+    let easing = matches
+        .value_of("easing")
+        .unwrap()
+        .parse::<Easing>()
+        .unwrap_or_else(|e| exit_with_error(&e));
+    let extrapolation = matches
+        .value_of("extrapolation")
+        .unwrap()
+        .parse::<Extrapolation>()
+        .unwrap();
+    let check = matches.is_present("check");
+    let target_faces = parse_arg::<usize>(&matches, "simplify");
+    let max_error = parse_arg::<f64>(&matches, "max_error");
+    let edge_length = parse_arg::<f64>(&matches, "remesh");
+    if edge_length.is_some_and(|l| !(l.is_finite() && l > 0.0)) {
+        exit_with_error("The remeshing edge length must be positive");
+    }
+    let levels = parse_arg::<usize>(&matches, "subdivide");
+    let wave = matches.value_of("wave").map(|wave| {
+        wave.parse::<RatioField>()
+            .unwrap_or_else(|e| exit_with_error(&e))
+    });
+    let spread = parse_arg::<f64>(&matches, "spread").unwrap();
+    let normals = parse_arg::<f64>(&matches, "crease").map(|crease| {
+        let weighting = parse_arg::<NormalWeighting>(&matches, "normal_weighting").unwrap();
+        (weighting, crease.to_radians())
+    });
     let fname1 = matches.value_of("obj1").unwrap();
     let fname2 = matches.value_of("obj2").unwrap();
-
-    let model1 = Model::load(fname1).expect(&format!("Cannot open model file \"{}\"", fname1));
-    let model2 = Model::load(fname2).expect(&format!("Cannot open model file \"{}\"", fname2));
+    let nr_models = 2 + matches.values_of("more").map_or(0, |more| more.count());
+    let weights = parse_weights(&matches, nr_models);
     let config = Config {
         edge_only: matches.occurrences_of("edge_only") > 0,
         sphere_only: matches.occurrences_of("shpere_only") > 0,
         scale: matches.occurrences_of("scale") > 0,
+        keep_coords: matches.is_present("keep_coords"),
+        snap_tolerance: parse_arg::<f64>(&matches, "snap").unwrap().to_radians(),
+    };
+
+    if let Some(more) = matches.values_of("more") {
+        let fnames = [fname1, fname2]
+            .iter()
+            .copied()
+            .chain(more)
+            .collect::<Vec<_>>();
+        let models = fnames.iter().map(|f| load_model(f)).collect::<Vec<_>>();
+        let pairs = if matches.occurrences_of("auto") > 0 {
+            models
+                .windows(2)
+                .map(|w| morphing::match_features(&w[0], &w[1]))
+                .collect::<Vec<_>>()
+        } else {
+            Vec::new()
+        };
+
+        let merged_model = morphing::merge_all(models, &pairs, config)
+            .unwrap_or_else(|e| exit_with_error(&e.to_string()));
+        if let Some(format) = matches.value_of("stats") {
+            print_stats(merged_model.stats(), format);
+        }
+        let stems = fnames
+            .iter()
+            .map(|f| Path::new(f).file_stem().unwrap().to_string_lossy())
+            .collect::<Vec<_>>();
+        merged_model
+            .save(&format!("{}.obj", stems.join("_")))
+            .unwrap();
+
+        if let Some(output) = matches.value_of("output") {
+            let spline = matches
+                .value_of("spline")
+                .unwrap()
+                .parse::<Spline>()
+                .unwrap();
+            let (result, outside) = match weights {
+                Some(mut weights) => {
+                    let outside = extrapolate_weights(&mut weights, extrapolation);
+                    let result = merged_model
+                        .blend(&weights)
+                        .unwrap_or_else(|e| exit_with_error(&e.to_string()));
+                    (result, outside)
+                }
+                None => {
+                    let mut ratio = [easing.apply(ratio)];
+                    let outside = extrapolate(&mut ratio, extrapolation);
+                    (merged_model.interpolation(ratio[0], spline), outside)
+                }
+            };
+            save_result(result, output, check || outside, normals);
+        }
+        return;
+    }
+
+    let model1 = load_model(fname1);
+    let model2 = load_model(fname2);
+
+    let pairs = if matches.occurrences_of("auto") > 0 {
+        let pairs = morphing::match_features(&model1, &model2);
+        // the pairs are printed to stderr in the format of the pairs file, so
+        // that stdout only has the statistics
+        log::info!("Suggested vertex pairs:");
+        for p in &pairs {
+            eprintln!("{} {} # cost {:.4}", p.index1 + 1, p.index2 + 1, p.cost);
+        }
+        pairs
+    } else if let Some(fname) = matches.value_of("pairs") {
+        load_pairs(fname, (model1.nr_verts(), model2.nr_verts())).unwrap_or_else(|e| {
+            exit_with_error(&format!("Cannot load pairs file \"{}\": {}", fname, e))
+        })
+    } else {
+        Vec::new()
     };
 
-    let merged_model = morphing::merge(model1, model2, config);
+    let mut merged_model = morphing::merge_with_pairs(model1, model2, &pairs, config)
+        .unwrap_or_else(|e| exit_with_error(&e.to_string()));
+    if let Some(format) = matches.value_of("stats") {
+        print_stats(merged_model.stats(), format);
+    }
+    if target_faces.is_some() || max_error.is_some() {
+        merged_model = merged_model.simplify(
+            target_faces.unwrap_or(0),
+            max_error.unwrap_or(f64::INFINITY),
+        );
+    }
+    if let Some(edge_length) = edge_length {
+        merged_model = merged_model.remesh(edge_length, REMESH_ITERATIONS);
+    }
+    if let Some(levels) = levels {
+        merged_model = merged_model.subdivide(levels);
+    }
     let merged_fname = format!(
         "{}_{}.obj",
         Path::new(fname1).file_stem().unwrap().to_string_lossy(),
@@ -71,6 +564,100 @@ fn main() {
     merged_model.save(&merged_fname).unwrap();
 
     if let Some(output) = matches.value_of("output") {
-        merged_model.interpolation(ratio).save(output).unwrap();
+        if let Some(mut weights) = weights {
+            let outside = extrapolate_weights(&mut weights, extrapolation);
+            let result = MultiMergedModel::from(merged_model)
+                .blend(&weights)
+                .unwrap_or_else(|e| exit_with_error(&e.to_string()));
+            save_result(result, output, check || outside, normals);
+            return;
+        }
+        let field = match wave {
+            Some(wave) => Some(wave),
+            None => matches.value_of("delays").map(|fname| {
+                RatioField::Values(load_delays(fname, merged_model.nr_verts()).unwrap_or_else(
+                    |e| exit_with_error(&format!("Cannot load delays file \"{}\": {}", fname, e)),
+                ))
+            }),
+        };
+        let method = parse_arg(&matches, "method").unwrap_or(InterpolationMethod::Linear);
+        let mut ratios = match field {
+            Some(field) => field
+                .ratios(&merged_model, ratio, spread)
+                .unwrap_or_else(|e| exit_with_error(&e.to_string()))
+                .iter()
+                .map(|&r| easing.apply(r))
+                .collect(),
+            None => vec![easing.apply(ratio); merged_model.nr_verts()],
+        };
+        let outside = extrapolate(&mut ratios, extrapolation);
+        let result = if matches.is_present("world") {
+            merged_model.world_interpolation_with_method(&ratios, method)
+        } else {
+            merged_model.interpolation_with_method(&ratios, method)
+        };
+        save_result(result, output, check || outside, normals);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes `content` to a file of the temporary directory.
+    fn temp_file(name: &str, content: &str) -> String {
+        let path = std::env::temp_dir().join(format!("morphing-{}-{}", process::id(), name));
+        std::fs::write(&path, content).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn pairs_are_loaded_one_based() {
+        let fname = temp_file("pairs.txt", "# id1 id2\n1 2\n\n8 6 # cost 0.1\n");
+        let pairs = load_pairs(&fname, (8, 6)).unwrap();
+        std::fs::remove_file(fname).unwrap();
+        assert_eq!(pairs.len(), 2);
+        assert_eq!((pairs[0].index1, pairs[0].index2), (0, 1));
+        assert_eq!((pairs[1].index1, pairs[1].index2), (7, 5));
+    }
+
+    #[test]
+    fn malformed_pairs_are_rejected() {
+        for (k, content) in ["0 1\n", "1 7\n", "9 1\n", "a 1\n", "1 -2\n", "1.5 2\n"]
+            .iter()
+            .enumerate()
+        {
+            let fname = temp_file(&format!("bad-pairs-{}.txt", k), content);
+            let err = load_pairs(&fname, (8, 6)).unwrap_err();
+            std::fs::remove_file(fname).unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{:?}", content);
+        }
+    }
+
+    #[test]
+    fn delays_are_loaded_and_counted() {
+        let fname = temp_file(
+            "delays.txt",
+            "# delays
+0
+0.5
+
+1
+",
+        );
+        assert_eq!(load_delays(&fname, 3).unwrap(), [0.0, 0.5, 1.0]);
+        let err = load_delays(&fname, 4).unwrap_err();
+        std::fs::remove_file(fname).unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let fname = temp_file(
+            "bad-delays.txt",
+            "0
+half
+",
+        );
+        let err = load_delays(&fname, 2).unwrap_err();
+        std::fs::remove_file(fname).unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
     }
 }
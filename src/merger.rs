@@ -0,0 +1,399 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::geo::{Face, Vertex};
+use crate::model::ProjectionModel;
+use crate::{Config, FeaturePair, MergedModel, Model};
+
+const PROGRESS_STEP: f64 = 0.01;
+
+/// Stages of a merge, in order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Stage {
+    Projection,     // models onto the sphere
+    Overlay,        // arcs of both models intersected on the sphere
+    BackProjection, // merged vertices back onto the models
+    FaceTracing,    // faces of the overlaid arcs
+    Triangulation,  // faces split into triangles
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Stage::Projection => "projection",
+            Stage::Overlay => "overlay",
+            Stage::BackProjection => "back projection",
+            Stage::FaceTracing => "face tracing",
+            Stage::Triangulation => "triangulation",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Results of a stage, passed to the callback when the stage is done.
+#[derive(Debug)]
+pub enum Intermediate<'a> {
+    Projection {
+        sphere_verts: &'a [&'a [Vertex]], // of each model
+    },
+    Overlay {
+        sphere_verts: &'a [Vertex],
+        edges: &'a [(usize, usize)],
+    },
+    BackProjection {
+        positions: &'a [Vec<Vertex>], // of each merged vertex on every model
+    },
+    FaceTracing {
+        faces: &'a [Face],
+    },
+    Triangulation {
+        faces: &'a [Face],
+    },
+}
+
+/// Flag shared with a running merge to abort it.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Merge cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Reasons for a merge to fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeError {
+    Cancelled,
+    InvalidPair(usize), // index of a vertex pair out of range of its models
+}
+
+impl From<Cancelled> for MergeError {
+    fn from(_: Cancelled) -> Self {
+        MergeError::Cancelled
+    }
+}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MergeError::Cancelled => write!(f, "Merge cancelled"),
+            MergeError::InvalidPair(k) => write!(f, "Vertex pair {} out of range", k + 1),
+        }
+    }
+}
+
+impl std::error::Error for MergeError {}
+
+/// Checks that `pairs` index vertices of `model1` and `model2`.
+pub(crate) fn check_pairs(
+    pairs: &[FeaturePair],
+    model1: &Model,
+    model2: &Model,
+) -> Result<(), MergeError> {
+    match pairs
+        .iter()
+        .position(|p| p.index1 >= model1.nr_verts() || p.index2 >= model2.nr_verts())
+    {
+        Some(k) => Err(MergeError::InvalidPair(k)),
+        None => Ok(()),
+    }
+}
+
+/// Callbacks and cancellation of a merge, timing its stages.
+#[derive(Default)]
+pub(crate) struct Hooks<'a> {
+    progress: Option<&'a mut dyn FnMut(Stage, f64)>,
+    intermediate: Option<&'a mut dyn FnMut(&Intermediate)>,
+    cancel: Option<&'a CancelToken>,
+    last: Option<(Stage, f64)>,
+    started: Option<(Stage, Instant)>,
+    timings: Vec<(Stage, Duration)>,
+}
+
+impl<'a> Hooks<'a> {
+    /// Reports the progress of `stage` (from 0 to 1) in steps of at least
+    /// `PROGRESS_STEP`, and fails if the merge was cancelled.
+    pub(crate) fn progress(&mut self, stage: Stage, fraction: f64) -> Result<(), Cancelled> {
+        if self.cancel.is_some_and(|c| c.is_cancelled()) {
+            return Err(Cancelled);
+        }
+        self.start_stage(stage);
+        let report = match self.last {
+            Some((s, f)) if s == stage => fraction >= f + PROGRESS_STEP || fraction >= 1.0,
+            _ => true,
+        };
+        if report && self.last != Some((stage, 1.0)) {
+            self.last = Some((stage, fraction));
+            if let Some(progress) = &mut self.progress {
+                progress(stage, fraction);
+            }
+        }
+        Ok(())
+    }
+
+    /// Starts timing `stage` unless it is the current one.
+    pub(crate) fn start_stage(&mut self, stage: Stage) {
+        if self.started.map(|(s, _)| s) != Some(stage) {
+            self.end_stage();
+            self.started = Some((stage, Instant::now()));
+        }
+    }
+
+    fn end_stage(&mut self) {
+        if let Some((stage, start)) = self.started.take() {
+            self.timings.push((stage, start.elapsed()));
+        }
+    }
+
+    /// Time spent in each stage so far.
+    pub(crate) fn timings(&mut self) -> Vec<(Stage, Duration)> {
+        self.end_stage();
+        std::mem::take(&mut self.timings)
+    }
+
+    /// Whether intermediate results are wanted, as they may need to be
+    /// collected first.
+    pub(crate) fn wants_intermediate(&self) -> bool {
+        self.intermediate.is_some()
+    }
+
+    pub(crate) fn intermediate(&mut self, result: Intermediate) {
+        if let Some(intermediate) = &mut self.intermediate {
+            intermediate(&result);
+        }
+    }
+}
+
+type ProgressFn<'a> = Box<dyn FnMut(Stage, f64) + 'a>;
+type IntermediateFn<'a> = Box<dyn FnMut(&Intermediate) + 'a>;
+
+/// Merge of two models with progress callbacks, cancellation and access to
+/// the results of each stage.
+pub struct Merger<'a> {
+    model1: Model,
+    model2: Model,
+    pairs: Vec<FeaturePair>,
+    config: Config,
+    progress: Option<ProgressFn<'a>>,
+    intermediate: Option<IntermediateFn<'a>>,
+    cancel: Option<CancelToken>,
+}
+
+impl<'a> Merger<'a> {
+    pub fn new(model1: Model, model2: Model) -> Self {
+        Self {
+            model1,
+            model2,
+            pairs: Vec::new(),
+            config: Config::default(),
+            progress: None,
+            intermediate: None,
+            cancel: None,
+        }
+    }
+
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Vertex pairs to overlay, as in `merge_with_pairs`.
+    pub fn pairs(mut self, pairs: &[FeaturePair]) -> Self {
+        self.pairs = pairs.to_vec();
+        self
+    }
+
+    /// Called with the progress of each stage, from 0 to 1.
+    pub fn on_progress(mut self, f: impl FnMut(Stage, f64) + 'a) -> Self {
+        self.progress = Some(Box::new(f));
+        self
+    }
+
+    /// Called with the results of each stage when it is done.
+    pub fn on_intermediate(mut self, f: impl FnMut(&Intermediate) + 'a) -> Self {
+        self.intermediate = Some(Box::new(f));
+        self
+    }
+
+    pub fn cancel_token(mut self, token: CancelToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Fails if cancelled, or if a vertex pair is out of range.
+    pub fn merge(mut self) -> Result<MergedModel, MergeError> {
+        check_pairs(&self.pairs, &self.model1, &self.model2)?;
+        let mut hooks = Hooks {
+            progress: self.progress.as_deref_mut().map(|f| f as _),
+            intermediate: self.intermediate.as_deref_mut().map(|f| f as _),
+            cancel: self.cancel.as_ref(),
+            ..Default::default()
+        };
+
+        hooks.progress(Stage::Projection, 0.0)?;
+        let model1 = ProjectionModel::new(self.model1);
+        hooks.progress(Stage::Projection, 0.5)?;
+        let mut model2 = ProjectionModel::new(self.model2);
+        model2.align_to(&model1, &self.pairs);
+        hooks.progress(Stage::Projection, 1.0)?;
+        if hooks.wants_intermediate() {
+            let sphere_verts = [model1.sphere_verts(), model2.sphere_verts()];
+            hooks.intermediate(Intermediate::Projection {
+                sphere_verts: &sphere_verts,
+            });
+        }
+
+        Ok(MergedModel::merge_with_hooks(
+            model1,
+            model2,
+            self.config,
+            &mut hooks,
+        )?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{cube, octahedron};
+
+    const STAGES: [Stage; 5] = [
+        Stage::Projection,
+        Stage::Overlay,
+        Stage::BackProjection,
+        Stage::FaceTracing,
+        Stage::Triangulation,
+    ];
+
+    fn index(stage: Stage) -> usize {
+        STAGES.iter().position(|&s| s == stage).unwrap()
+    }
+
+    #[test]
+    fn reports_progress_of_each_stage() {
+        let mut reports = Vec::new();
+        let mut results = Vec::new();
+        let merged = Merger::new(cube(), octahedron())
+            .on_progress(|stage, fraction| reports.push((stage, fraction)))
+            .on_intermediate(|result| {
+                results.push(match result {
+                    Intermediate::Projection { sphere_verts } => {
+                        assert_eq!(sphere_verts.len(), 2);
+                        Stage::Projection
+                    }
+                    Intermediate::Overlay { .. } => Stage::Overlay,
+                    Intermediate::BackProjection { .. } => Stage::BackProjection,
+                    Intermediate::FaceTracing { .. } => Stage::FaceTracing,
+                    Intermediate::Triangulation { faces } => {
+                        assert!(!faces.is_empty());
+                        Stage::Triangulation
+                    }
+                })
+            })
+            .merge()
+            .unwrap();
+        assert_eq!(results, STAGES);
+        // stages in order, each from 0 to 1
+        for w in reports.windows(2) {
+            let ((s1, f1), (s2, f2)) = (w[0], w[1]);
+            assert!(index(s1) < index(s2) || s1 == s2 && f1 < f2, "{:?}", w);
+        }
+        for stage in STAGES {
+            assert!(reports.contains(&(stage, 1.0)), "{}", stage);
+        }
+        assert!(reports.iter().all(|&(_, f)| (0.0..=1.0).contains(&f)));
+        let timed = merged
+            .stats()
+            .timings
+            .iter()
+            .map(|t| t.0)
+            .collect::<Vec<_>>();
+        assert_eq!(timed, STAGES);
+    }
+
+    #[test]
+    fn progress_is_reported_in_steps() {
+        let mut count = 0;
+        let mut progress = |_, _| count += 1;
+        let mut hooks = Hooks {
+            progress: Some(&mut progress),
+            ..Default::default()
+        };
+        for k in 0..=1000 {
+            hooks.progress(Stage::Overlay, k as f64 / 1000.0).unwrap();
+        }
+        hooks.progress(Stage::Overlay, 1.0).unwrap();
+        drop(hooks);
+        assert!((100..=102).contains(&count), "{}", count);
+    }
+
+    #[test]
+    fn cancelled_merges_fail() {
+        let token = CancelToken::new();
+        token.cancel();
+        let result = Merger::new(cube(), octahedron())
+            .cancel_token(token)
+            .merge();
+        assert_eq!(result.err(), Some(MergeError::Cancelled));
+
+        // cancelled during the overlay, no later stage is reported
+        let token = CancelToken::new();
+        let mut stages = Vec::new();
+        let result = Merger::new(cube(), octahedron())
+            .cancel_token(token.clone())
+            .on_progress(|stage, _| {
+                stages.push(stage);
+                if stage == Stage::Overlay {
+                    token.cancel();
+                }
+            })
+            .merge();
+        assert_eq!(result.err(), Some(MergeError::Cancelled));
+        assert_eq!(stages.last(), Some(&Stage::Overlay));
+    }
+
+    #[test]
+    fn pairs_are_checked() {
+        let pairs = [
+            FeaturePair {
+                index1: 0,
+                index2: 0,
+                cost: 0.0,
+            },
+            FeaturePair {
+                index1: 0,
+                index2: 6,
+                cost: 0.0,
+            },
+        ];
+        let result = Merger::new(cube(), octahedron()).pairs(&pairs).merge();
+        assert_eq!(result.err(), Some(MergeError::InvalidPair(1)));
+        assert_eq!(
+            MergeError::InvalidPair(1).to_string(),
+            "Vertex pair 2 out of range"
+        );
+    }
+}
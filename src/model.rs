@@ -1,20 +1,49 @@
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
 use std::io::{self, prelude::*, BufReader, BufWriter};
 use std::vec::Vec;
 use std::{cmp::Ordering, ops::Deref};
 
-use crate::geo::{adjust_order, Arc, ArcIntersectionResult, Face, Triangle, EPS};
+use crate::attribute::Attribute;
+use crate::feature::FeaturePair;
+use crate::geo::{
+    adjust_order, triangulate, Arc, ArcIntersectionResult, Face, Matrix3, Quaternion, Triangle, EPS,
+};
 use crate::graph::{Edge, EdgeList, Graph, RcGraphEdge};
+use crate::interpolate::InterpolationMethod;
+use crate::label::{combine_labels, FaceLabel, LabelTable};
+use crate::locate::Adjacency;
+use crate::merger::{Cancelled, Hooks, Intermediate, Stage};
+use crate::placement::Placement;
+use crate::provenance::{Provenance, SourceLocation, VertexProvenance, VertexSource};
+use crate::stats::{IntersectionCounts, MergeStats};
 use crate::Config;
 use crate::Vertex;
 
 const SPHERE_RADIUS: f64 = 100.0;
 const MODEL_SIZE: f64 = 1.0;
+const WARP_WIDTH: f64 = 0.5;
+const WARP_STEPS: usize = 8;
+const CORNER_EPS: f64 = 1e-9;
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Model {
-    verts: Vec<Vertex>,
-    faces: Vec<Face>,
+    pub(crate) verts: Vec<Vertex>,
+    pub(crate) faces: Vec<Face>,
+    pub(crate) colors: Vec<Vertex>, // RGB in [0, 1] per vertex, empty if not colored
+    // one or more sets of texture coordinates, indexed by the corners of
+    // `face_texcoords`, empty if the model is not textured
+    pub(crate) texcoords: Vec<Vec<Vertex>>,
+    pub(crate) face_texcoords: Vec<Face>,
+    // unit normals, indexed by the corners of `face_normals`
+    pub(crate) normals: Vec<Vertex>,
+    pub(crate) face_normals: Vec<Face>,
+    pub(crate) attributes: BTreeMap<String, Attribute>,
+    // distinct groups and materials, indexed by `face_labels`, empty if the
+    // model has none
+    pub(crate) labels: Vec<FaceLabel>,
+    pub(crate) face_labels: Vec<usize>,
 }
 
 #[derive(Debug)]
@@ -24,6 +53,9 @@ pub struct ProjectionModel {
 
     center: Vertex,
     sphere_verts: Vec<Vertex>,
+    rotation: Matrix3,
+    warped: bool,
+    adjacency: Adjacency,
 }
 
 impl Deref for ProjectionModel {
@@ -34,10 +66,24 @@ impl Deref for ProjectionModel {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MergedModel {
-    faces: Vec<Face>,
-    vert_pairs: Vec<(Vertex, Vertex)>,
+    pub(crate) faces: Vec<Face>,
+    pub(crate) vert_pairs: Vec<(Vertex, Vertex)>,
+    pub(crate) placements: [Placement; 2],
+    pub(crate) keep_coords: bool, // outputs restored to the original coordinates by `placements`
+    pub(crate) texcoord_pairs: Vec<(Vertex, Vertex)>,
+    pub(crate) face_texcoords: Vec<Face>,
+    pub(crate) color_pairs: Vec<(Vertex, Vertex)>,
+    pub(crate) normal_pairs: Vec<(Vertex, Vertex)>,
+    pub(crate) face_normals: Vec<Face>,
+    pub(crate) attribute_pairs: BTreeMap<String, (Attribute, Attribute)>,
+    // labels of the faces of each model containing the merged faces
+    pub(crate) labels: [Vec<FaceLabel>; 2],
+    pub(crate) face_labels: Vec<(usize, usize)>,
+    pub(crate) provenance: Provenance,
+    pub(crate) stats: MergeStats,
 }
 
 impl Model {
@@ -50,7 +96,67 @@ impl Model {
     }
 
     pub fn new(verts: Vec<Vertex>, faces: Vec<Face>) -> Self {
-        Self { verts, faces }
+        Self {
+            verts,
+            faces,
+            colors: Vec::new(),
+            texcoords: Vec::new(),
+            face_texcoords: Vec::new(),
+            normals: Vec::new(),
+            face_normals: Vec::new(),
+            attributes: BTreeMap::new(),
+            labels: Vec::new(),
+            face_labels: Vec::new(),
+        }
+    }
+
+    /// A model from vertex coordinates and triangles.
+    pub fn from_slices(verts: &[[f64; 3]], faces: &[[usize; 3]]) -> Self {
+        assert!(
+            faces.iter().flatten().all(|&i| i < verts.len()),
+            "Face index out of range"
+        );
+        Self::new(
+            verts.iter().map(|&v| v.into()).collect(),
+            faces.iter().map(|f| f.to_vec()).collect(),
+        )
+    }
+
+    pub fn verts(&self) -> &[Vertex] {
+        &self.verts
+    }
+
+    pub fn faces(&self) -> &[Face] {
+        &self.faces
+    }
+
+    /// Colors of the vertices, empty if the model is not colored.
+    pub fn colors(&self) -> &[Vertex] {
+        &self.colors
+    }
+
+    /// Corners of the faces, polygons being split into fans of triangles.
+    pub fn triangles(&self) -> impl Iterator<Item = [Vertex; 3]> + '_ {
+        self.faces.iter().flat_map(move |f| {
+            (1..f.len().saturating_sub(1))
+                .map(move |i| [self.verts[f[0]], self.verts[f[i]], self.verts[f[i + 1]]])
+        })
+    }
+
+    /// Orients the faces (and the indices of their texture coordinates and
+    /// normals) away from the center.
+    pub(crate) fn orient(&mut self) {
+        let center = self.center();
+        for (k, f) in self.faces.iter_mut().enumerate() {
+            if adjust_order(f, &self.verts, center) {
+                if let Some(t) = self.face_texcoords.get_mut(k) {
+                    t.reverse();
+                }
+                if let Some(n) = self.face_normals.get_mut(k) {
+                    n.reverse();
+                }
+            }
+        }
     }
 
     pub fn center(&self) -> Vertex {
@@ -61,7 +167,11 @@ impl Model {
         center / self.nr_verts() as f64
     }
 
+    /// Loads an OBJ file, or a PLY file if the extension is `.ply`.
     pub fn load(filename: &str) -> io::Result<Self> {
+        if filename.ends_with(".ply") {
+            return Self::load_ply(filename);
+        }
         assert!(filename.ends_with(".obj"));
 
         let file = File::open(filename)?;
@@ -69,33 +179,138 @@ impl Model {
 
         let mut verts = Vec::new();
         let mut faces = Vec::new();
+        let mut colors = Vec::new();
+        let mut texcoords = Vec::new();
+        let mut face_texcoords = Vec::new();
+        let mut normals = Vec::new();
+        let mut face_normals = Vec::new();
+        let mut labels = LabelTable::default();
+        let mut label = FaceLabel::default();
+        let mut face_labels = Vec::new();
+        let mut labeled = false;
         for line in reader.lines() {
             let line = line?;
             let vals = line.split_whitespace().collect::<Vec<_>>();
-            if vals.len() == 0 {
+            if vals.is_empty() {
                 continue;
             }
             match vals[0] {
                 "v" => {
-                    assert!(vals.len() == 4);
+                    // `v x y z` or `v x y z r g b`
+                    assert!(vals.len() == 4 || vals.len() == 7);
                     verts.push(Vertex::new(
                         vals[1].parse().unwrap(),
                         vals[2].parse().unwrap(),
                         vals[3].parse().unwrap(),
-                    ))
+                    ));
+                    if vals.len() == 7 {
+                        colors.push(Vertex::new(
+                            vals[4].parse().unwrap(),
+                            vals[5].parse().unwrap(),
+                            vals[6].parse().unwrap(),
+                        ));
+                    }
+                }
+                "vt" => {
+                    assert!(vals.len() == 3 || vals.len() == 4);
+                    texcoords.push(vec![Vertex::new(
+                        vals[1].parse().unwrap(),
+                        vals[2].parse().unwrap(),
+                        vals.get(3).map_or(0.0, |w| w.parse().unwrap()),
+                    )])
+                }
+                "vn" => {
+                    assert!(vals.len() == 4);
+                    normals.push(
+                        Vertex::new(
+                            vals[1].parse().unwrap(),
+                            vals[2].parse().unwrap(),
+                            vals[3].parse().unwrap(),
+                        )
+                        .unit(),
+                    )
+                }
+                "g" => {
+                    label.group = Some(vals[1..].join(" ")).filter(|g| !g.is_empty());
+                    labeled = true;
+                }
+                "usemtl" => {
+                    label.material = Some(vals[1..].join(" ")).filter(|m| !m.is_empty());
+                    labeled = true;
                 }
                 "f" => {
                     assert!(vals.len() == 4);
-                    faces.push(vec![
-                        vals[1].parse::<usize>().unwrap() - 1,
-                        vals[2].parse::<usize>().unwrap() - 1,
-                        vals[3].parse::<usize>().unwrap() - 1,
-                    ])
+                    face_labels.push(labels.intern(label.clone()));
+                    // `v`, `v/vt`, `v/vt/vn` or `v//vn`
+                    let corners = vals[1..]
+                        .iter()
+                        .map(|c| {
+                            let mut ids = c.split('/');
+                            let v = ids.next().unwrap().parse::<usize>().unwrap() - 1;
+                            let t = match ids.next().filter(|t| !t.is_empty()) {
+                                Some(t) => Some(parse_index(t, "texture coordinate")?),
+                                None => None,
+                            };
+                            let n = match ids.next() {
+                                Some(n) => Some(parse_index(n, "normal")?),
+                                None => None,
+                            };
+                            Ok((v, t, n))
+                        })
+                        .collect::<io::Result<Vec<_>>>()?;
+                    faces.push(corners.iter().map(|c| c.0).collect());
+                    face_texcoords.push(corners.iter().map(|c| c.1).collect::<Option<Vec<_>>>());
+                    face_normals.push(corners.iter().map(|c| c.2).collect::<Option<Vec<_>>>());
                 }
                 _ => {}
             }
         }
-        Ok(Self::new(verts, faces))
+        if let Some(t) = face_texcoords
+            .iter()
+            .flatten()
+            .flatten()
+            .find(|&&t| t >= texcoords.len())
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid texture coordinate index {}", t + 1),
+            ));
+        }
+        if let Some(n) = face_normals
+            .iter()
+            .flatten()
+            .flatten()
+            .find(|&&n| n >= normals.len())
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid normal index {}", n + 1),
+            ));
+        }
+        let mut model = Self::new(verts, faces);
+        // colors are only kept if every vertex has one
+        if colors.len() == model.verts.len() {
+            model.colors = colors;
+        }
+        // texture coordinates are only kept if every face has them
+        if let Some(face_texcoords) = face_texcoords.into_iter().collect::<Option<Vec<_>>>() {
+            if !texcoords.is_empty() {
+                model.texcoords = texcoords;
+                model.face_texcoords = face_texcoords;
+            }
+        }
+        // and so are normals
+        if let Some(face_normals) = face_normals.into_iter().collect::<Option<Vec<_>>>() {
+            if !normals.is_empty() {
+                model.normals = normals;
+                model.face_normals = face_normals;
+            }
+        }
+        if labeled {
+            model.labels = labels.labels;
+            model.face_labels = face_labels;
+        }
+        Ok(model)
     }
 
     pub fn save(&self, filename: &str) -> io::Result<()> {
@@ -104,18 +319,121 @@ impl Model {
         let file = File::create(filename)?;
         let mut writer = BufWriter::new(file);
 
-        for v in &self.verts {
-            writeln!(writer, "v {} {} {}", v.x, v.y, v.z)?;
+        write_verts(&mut writer, "v", &self.verts, &self.colors)?;
+        write_texcoords(&mut writer, &self.texcoords)?;
+        for n in &self.normals {
+            writeln!(writer, "vn {} {} {}", n.x, n.y, n.z)?;
+        }
+        write_faces(
+            &mut writer,
+            &self.faces,
+            &self.face_texcoords,
+            &self.face_normals,
+            &self.labels,
+            &self.face_labels,
+        )
+    }
+}
+
+/// Parses a one-based index of a face corner.
+fn parse_index(s: &str, what: &str) -> io::Result<usize> {
+    match s.parse::<usize>() {
+        Ok(i) if i > 0 => Ok(i - 1),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Invalid {} index \"{}\"", what, s),
+        )),
+    }
+}
+
+/// Writes the vertices as `prefix x y z` lines, followed by their colors if
+/// any.
+pub(crate) fn write_verts(
+    writer: &mut impl Write,
+    prefix: &str,
+    verts: &[Vertex],
+    colors: &[Vertex],
+) -> io::Result<()> {
+    for (i, v) in verts.iter().enumerate() {
+        match colors.get(i) {
+            Some(c) => writeln!(
+                writer,
+                "{} {} {} {} {} {} {}",
+                prefix, v.x, v.y, v.z, c.x, c.y, c.z
+            )?,
+            None => writeln!(writer, "{} {} {} {}", prefix, v.x, v.y, v.z)?,
+        }
+    }
+    Ok(())
+}
+
+/// Writes the first set of texture coordinates as `vt` lines, and the others
+/// as `ut` lines.
+pub(crate) fn write_texcoords(
+    writer: &mut impl Write,
+    texcoords: &[Vec<Vertex>],
+) -> io::Result<()> {
+    for k in 0..texcoords.first().map_or(0, |t| t.len()) {
+        let prefix = if k == 0 { "vt" } else { "ut" };
+        for t in texcoords {
+            writeln!(writer, "{} {} {}", prefix, t[k].x, t[k].y)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes the first set of normals as `vn` lines, and the others as `un`
+/// lines.
+pub(crate) fn write_normals(writer: &mut impl Write, normals: &[Vec<Vertex>]) -> io::Result<()> {
+    for k in 0..normals.first().map_or(0, |n| n.len()) {
+        let prefix = if k == 0 { "vn" } else { "un" };
+        for n in normals {
+            writeln!(writer, "{} {} {} {}", prefix, n[k].x, n[k].y, n[k].z)?;
         }
-        for f in &self.faces {
-            let mut line = "f".to_string();
-            for id in f {
-                line += &format!(" {}", id + 1);
+    }
+    Ok(())
+}
+
+/// Writes the faces as `f` lines, with the indices of their texture
+/// coordinates and normals if any.
+pub(crate) fn write_faces(
+    writer: &mut impl Write,
+    faces: &[Face],
+    face_texcoords: &[Face],
+    face_normals: &[Face],
+    labels: &[FaceLabel],
+    face_labels: &[usize],
+) -> io::Result<()> {
+    // faces keep their order, which the provenance refers to, so a label is
+    // written again whenever it changes
+    let mut current: Option<&FaceLabel> = None;
+    for (k, f) in faces.iter().enumerate() {
+        if let Some(label) = face_labels.get(k).map(|&l| &labels[l]) {
+            if current != Some(label) {
+                writeln!(writer, "g {}", label.group.as_deref().unwrap_or("default"))?;
+                match &label.material {
+                    Some(material) => writeln!(writer, "usemtl {}", material)?,
+                    // an empty `usemtl` clears the previous material
+                    None if current.is_some_and(|l| l.material.is_some()) => {
+                        writeln!(writer, "usemtl")?
+                    }
+                    None => {}
+                }
+                current = Some(label);
             }
-            writeln!(writer, "{}", line)?;
         }
-        Ok(())
+        let mut line = "f".to_string();
+        for (c, id) in f.iter().enumerate() {
+            match (face_texcoords.get(k), face_normals.get(k)) {
+                (Some(t), Some(n)) => line += &format!(" {}/{}/{}", id + 1, t[c] + 1, n[c] + 1),
+                (Some(t), None) => line += &format!(" {}/{}", id + 1, t[c] + 1),
+                (None, Some(n)) => line += &format!(" {}//{}", id + 1, n[c] + 1),
+                (None, None) => line += &format!(" {}", id + 1),
+            }
+        }
+        writeln!(writer, "{}", line)?;
     }
+    Ok(())
 }
 
 impl ProjectionModel {
@@ -135,21 +453,209 @@ impl ProjectionModel {
         }
 
         Self {
+            adjacency: Adjacency::new(&model.faces),
             model,
             edges,
             center,
             sphere_verts,
+            rotation: Matrix3::identity(),
+            warped: false,
         }
     }
 
-    fn project_from_sphere(&self, v: Vertex) -> Vertex {
-        for f in &self.faces {
+    /// Warps the sphere embedding so that the `index2` vertices of `pairs` move
+    /// onto the `index1` vertices of `other`: a best-fit rotation first, then a
+    /// smooth local deformation applied in small steps to avoid fold-overs.
+    pub fn align_to(&mut self, other: &ProjectionModel, pairs: &[FeaturePair]) {
+        if pairs.is_empty() {
+            return;
+        }
+        let targets = pairs
+            .iter()
+            .map(|p| other.sphere_verts[p.index1].unit())
+            .collect::<Vec<_>>();
+
+        let mut cov = Matrix3::zero();
+        for (p, t) in pairs.iter().zip(&targets) {
+            cov += Matrix3::outer(*t, self.sphere_verts[p.index2].unit());
+        }
+        if let Some(rot) = cov.nearest_rotation() {
+            for v in &mut self.sphere_verts {
+                *v = rot * *v;
+            }
+            self.rotation = rot * self.rotation;
+        }
+
+        for step in 0..WARP_STEPS {
+            let remain = (WARP_STEPS - step) as f64;
+            let sources = pairs
+                .iter()
+                .map(|p| self.sphere_verts[p.index2].unit())
+                .collect::<Vec<_>>();
+            for v in &mut self.sphere_verts {
+                let u = v.unit();
+                let mut delta = Vertex::new(0.0, 0.0, 0.0);
+                let mut weight = 0.0;
+                for (s, t) in sources.iter().zip(&targets) {
+                    let angle = Vertex::angle(u, *s);
+                    let w = (-(angle / WARP_WIDTH).powi(2)).exp();
+                    delta += (*t - *s) * (w / remain);
+                    weight += w;
+                }
+                *v = (u + delta / weight.max(1.0))
+                    .project_to_sphere(Vertex::new(0.0, 0.0, 0.0), SPHERE_RADIUS);
+            }
+        }
+        self.warped = true;
+    }
+
+    /// Barycentric coordinates on face `k` of the point of the model mapped to
+    /// `v` on the sphere, possibly outside of the face.
+    fn face_barycentric(&self, k: usize, v: Vertex) -> Option<[f64; 3]> {
+        let f = &self.faces[k];
+        let tri = if self.warped {
+            // the embedding is no longer a central projection, use the
+            // containing spherical triangle
+            let p = |i: usize| self.sphere_verts[f[i]];
+            Triangle::new(p(0), p(1), p(2))
+        } else {
+            let p = |i: usize| self.verts[f[i]] - self.center;
+            Triangle::new(p(0), p(1), p(2))
+        };
+        tri.central_barycentric(v)
+    }
+
+    /// The face containing the point mapped to `v` on the sphere, or the
+    /// nearest face for points falling between faces, with the barycentric
+    /// coordinates of the point. The search walks from face `hint`.
+    fn locate(&self, v: Vertex, hint: usize) -> Option<(usize, [f64; 3])> {
+        self.adjacency.locate(hint, |k| self.face_barycentric(k, v))
+    }
+
+    /// Color at location `l`, black if the model has no colors.
+    fn color_at(&self, l: &SourceLocation) -> Vertex {
+        if self.colors.is_empty() {
+            return Vertex::new(0.0, 0.0, 0.0);
+        }
+        let (f, w) = (&self.faces[l.face], l.barycentric);
+        self.colors[f[0]] * w[0] + self.colors[f[1]] * w[1] + self.colors[f[2]] * w[2]
+    }
+
+    pub(crate) fn sphere_verts(&self) -> &[Vertex] {
+        &self.sphere_verts
+    }
+
+    /// Values of `attribute` of the model at location `l`.
+    fn attribute_at(&self, attribute: &Attribute, l: &SourceLocation) -> Vec<f64> {
+        let (f, w) = (&self.faces[l.face], l.barycentric);
+        attribute.mix(&[(f[0], w[0]), (f[1], w[1]), (f[2], w[2])])
+    }
+
+    /// Texture coordinates (of the first set) at the point mapped to `v` on
+    /// the sphere, by its barycentric coordinates on face `k`.
+    fn texcoord_at(&self, k: usize, v: Vertex) -> Option<Vertex> {
+        if self.texcoords.is_empty() {
+            return None;
+        }
+        let w = self.face_barycentric(k, v).unwrap_or([1.0, 0.0, 0.0]);
+        let t = &self.face_texcoords[k];
+        Some(
+            self.texcoords[t[0]][0] * w[0]
+                + self.texcoords[t[1]][0] * w[1]
+                + self.texcoords[t[2]][0] * w[2],
+        )
+    }
+
+    /// Normal at the point mapped to `v` on the sphere, by its barycentric
+    /// coordinates on face `k`, or the normal of the face if the model has no
+    /// normals.
+    fn normal_at(&self, k: usize, v: Vertex) -> Option<Vertex> {
+        let f = &self.faces[k];
+        let n = if self.normals.is_empty() {
+            let n = (self.verts[f[1]] - self.verts[f[0]]) * (self.verts[f[2]] - self.verts[f[0]]);
+            if n.dot(self.verts[f[0]] - self.center) < 0.0 {
+                n * -1.0
+            } else {
+                n
+            }
+        } else {
+            let w = self.face_barycentric(k, v).unwrap_or([1.0, 0.0, 0.0]);
+            let n = &self.face_normals[k];
+            self.normals[n[0]] * w[0] + self.normals[n[1]] * w[1] + self.normals[n[2]] * w[2]
+        };
+        if n.len() > EPS {
+            Some(n.unit())
+        } else {
+            None
+        }
+    }
+
+    /// The point of the model mapped to `v` on the sphere, found at location
+    /// `l` of `v`.
+    fn project_from_sphere(&self, v: Vertex, l: &SourceLocation) -> Vertex {
+        let f = &self.faces[l.face];
+        if self.warped {
+            let w = l.barycentric;
+            return self.verts[f[0]] * w[0] + self.verts[f[1]] * w[1] + self.verts[f[2]] * w[2];
+        }
+        for f in std::iter::once(f).chain(&self.faces) {
             let tri = Triangle::new(self.verts[f[0]], self.verts[f[1]], self.verts[f[2]]);
             if let Some(int) = tri.intersect(self.center, self.center + v) {
                 return int;
             }
         }
-        panic!(format!("No intersect found of {:?}!", v))
+        panic!("No intersect found of {:?}!", v)
+    }
+}
+
+/// Common mesh of several overlaid models.
+pub(crate) struct Overlay {
+    pub(crate) positions: Vec<Vec<Vertex>>,
+    pub(crate) faces: Vec<Face>,
+    pub(crate) placements: Vec<Placement>,
+    pub(crate) texcoords: Vec<Vec<Vertex>>,
+    pub(crate) face_texcoords: Vec<Face>,
+    pub(crate) colors: Vec<Vec<Vertex>>,
+    pub(crate) normals: Vec<Vec<Vertex>>,
+    pub(crate) face_normals: Vec<Face>,
+    pub(crate) attributes: BTreeMap<String, Vec<Attribute>>, // one for every model
+    pub(crate) labels: Vec<Vec<FaceLabel>>,
+    pub(crate) face_labels: Vec<Vec<usize>>,
+    // model (1-based, 0 for intersections) and index of each merged vertex,
+    // with its location on every model
+    pub(crate) sources: Vec<(usize, usize)>,
+    pub(crate) locations: Vec<Vec<SourceLocation>>,
+    // face of every model containing each merged face
+    pub(crate) face_sources: Vec<Vec<usize>>,
+    pub(crate) stats: MergeStats,
+}
+
+/// Values of a per corner attribute on every model, indexed by the corners of
+/// `faces`. Corners of a vertex with the same values share their index.
+#[derive(Default)]
+pub(crate) struct CornerValues {
+    pub(crate) values: Vec<Vec<Vertex>>,
+    pub(crate) faces: Vec<Face>,
+    index: HashMap<(usize, Vec<i64>), usize>,
+}
+
+impl CornerValues {
+    /// Adds a face from the vertex and the values of each corner.
+    pub(crate) fn push_face(&mut self, corners: Vec<(usize, Vec<Vertex>)>) {
+        let mut face = Vec::with_capacity(corners.len());
+        for (i, v) in corners {
+            let key = v
+                .iter()
+                .flat_map(|x| [x.x, x.y, x.z])
+                .map(|x| (x / CORNER_EPS).round() as i64)
+                .collect();
+            let values = &mut self.values;
+            face.push(*self.index.entry((i, key)).or_insert_with(|| {
+                values.push(v);
+                values.len() - 1
+            }));
+        }
+        self.faces.push(face);
     }
 }
 
@@ -160,176 +666,505 @@ struct SphereVertex {
 }
 
 impl MergedModel {
+    /// A merged model from the positions of each vertex on both models and
+    /// the common triangles, without any other attribute.
+    pub fn new(vert_pairs: Vec<(Vertex, Vertex)>, faces: Vec<Face>) -> Self {
+        assert!(
+            faces.iter().all(|f| f.len() == 3),
+            "Faces must be triangles"
+        );
+        assert!(
+            faces.iter().flatten().all(|&i| i < vert_pairs.len()),
+            "Face index out of range"
+        );
+        Self {
+            faces,
+            vert_pairs,
+            placements: [Placement::identity(); 2],
+            keep_coords: false,
+            texcoord_pairs: Vec::new(),
+            face_texcoords: Vec::new(),
+            color_pairs: Vec::new(),
+            normal_pairs: Vec::new(),
+            face_normals: Vec::new(),
+            attribute_pairs: BTreeMap::new(),
+            labels: Default::default(),
+            face_labels: Vec::new(),
+            provenance: Provenance::default(),
+            stats: MergeStats::default(),
+        }
+    }
+
+    /// Positions of the merged vertices on both models, centered and normalized
+    /// as given by `placements`, even if the model keeps the original
+    /// coordinates: only `save` and the interpolations restore them.
+    pub fn vert_pairs(&self) -> &[(Vertex, Vertex)] {
+        &self.vert_pairs
+    }
+
+    pub fn faces(&self) -> &[Face] {
+        &self.faces
+    }
+
+    /// Colors of the vertices on both models, empty if neither is colored.
+    pub fn color_pairs(&self) -> &[(Vertex, Vertex)] {
+        &self.color_pairs
+    }
+
+    /// Corners of each face on both models.
+    pub fn triangle_pairs(&self) -> impl Iterator<Item = ([Vertex; 3], [Vertex; 3])> + '_ {
+        self.faces.iter().flat_map(move |f| {
+            (1..f.len().saturating_sub(1)).map(move |i| {
+                let p = |j: usize| self.vert_pairs[f[j]];
+                let (a, b, c) = (p(0), p(i), p(i + 1));
+                ([a.0, b.0, c.0], [a.1, b.1, c.1])
+            })
+        })
+    }
+
+    pub fn nr_verts(&self) -> usize {
+        self.vert_pairs.len()
+    }
+
+    pub fn nr_faces(&self) -> usize {
+        self.faces.len()
+    }
+
+    /// Saves the model, and its provenance next to it with the `.prov`
+    /// extension.
     pub fn save(&self, filename: &str) -> io::Result<()> {
         assert!(filename.ends_with(".obj"));
 
+        if !self.provenance.is_empty() {
+            self.provenance
+                .save(&format!("{}.prov", filename.trim_end_matches(".obj")))?;
+        }
+
         let file = File::create(filename)?;
         let mut writer = BufWriter::new(file);
 
-        for v in &self.vert_pairs {
-            writeln!(writer, "v {} {} {}", v.0.x, v.0.y, v.0.z)?;
-        }
-        for v in &self.vert_pairs {
-            writeln!(writer, "u {} {} {}", v.1.x, v.1.y, v.1.z)?;
-        }
-        for f in &self.faces {
-            let mut line = "f".to_string();
-            for id in f {
-                line += &format!(" {}", id + 1);
+        let (mut verts1, mut verts2): (Vec<_>, Vec<_>) = self.vert_pairs.iter().copied().unzip();
+        if self.keep_coords {
+            for (v1, v2) in verts1.iter_mut().zip(&mut verts2) {
+                *v1 = self.placements[0].restore(*v1);
+                *v2 = self.placements[1].restore(*v2);
             }
-            writeln!(writer, "{}", line)?;
         }
-        Ok(())
+        let (colors1, colors2): (Vec<_>, Vec<_>) = self.color_pairs.iter().copied().unzip();
+        write_verts(&mut writer, "v", &verts1, &colors1)?;
+        write_verts(&mut writer, "u", &verts2, &colors2)?;
+        let texcoords = self
+            .texcoord_pairs
+            .iter()
+            .map(|t| vec![t.0, t.1])
+            .collect::<Vec<_>>();
+        write_texcoords(&mut writer, &texcoords)?;
+        let normals = self
+            .normal_pairs
+            .iter()
+            .map(|n| vec![n.0, n.1])
+            .collect::<Vec<_>>();
+        write_normals(&mut writer, &normals)?;
+        let face_labels = self
+            .face_labels
+            .iter()
+            .map(|l| vec![l.0, l.1])
+            .collect::<Vec<_>>();
+        let (labels, face_labels) = combine_labels(&self.labels, &face_labels);
+        write_faces(
+            &mut writer,
+            &self.faces,
+            &self.face_texcoords,
+            &self.face_normals,
+            &labels,
+            &face_labels,
+        )
     }
 
     pub fn merge(model1: ProjectionModel, model2: ProjectionModel, config: Config) -> Self {
+        Self::merge_with_hooks(model1, model2, config, &mut Hooks::default())
+            .expect("Merge cancelled without a cancel token")
+    }
+
+    pub(crate) fn merge_with_hooks(
+        model1: ProjectionModel,
+        model2: ProjectionModel,
+        config: Config,
+        hooks: &mut Hooks,
+    ) -> Result<Self, Cancelled> {
+        let overlay = Self::merge_all_with(&[model1, model2], &config, hooks)?;
+        Ok(MergedModel {
+            vert_pairs: overlay.positions.iter().map(|p| (p[0], p[1])).collect(),
+            faces: overlay.faces,
+            placements: [overlay.placements[0], overlay.placements[1]],
+            keep_coords: config.keep_coords,
+            texcoord_pairs: overlay.texcoords.iter().map(|t| (t[0], t[1])).collect(),
+            face_texcoords: overlay.face_texcoords,
+            color_pairs: overlay.colors.iter().map(|c| (c[0], c[1])).collect(),
+            normal_pairs: overlay.normals.iter().map(|n| (n[0], n[1])).collect(),
+            face_normals: overlay.face_normals,
+            attribute_pairs: overlay
+                .attributes
+                .into_iter()
+                .map(|(name, a)| {
+                    let mut a = a.into_iter();
+                    (name, (a.next().unwrap(), a.next().unwrap()))
+                })
+                .collect(),
+            labels: match overlay.labels.as_slice() {
+                [l1, l2] => [l1.clone(), l2.clone()],
+                _ => Default::default(),
+            },
+            face_labels: overlay.face_labels.iter().map(|l| (l[0], l[1])).collect(),
+            provenance: Provenance {
+                vertices: overlay
+                    .sources
+                    .iter()
+                    .zip(&overlay.locations)
+                    .map(|(&(from, index), l)| VertexProvenance {
+                        source: match from {
+                            1 => VertexSource::Model1(index),
+                            2 => VertexSource::Model2(index),
+                            _ => VertexSource::Intersection,
+                        },
+                        locations: [l[0], l[1]],
+                    })
+                    .collect(),
+                faces: overlay.face_sources.iter().map(|f| [f[0], f[1]]).collect(),
+            },
+            stats: overlay.stats,
+        })
+    }
+
+    /// Overlays the sphere embeddings of all models one after another, and
+    /// returns the positions of each merged vertex on every model with the
+    /// faces of the common mesh and the placement of each model, reporting the
+    /// progress and results of each stage to `hooks`.
+    pub(crate) fn merge_all_with(
+        models: &[ProjectionModel],
+        config: &Config,
+        hooks: &mut Hooks,
+    ) -> Result<Overlay, Cancelled> {
+        hooks.progress(Stage::Overlay, 0.0)?;
+        let nr_arcs = models.iter().skip(1).map(|m| m.edges.len()).sum::<usize>();
+        let mut done_arcs = 0;
         let mut all_sphere_verts = Vec::new();
         let mut all_edges = EdgeList::new();
+        let mut snapped = 0;
+        let mut intersections = IntersectionCounts::default();
 
-        // origin sphere vertices of two models
-        let n = model1.nr_verts();
-        let m = model2.nr_verts();
-        for i in 0..n {
-            all_sphere_verts.push(SphereVertex {
-                v: model1.sphere_verts[i],
-                from: 1,
-                index: i,
-            });
-        }
-        for i in 0..m {
-            all_sphere_verts.push(SphereVertex {
-                v: model2.sphere_verts[i],
-                from: 2,
-                index: i,
-            });
-        }
-        println!("SIZE {:?} {:?}", all_sphere_verts.len(), all_edges.len());
-
-        // calcuation new vertices from intersection, split & add edges
-        for e in model1.edges.iter() {
-            all_edges.add(e.from, e.to);
-        }
-        for e2 in model2.edges.iter() {
-            let e2 = Edge::new(e2.from + n, e2.to + n);
-            let v1 = all_sphere_verts[e2.from].v;
-            let v2 = all_sphere_verts[e2.to].v;
-            let arc2 = Arc::new(v1, v2, e2.from, e2.to);
-            let mut ints = vec![(0.0, e2.from), (1.0, e2.to)];
-
-            let mut donot_add = false;
-            for e1 in &mut all_edges.clone().iter() {
-                let u1 = all_sphere_verts[e1.from].v;
-                let u2 = all_sphere_verts[e1.to].v;
-                let arc1 = Arc::new(u1, u2, e1.from, e1.to);
-
-                match Arc::intersect(&arc1, &arc2) {
-                    ArcIntersectionResult::T1(index, k) => ints.push((k, index)),
-                    ArcIntersectionResult::T2(index, _k) => {
-                        all_edges.remove(e1);
-                        all_edges.add(e1.from, index);
-                        all_edges.add(e1.to, index);
-                    }
-                    ArcIntersectionResult::X(v, k) => {
-                        let id = all_sphere_verts.len();
-                        all_sphere_verts.push(SphereVertex {
-                            v,
-                            from: 0,
-                            index: 0,
-                        });
-                        all_edges.remove(e1);
-                        all_edges.add(e1.from, id);
-                        all_edges.add(e1.to, id);
-                        ints.push((k, id))
-                    }
-                    ArcIntersectionResult::I((id1, k1), (id2, k2)) => {
-                        all_edges.remove(e1);
-                        if k1 > 0.0 {
-                            ints.push((k1, id1))
-                        } else if k1 < 0.0 {
-                            all_edges.add(id1, ints[0].1);
-                        } else {
-                            // assert!(id1 == e2.from);
+        for (k, model) in models.iter().enumerate() {
+            // origin sphere vertices of the model
+            let n = all_sphere_verts.len();
+            for i in 0..model.nr_verts() {
+                all_sphere_verts.push(SphereVertex {
+                    v: model.sphere_verts[i],
+                    from: k + 1,
+                    index: i,
+                });
+            }
+            if k == 0 {
+                for e in model.edges.iter() {
+                    all_edges.add(e.from, e.to);
+                }
+                continue;
+            }
+            log::debug!(
+                "Overlaying model {}: {} vertices, {} arcs",
+                k + 1,
+                all_sphere_verts.len(),
+                all_edges.len()
+            );
+
+            // snap nearly coincident vertices before splitting arcs
+            if config.snap_tolerance > 0.0 {
+                snapped += Self::snap_vertices(
+                    &mut all_sphere_verts,
+                    n,
+                    &all_edges,
+                    &model.edges,
+                    config.snap_tolerance,
+                );
+            }
+
+            // calcuation new vertices from intersection, split & add edges
+            for e2 in model.edges.iter() {
+                hooks.progress(Stage::Overlay, done_arcs as f64 / nr_arcs as f64)?;
+                done_arcs += 1;
+                let e2 = Edge::new(e2.from + n, e2.to + n);
+                let v1 = all_sphere_verts[e2.from].v;
+                let v2 = all_sphere_verts[e2.to].v;
+                let arc2 = Arc::new(v1, v2, e2.from, e2.to);
+                let mut ints = vec![(0.0, e2.from), (1.0, e2.to)];
+
+                let mut donot_add = false;
+                for e1 in &mut all_edges.clone().iter() {
+                    let u1 = all_sphere_verts[e1.from].v;
+                    let u2 = all_sphere_verts[e1.to].v;
+                    let arc1 = Arc::new(u1, u2, e1.from, e1.to);
+
+                    match Arc::intersect(&arc1, &arc2) {
+                        ArcIntersectionResult::T1(index, k) => {
+                            intersections.t1 += 1;
+                            ints.push((k, index))
+                        }
+                        ArcIntersectionResult::T2(index, _k) => {
+                            intersections.t2 += 1;
+                            all_edges.remove(e1);
+                            all_edges.add(e1.from, index);
+                            all_edges.add(e1.to, index);
+                        }
+                        ArcIntersectionResult::X(v, k) => {
+                            intersections.x += 1;
+                            let id = all_sphere_verts.len();
+                            all_sphere_verts.push(SphereVertex {
+                                v,
+                                from: 0,
+                                index: 0,
+                            });
+                            all_edges.remove(e1);
+                            all_edges.add(e1.from, id);
+                            all_edges.add(e1.to, id);
+                            ints.push((k, id))
                         }
+                        ArcIntersectionResult::I((id1, k1), (id2, k2)) => {
+                            intersections.i += 1;
+                            all_edges.remove(e1);
+                            if k1 > 0.0 {
+                                ints.push((k1, id1))
+                            } else if k1 < 0.0 {
+                                all_edges.add(id1, ints[0].1);
+                            } else {
+                                // assert!(id1 == e2.from);
+                            }
 
-                        if k2 < 1.0 {
-                            ints.push((k2, id2))
-                        } else if k2 > 1.0 {
-                            all_edges.add(id2, ints[1].1);
-                        } else {
-                            // assert!(id2 == e2.to);
+                            if k2 < 1.0 {
+                                ints.push((k2, id2))
+                            } else if k2 > 1.0 {
+                                all_edges.add(id2, ints[1].1);
+                            } else {
+                                // assert!(id2 == e2.to);
+                            }
                         }
-                    }
-                    ArcIntersectionResult::L(id1, id2) => {
-                        if id2 == e2.from {
-                            // assert!(ints[0].1 == id1);
-                            ints[0].1 = id1
-                        } else if id2 == e2.to {
-                            // assert!(ints[1].1 == id1);
-                            ints[1].1 = id1
+                        ArcIntersectionResult::L(id1, id2) => {
+                            intersections.l += 1;
+                            if id2 == e2.from {
+                                // assert!(ints[0].1 == id1);
+                                ints[0].1 = id1
+                            } else if id2 == e2.to {
+                                // assert!(ints[1].1 == id1);
+                                ints[1].1 = id1
+                            }
                         }
+                        ArcIntersectionResult::S => {
+                            intersections.s += 1;
+                            donot_add = true;
+                            break;
+                        }
+                        ArcIntersectionResult::N => intersections.n += 1,
                     }
-                    ArcIntersectionResult::S => {
-                        donot_add = true;
-                        break;
-                    }
-                    ArcIntersectionResult::N => {}
                 }
-            }
-            if donot_add {
-                continue;
-            }
+                if donot_add {
+                    continue;
+                }
 
-            ints.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
-            for i in 0..ints.len() - 1 {
-                all_edges.add(ints[i].1, ints[i + 1].1);
+                ints.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+                for i in 0..ints.len() - 1 {
+                    all_edges.add(ints[i].1, ints[i + 1].1);
+                }
             }
         }
-        println!("SIZE {:?} {:?}", all_sphere_verts.len(), all_edges.len());
+        if config.snap_tolerance > 0.0 {
+            log::info!("Snapped {} vertices", snapped);
+        }
+        log::debug!(
+            "Overlay: {} vertices, {} arcs",
+            all_sphere_verts.len(),
+            all_edges.len()
+        );
+        hooks.progress(Stage::Overlay, 1.0)?;
+        if hooks.wants_intermediate() {
+            let verts = all_sphere_verts.iter().map(|v| v.v).collect::<Vec<_>>();
+            let edges = all_edges.iter().map(|e| (e.from, e.to)).collect::<Vec<_>>();
+            hooks.intermediate(Intermediate::Overlay {
+                sphere_verts: &verts,
+                edges: &edges,
+            });
+        }
 
-        // project back to the origin model
-        let mut model_vert_pairs = Vec::new();
-        for v in &all_sphere_verts {
-            let mut p = if config.sphere_only {
-                (v.v, v.v)
-            } else {
-                match v.from {
-                    1 => (model1.verts[v.index], model2.project_from_sphere(v.v)),
-                    2 => (model1.project_from_sphere(v.v), model2.verts[v.index]),
-                    _ => (
-                        model1.project_from_sphere(v.v),
-                        model2.project_from_sphere(v.v),
-                    ),
+        // location of each merged vertex on every model, walking from the face
+        // of the previous vertex, so that each vertex is located once for its
+        // position, color, attributes and provenance
+        let mut sources = all_sphere_verts
+            .iter()
+            .map(|v| (v.from, v.index))
+            .collect::<Vec<_>>();
+        let mut locations = Vec::with_capacity(all_sphere_verts.len());
+        let vert_faces = models
+            .iter()
+            .map(|m| {
+                let mut vert_faces = vec![(0, [1.0, 0.0, 0.0]); m.nr_verts()];
+                for (k, f) in m.faces.iter().enumerate() {
+                    for (c, &i) in f.iter().enumerate() {
+                        let mut w = [0.0; 3];
+                        w[c] = 1.0;
+                        vert_faces[i] = (k, w);
+                    }
                 }
-            };
-            p.0 -= model1.center;
-            p.1 -= model2.center;
-            model_vert_pairs.push(p);
+                vert_faces
+            })
+            .collect::<Vec<_>>();
+        let mut hints = vec![0; models.len()];
+        for (i, v) in all_sphere_verts.iter().enumerate() {
+            hooks.progress(
+                Stage::BackProjection,
+                0.5 * i as f64 / all_sphere_verts.len() as f64,
+            )?;
+            let l = models
+                .iter()
+                .enumerate()
+                .map(|(k, model)| {
+                    let (face, barycentric) = if v.from == k + 1 {
+                        vert_faces[k][v.index]
+                    } else {
+                        let l = model.locate(v.v, hints[k]).unwrap_or((0, [1.0, 0.0, 0.0]));
+                        hints[k] = l.0;
+                        l
+                    };
+                    SourceLocation { face, barycentric }
+                })
+                .collect::<Vec<_>>();
+            locations.push(l);
+        }
+
+        // project back to the origin models
+        let mut positions = Vec::new();
+        for (i, (v, l)) in all_sphere_verts.iter().zip(&locations).enumerate() {
+            hooks.progress(
+                Stage::BackProjection,
+                0.5 + 0.5 * i as f64 / all_sphere_verts.len() as f64,
+            )?;
+            let p = models
+                .iter()
+                .zip(l)
+                .enumerate()
+                .map(|(k, (model, l))| {
+                    let p = if config.sphere_only {
+                        v.v
+                    } else if v.from == k + 1 {
+                        model.verts[v.index]
+                    } else {
+                        model.project_from_sphere(v.v, l)
+                    };
+                    p - model.center
+                })
+                .collect::<Vec<_>>();
+            positions.push(p);
         }
 
         // scale models to the same scale
-        let bbox1 = Vertex::bounding_box(&model_vert_pairs.iter().map(|p| p.0).collect());
-        let bbox2 = Vertex::bounding_box(&model_vert_pairs.iter().map(|p| p.1).collect());
-        let mut scale1 = (bbox1.1 - bbox1.0).max();
-        let mut scale2 = (bbox2.1 - bbox2.0).max();
-        println!("Bounding box 1: {:?}", bbox1);
-        println!("Bounding box 2: {:?}", bbox2);
-        println!("Scale 1: {:?}", scale1);
-        println!("Scale 2: {:?}", scale2);
-        if !config.scale {
-            let r = if scale1 > scale2 { scale1 } else { scale2 };
-            scale1 = r;
-            scale2 = r;
-        }
-        for p in &mut model_vert_pairs {
-            p.0 *= MODEL_SIZE / scale1;
-            p.1 *= MODEL_SIZE / scale2;
+        let mut scales = Vec::new();
+        for k in 0..models.len() {
+            let bbox = Vertex::bounding_box(&positions.iter().map(|p| p[k]).collect::<Vec<_>>());
+            log::debug!("Bounding box {}: {:?}", k + 1, bbox);
+            scales.push((bbox.1 - bbox.0).max());
+        }
+        for (k, scale) in scales.iter().enumerate() {
+            log::debug!("Scale {}: {:?}", k + 1, scale);
+        }
+        // original coordinates are restored with a single scale, so that they
+        // blend like the normalized ones
+        if !config.scale || config.keep_coords {
+            let r = scales.iter().copied().fold(f64::MIN, f64::max);
+            scales.fill(r);
+        }
+        for p in &mut positions {
+            for (v, scale) in p.iter_mut().zip(&scales) {
+                *v *= MODEL_SIZE / scale;
+            }
+        }
+        let placements = models
+            .iter()
+            .zip(&scales)
+            .map(|(model, scale)| Placement {
+                center: model.center,
+                scale: scale / MODEL_SIZE,
+                // sphere vertices are already in the aligned frame
+                rotation: if config.sphere_only {
+                    Quaternion::identity()
+                } else {
+                    Quaternion::from_matrix(model.rotation.transpose())
+                },
+            })
+            .collect();
+
+        // colors of each merged vertex on every model
+        let mut colors = Vec::new();
+        if models.iter().any(|m| !m.colors.is_empty()) {
+            for l in &locations {
+                let c = models
+                    .iter()
+                    .zip(l)
+                    .map(|(model, l)| model.color_at(l))
+                    .collect::<Vec<_>>();
+                colors.push(c);
+            }
         }
 
+        // attributes of each merged vertex on every model, models without an
+        // attribute take the values of the first model having it
+        let names = models
+            .iter()
+            .flat_map(|m| m.attributes.keys())
+            .collect::<std::collections::BTreeSet<_>>();
+        let mut attributes = BTreeMap::new();
+        for name in names {
+            let first = models
+                .iter()
+                .position(|m| m.attributes.contains_key(name))
+                .unwrap();
+            let a = &models[first].attributes[name];
+            let values: Vec<_> = (0..models.len())
+                .map(|k| {
+                    let j = if models[k].attributes.contains_key(name) {
+                        k
+                    } else {
+                        first
+                    };
+                    let b = &models[j].attributes[name];
+                    assert_eq!(
+                        a.arity(),
+                        b.arity(),
+                        "Attribute \"{}\" has different arities",
+                        name
+                    );
+                    a.with_rows(locations.iter().map(|l| models[j].attribute_at(b, &l[j])))
+                })
+                .collect();
+            attributes.insert(name.clone(), values);
+        }
+
+        hooks.progress(Stage::BackProjection, 1.0)?;
+        hooks.intermediate(Intermediate::BackProjection {
+            positions: &positions,
+        });
+
         let all_sphere_verts = all_sphere_verts.iter().map(|v| v.v).collect::<Vec<_>>();
-        let all_faces = if config.edge_only {
+        let mut dropped_faces = 0;
+        let triangle_faces = if config.edge_only {
             // show all edges only, without faces
-            for p in model_vert_pairs.clone().iter() {
-                model_vert_pairs.push(*p)
+            for p in positions.clone() {
+                positions.push(p)
+            }
+            for c in colors.clone() {
+                colors.push(c)
+            }
+            sources.extend_from_slice(&sources.clone());
+            locations.extend_from_slice(&locations.clone());
+            for values in attributes.values_mut() {
+                for a in values.iter_mut() {
+                    *a = a.with_rows((0..2 * a.len()).map(|i| a.get(i % a.len())));
+                }
             }
             let n = all_sphere_verts.len();
             all_edges
@@ -338,56 +1173,236 @@ impl MergedModel {
                 .collect()
         } else {
             // face tracing
-            Self::resolve_faces(&all_sphere_verts, &all_edges)
-        };
+            hooks.progress(Stage::FaceTracing, 0.0)?;
+            let all_faces = Self::resolve_faces(&all_sphere_verts, &all_edges);
+            hooks.progress(Stage::FaceTracing, 1.0)?;
+            hooks.intermediate(Intermediate::FaceTracing { faces: &all_faces });
 
-        // triangulize & unique
-        let mut triangle_faces = Vec::new();
-        let mut set = std::collections::BTreeSet::<Vec<usize>>::new();
-        for f in all_faces {
-            if f.len() > 3 {
-                for i in 1..f.len() - 1 {
-                    let mut tri = vec![f[0], f[i], f[i + 1]];
-                    tri.sort();
-                    if f[0] == f[i] || f[0] == f[i + 1] {
-                        continue;
-                    }
-                    if set.insert(tri.clone()) {
-                        adjust_order(&mut tri, &all_sphere_verts, Vertex::new(0.0, 0.0, 0.0));
+            // triangulize & unique
+            let surfaces = (0..models.len())
+                .map(|k| positions.iter().map(|p| p[k]).collect::<Vec<_>>())
+                .collect::<Vec<_>>();
+            let surfaces = surfaces.iter().map(|s| s.as_slice()).collect::<Vec<_>>();
+            let mut triangle_faces = Vec::new();
+            let mut set = std::collections::BTreeSet::<Vec<usize>>::new();
+            for (k, f) in all_faces.iter().enumerate() {
+                hooks.progress(
+                    Stage::Triangulation,
+                    0.5 * k as f64 / all_faces.len() as f64,
+                )?;
+                let tris = triangulate(f, &all_sphere_verts, &surfaces);
+                if tris.is_empty() {
+                    dropped_faces += 1;
+                }
+                for tri in tris {
+                    let mut key = tri.clone();
+                    key.sort();
+                    if set.insert(key) {
                         triangle_faces.push(tri);
+                    } else {
+                        dropped_faces += 1;
                     }
                 }
-            } else {
-                let mut tri = f;
-                tri.sort();
-                if set.insert(tri.clone()) {
-                    adjust_order(&mut tri, &all_sphere_verts, Vertex::new(0.0, 0.0, 0.0));
-                    triangle_faces.push(tri);
+            }
+            triangle_faces
+        };
+
+        // texture coordinates and normals of each face corner on every model,
+        // transferred from the source face containing the merged face, so that
+        // corners of a merged vertex on a seam or crease get their own values,
+        // and the labels of the source faces
+        let mut texcoords = CornerValues::default();
+        let mut normals = CornerValues::default();
+        let mut labels = Vec::new();
+        let mut face_labels = Vec::new();
+        let textured = models.iter().any(|m| !m.texcoords.is_empty());
+        let with_normals = models.iter().any(|m| !m.normals.is_empty());
+        let labeled = models.iter().any(|m| !m.face_labels.is_empty());
+        if labeled && !config.edge_only {
+            labels = models
+                .iter()
+                .map(|m| {
+                    if m.face_labels.is_empty() {
+                        vec![FaceLabel::default()]
+                    } else {
+                        m.labels.clone()
+                    }
+                })
+                .collect();
+        }
+        let mut face_sources = Vec::new();
+        if !config.edge_only {
+            for (k, f) in triangle_faces.iter().enumerate() {
+                hooks.progress(
+                    Stage::Triangulation,
+                    0.5 + 0.5 * k as f64 / triangle_faces.len() as f64,
+                )?;
+                let center = f.iter().fold(Vertex::new(0.0, 0.0, 0.0), |acc, &i| {
+                    acc + all_sphere_verts[i]
+                });
+                // walking from the face of a corner, faces falling between
+                // source faces take the nearest for labels only
+                let located = models
+                    .iter()
+                    .enumerate()
+                    .map(|(j, m)| m.locate(center, locations[f[0]][j].face))
+                    .collect::<Vec<_>>();
+                let sources = located
+                    .iter()
+                    .map(|l| {
+                        l.filter(|(_, w)| w.iter().all(|x| *x > -EPS))
+                            .map(|(k, _)| k)
+                    })
+                    .collect::<Vec<_>>();
+                let nearest = located
+                    .iter()
+                    .map(|l| l.map_or(0, |(k, _)| k))
+                    .collect::<Vec<_>>();
+                let values = |at: fn(&ProjectionModel, usize, Vertex) -> Option<Vertex>| {
+                    f.iter()
+                        .map(|&i| {
+                            let v = models
+                                .iter()
+                                .zip(&sources)
+                                .map(|(m, s)| {
+                                    s.and_then(|k| at(m, k, all_sphere_verts[i]))
+                                        .unwrap_or_else(|| Vertex::new(0.0, 0.0, 0.0))
+                                })
+                                .collect();
+                            (i, v)
+                        })
+                        .collect()
+                };
+                if textured {
+                    texcoords.push_face(values(ProjectionModel::texcoord_at));
+                }
+                if with_normals {
+                    normals.push_face(values(ProjectionModel::normal_at));
+                }
+                if labeled {
+                    let l = models
+                        .iter()
+                        .zip(&nearest)
+                        .map(|(m, &k)| {
+                            if m.face_labels.is_empty() {
+                                0
+                            } else {
+                                m.face_labels[k]
+                            }
+                        })
+                        .collect();
+                    face_labels.push(l);
                 }
+                face_sources.push(nearest);
             }
+            hooks.progress(Stage::Triangulation, 1.0)?;
+            hooks.intermediate(Intermediate::Triangulation {
+                faces: &triangle_faces,
+            });
         }
 
-        MergedModel {
-            vert_pairs: model_vert_pairs,
-            faces: triangle_faces,
+        let stats = MergeStats {
+            input_verts: models.iter().map(|m| m.nr_verts()).collect(),
+            input_faces: models.iter().map(|m| m.nr_faces()).collect(),
+            output_verts: positions.len(),
+            output_faces: triangle_faces.len(),
+            intersections,
+            timings: hooks.timings(),
+            snapped_verts: snapped,
+            dropped_faces,
+        };
+        if dropped_faces > 0 {
+            log::info!("Dropped {} degenerate faces", dropped_faces);
         }
+
+        Ok(Overlay {
+            positions,
+            faces: triangle_faces,
+            placements,
+            texcoords: texcoords.values,
+            face_texcoords: texcoords.faces,
+            colors,
+            normals: normals.values,
+            face_normals: normals.faces,
+            attributes,
+            labels,
+            face_labels,
+            sources,
+            locations,
+            face_sources,
+            stats,
+        })
     }
 
     pub fn interpolation(&self, ratio: f64) -> Model {
-        let mut new_verts = Vec::new();
-        for (v1, v2) in &self.vert_pairs {
-            new_verts.push(*v1 + (*v2 - *v1) * ratio);
+        self.interpolation_with_ratios(&vec![ratio; self.vert_pairs.len()])
+    }
+
+    /// Interpolation with a separate ratio for each merged vertex.
+    pub fn interpolation_with_ratios(&self, ratios: &[f64]) -> Model {
+        self.interpolation_with_method(ratios, InterpolationMethod::Linear)
+    }
+
+    /// Snaps the vertices of the model starting at `n` to the earlier vertices
+    /// or arcs `edges1`, and earlier vertices to the arcs `edges2` of the model.
+    fn snap_vertices(
+        verts: &mut [SphereVertex],
+        n: usize,
+        edges1: &EdgeList,
+        edges2: &EdgeList,
+        tolerance: f64,
+    ) -> usize {
+        let mut count = 0;
+
+        // vertices of the model to earlier vertices, else to earlier arcs
+        for j in n..verts.len() {
+            let v = verts[j].v;
+            let nearest = |candidates: &mut dyn Iterator<Item = Vertex>| {
+                candidates
+                    .map(|p| (Vertex::angle(p, v), p))
+                    .filter(|(angle, _)| *angle < tolerance)
+                    .min_by(|a, b| a.0.total_cmp(&b.0))
+            };
+            let nearest = nearest(&mut (0..n).map(|i| verts[i].v)).or_else(|| {
+                nearest(&mut edges1.iter().filter_map(|e| {
+                    Arc::new(verts[e.from].v, verts[e.to].v, e.from, e.to).closest(v)
+                }))
+            });
+            if let Some((angle, p)) = nearest {
+                if angle > 0.0 {
+                    verts[j].v = p;
+                    count += 1;
+                }
+            }
         }
 
-        let mut model = Model::new(new_verts, self.faces.clone());
-        let center = model.center();
-        for mut f in &mut model.faces {
-            adjust_order(&mut f, &model.verts, center);
+        // earlier vertices to arcs of the model
+        for i in 0..n {
+            let v = verts[i].v;
+            if verts[n..].iter().any(|u| u.v == v) {
+                continue;
+            }
+            let nearest = edges2
+                .iter()
+                .filter_map(|e| {
+                    let (a, b) = (verts[e.from + n].v, verts[e.to + n].v);
+                    if a == v || b == v {
+                        return None;
+                    }
+                    let arc = Arc::new(a, b, e.from + n, e.to + n);
+                    arc.closest(v).map(|p| (Vertex::angle(p, v), p))
+                })
+                .filter(|(angle, _)| *angle < tolerance)
+                .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+            if let Some((_, p)) = nearest {
+                verts[i].v = p;
+                count += 1;
+            }
         }
-        model
+        count
     }
 
-    fn resolve_faces(verts: &Vec<Vertex>, edges: &EdgeList) -> Vec<Face> {
+    fn resolve_faces(verts: &[Vertex], edges: &EdgeList) -> Vec<Face> {
         let n = verts.len();
         let mut graph = Graph::new(verts);
         for e in edges.iter() {
@@ -427,7 +1442,7 @@ impl MergedModel {
             adj_edges.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
             for j in 0..m {
                 let k = if j == m - 1 { 0 } else { j + 1 };
-                adj_edges[j].1.borrow_mut().next = std::rc::Rc::downgrade(&adj_edges[k].1);
+                adj_edges[j].1.borrow_mut().next = std::rc::Rc::downgrade(adj_edges[k].1);
             }
         }
 
@@ -453,3 +1468,226 @@ impl MergedModel {
         faces
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merge;
+    use crate::testing::{cube, octahedron, temp_path};
+
+    /// The cube turned by a small angle around the z axis, then the x axis.
+    fn turned_cube(angle: f64) -> Model {
+        let (sin, cos) = angle.sin_cos();
+        let cube = cube();
+        let verts = cube
+            .verts
+            .iter()
+            .map(|v| Vertex::new(v.x * cos - v.y * sin, v.x * sin + v.y * cos, v.z))
+            .map(|v| Vertex::new(v.x, v.y * cos - v.z * sin, v.y * sin + v.z * cos))
+            .collect();
+        Model::new(verts, cube.faces)
+    }
+
+    #[test]
+    fn snapping_merges_nearly_coincident_vertices() {
+        let config = |snap_tolerance| Config {
+            snap_tolerance,
+            ..Config::default()
+        };
+        let same = merge(cube(), cube(), config(0.0));
+        let merged = merge(cube(), turned_cube(1e-4), config(0.0));
+        assert_eq!(merged.stats().snapped_verts, 0);
+        assert!(merged.nr_faces() > same.nr_faces());
+
+        let merged = merge(cube(), turned_cube(1e-4), config(1e-3));
+        assert_eq!(merged.stats().snapped_verts, 8);
+        assert_eq!(merged.nr_verts(), same.nr_verts());
+        assert_eq!(merged.faces(), same.faces());
+    }
+
+    #[test]
+    fn snapping_keeps_distant_vertices() {
+        let config = Config {
+            snap_tolerance: 1e-3,
+            ..Config::default()
+        };
+        let same = merge(cube(), cube(), Config::default());
+        let merged = merge(cube(), turned_cube(0.1), config);
+        assert_eq!(merged.stats().snapped_verts, 0);
+        assert!(merged.nr_faces() > same.nr_faces());
+    }
+
+    fn moved(model: Model, center: Vertex, scale: f64) -> Model {
+        let verts = model.verts.iter().map(|&v| center + v * scale).collect();
+        Model::new(verts, model.faces)
+    }
+
+    fn assert_bbox(verts: &[Vertex], center: Vertex, size: f64) {
+        let (min, max) = Vertex::bounding_box(verts);
+        let half = Vertex::new(size, size, size) / 2.0;
+        assert!(Vertex::dist(min, center - half) < 1e-9, "{:?}", min);
+        assert!(Vertex::dist(max, center + half) < 1e-9, "{:?}", max);
+    }
+
+    #[test]
+    fn keep_coords_restores_outputs_only() {
+        let center1 = Vertex::new(5.0, 0.0, 0.0);
+        let center2 = Vertex::new(0.0, -3.0, 1.0);
+        let merged = merge(
+            moved(cube(), center1, 2.0),
+            moved(octahedron(), center2, 0.5),
+            Config {
+                keep_coords: true,
+                ..Config::default()
+            },
+        );
+        assert_bbox(&merged.interpolation(0.0).verts, center1, 4.0);
+        assert_bbox(&merged.interpolation(1.0).verts, center2, 1.0);
+
+        let path = temp_path("keep_coords.obj");
+        merged.save(&path).unwrap();
+        let saved = Model::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(temp_path("keep_coords.prov")).unwrap();
+        assert_bbox(&saved.unwrap().verts, center1, 4.0);
+
+        // the vertex pairs stay centered
+        let verts1 = merged.vert_pairs().iter().map(|p| p.0).collect::<Vec<_>>();
+        let (min, max) = Vertex::bounding_box(&verts1);
+        assert!(((min + max) / 2.0).len() < 1e-9);
+        let [p1, _] = merged.placements();
+        assert_bbox(
+            &verts1.iter().map(|&v| p1.restore(v)).collect::<Vec<_>>(),
+            center1,
+            4.0,
+        );
+    }
+
+    fn load_str(name: &str, content: &str) -> io::Result<Model> {
+        let path = temp_path(name);
+        std::fs::write(&path, content).unwrap();
+        let model = Model::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        model
+    }
+
+    #[test]
+    fn load_checks_texcoord_indices() {
+        let head = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 0 1\nvn 0 0 1\n";
+        let model = load_str("vt.obj", &format!("{}f 1/1 2/2 3/3\n", head)).unwrap();
+        assert_eq!(model.face_texcoords, [vec![0, 1, 2]]);
+        assert_eq!(model.texcoords[1], [Vertex::new(1.0, 0.0, 0.0)]);
+        let model = load_str("vt.obj", &format!("{}f 1//1 2//1 3//1\n", head)).unwrap();
+        assert!(model.texcoords.is_empty());
+        for face in [
+            "f 1/x 2/2 3/3",
+            "f 1/0 2/2 3/3",
+            "f 1/4 2/2 3/3",
+            "f 1/-1 2/2 3/3",
+        ] {
+            let err = load_str("vt.obj", &format!("{}{}\n", head, face)).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{}", face);
+        }
+    }
+
+    #[test]
+    fn load_checks_normal_indices() {
+        let head = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvn 0 0 2\n";
+        let model = load_str("vn.obj", &format!("{}f 1//1 2//1 3//1\n", head)).unwrap();
+        assert_eq!(model.face_normals, [vec![0, 0, 0]]);
+        assert_eq!(model.normals, [Vertex::new(0.0, 0.0, 1.0)]);
+        let model = load_str("vn.obj", &format!("{}f 1/1/1 2/1/1 3/1/1\n", head)).unwrap();
+        assert_eq!(model.face_texcoords, [vec![0, 0, 0]]);
+        assert_eq!(model.face_normals, [vec![0, 0, 0]]);
+        for face in [
+            "f 1//x 2//1 3//1",
+            "f 1//0 2//1 3//1",
+            "f 1//2 2//1 3//1",
+            "f 1/1/ 2/1/1 3/1/1",
+        ] {
+            let err = load_str("vn.obj", &format!("{}{}\n", head, face)).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{}", face);
+        }
+    }
+
+    #[test]
+    fn texcoords_are_transferred() {
+        // a planar projection, linear across the faces
+        let uv = |v: Vertex| Vertex::new(v.x + 0.5 * v.z, v.y - 0.25 * v.z, 0.0);
+        let mut textured = cube();
+        textured.texcoords = textured.verts.iter().map(|&v| vec![uv(v)]).collect();
+        textured.face_texcoords = textured.faces.clone();
+        let merged = merge(textured, octahedron(), Config::default());
+        assert_eq!(merged.face_texcoords.len(), merged.nr_faces());
+        let [p1, _] = merged.placements();
+        for (f, t) in merged.faces().iter().zip(&merged.face_texcoords) {
+            for (&i, &j) in f.iter().zip(t) {
+                let v = p1.restore(merged.vert_pairs()[i].0);
+                assert!(Vertex::dist(merged.texcoord_pairs[j].0, uv(v)) < 1e-6);
+            }
+        }
+
+        let model = merged.interpolation(0.5);
+        assert_eq!(model.face_texcoords, merged.face_texcoords);
+        assert!(model.texcoords.iter().all(|t| t.len() == 2));
+    }
+
+    #[test]
+    fn model_accessors() {
+        let model = Model::from_slices(
+            &[[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+            &[[0, 1, 2]],
+        );
+        assert_eq!(model.verts()[1], Vertex::new(1.0, 0.0, 0.0));
+        assert_eq!(model.faces(), [vec![0, 1, 2]]);
+        assert!(model.colors().is_empty());
+        let corners: [f64; 3] = model.verts()[2].into();
+        assert_eq!(corners, [0.0, 1.0, 0.0]);
+
+        // polygons are split into fans
+        let quad = Model::new(cube().verts, vec![vec![0, 1, 2, 3]]);
+        let triangles = quad.triangles().collect::<Vec<_>>();
+        let v = |i: usize| quad.verts()[i];
+        assert_eq!(triangles, [[v(0), v(1), v(2)], [v(0), v(2), v(3)]]);
+        assert_eq!(cube().triangles().count(), 12);
+    }
+
+    #[test]
+    #[should_panic(expected = "Face index out of range")]
+    fn model_faces_are_in_range() {
+        Model::from_slices(&[[0.0, 0.0, 0.0]], &[[0, 1, 2]]);
+    }
+
+    #[test]
+    fn merged_model_accessors() {
+        let verts = cube().verts;
+        let pairs = verts.iter().map(|&v| (v, v * 2.0)).collect::<Vec<_>>();
+        let merged = MergedModel::new(pairs.clone(), cube().faces);
+        assert_eq!(merged.vert_pairs(), &pairs[..]);
+        assert_eq!(merged.faces(), cube().faces());
+        assert_eq!((merged.nr_verts(), merged.nr_faces()), (8, 12));
+        assert!(merged.color_pairs().is_empty() && merged.provenance().is_empty());
+        for (t1, t2) in merged.triangle_pairs() {
+            for (a, b) in t1.iter().zip(&t2) {
+                assert_eq!(*a * 2.0, *b);
+            }
+        }
+        assert_eq!(merged.triangle_pairs().count(), 12);
+        let model = merged.interpolation(0.5);
+        assert_eq!(model.verts()[6], Vertex::new(1.5, 1.5, 1.5));
+    }
+
+    #[test]
+    #[should_panic(expected = "Faces must be triangles")]
+    fn merged_faces_are_triangles() {
+        let pairs = cube().verts.iter().map(|&v| (v, v)).collect();
+        MergedModel::new(pairs, vec![vec![0, 1, 2, 3]]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Face index out of range")]
+    fn merged_faces_are_in_range() {
+        let pairs = cube().verts.iter().map(|&v| (v, v)).collect();
+        MergedModel::new(pairs, vec![vec![0, 1, 8]]);
+    }
+}
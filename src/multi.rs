@@ -0,0 +1,347 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufWriter};
+
+use crate::attribute::{self, Attribute};
+use crate::field::CountMismatch;
+use crate::geo::{Face, Vertex, EPS};
+use crate::label::{combine_labels, FaceLabel};
+use crate::merger::Hooks;
+use crate::model::{write_faces, write_normals, write_texcoords, write_verts, ProjectionModel};
+use crate::placement::Placement;
+use crate::stats::MergeStats;
+use crate::{Config, MergedModel, Model, Spline};
+
+/// Common mesh of several models, with the position of each vertex on every one
+/// of them.
+#[derive(Debug)]
+pub struct MultiMergedModel {
+    pub(crate) faces: Vec<Face>,
+    pub(crate) positions: Vec<Vec<Vertex>>,
+    pub(crate) placements: Vec<Placement>,
+    pub(crate) keep_coords: bool,
+    pub(crate) texcoords: Vec<Vec<Vertex>>,
+    pub(crate) face_texcoords: Vec<Face>,
+    pub(crate) colors: Vec<Vec<Vertex>>,
+    pub(crate) normals: Vec<Vec<Vertex>>,
+    pub(crate) face_normals: Vec<Face>,
+    pub(crate) attributes: BTreeMap<String, Vec<Attribute>>,
+    pub(crate) labels: Vec<Vec<FaceLabel>>,
+    pub(crate) face_labels: Vec<Vec<usize>>,
+    pub(crate) stats: MergeStats,
+}
+
+impl MultiMergedModel {
+    pub(crate) fn merge(models: &[ProjectionModel], config: Config, hooks: &mut Hooks) -> Self {
+        let overlay = MergedModel::merge_all_with(models, &config, hooks)
+            .expect("Merge cancelled without a cancel token");
+        Self {
+            faces: overlay.faces,
+            positions: overlay.positions,
+            placements: overlay.placements,
+            keep_coords: config.keep_coords,
+            texcoords: overlay.texcoords,
+            face_texcoords: overlay.face_texcoords,
+            colors: overlay.colors,
+            normals: overlay.normals,
+            face_normals: overlay.face_normals,
+            attributes: overlay.attributes,
+            labels: overlay.labels,
+            face_labels: overlay.face_labels,
+            stats: overlay.stats,
+        }
+    }
+
+    pub fn nr_models(&self) -> usize {
+        self.positions.first().map_or(0, |p| p.len())
+    }
+
+    pub fn placements(&self) -> &[Placement] {
+        &self.placements
+    }
+
+    pub fn nr_verts(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn nr_faces(&self) -> usize {
+        self.faces.len()
+    }
+
+    /// Saves the positions on the first model as `v` lines, followed by one
+    /// block of `u` lines for each further model, and likewise the texture
+    /// coordinates as `vt` and `ut` lines.
+    pub fn save(&self, filename: &str) -> io::Result<()> {
+        assert!(filename.ends_with(".obj"));
+
+        let file = File::create(filename)?;
+        let mut writer = BufWriter::new(file);
+
+        for k in 0..self.nr_models() {
+            let prefix = if k == 0 { "v" } else { "u" };
+            let verts = self
+                .positions
+                .iter()
+                .map(|p| {
+                    if self.keep_coords {
+                        self.placements[k].restore(p[k])
+                    } else {
+                        p[k]
+                    }
+                })
+                .collect::<Vec<_>>();
+            let colors = self.colors.iter().map(|c| c[k]).collect::<Vec<_>>();
+            write_verts(&mut writer, prefix, &verts, &colors)?;
+        }
+        write_texcoords(&mut writer, &self.texcoords)?;
+        write_normals(&mut writer, &self.normals)?;
+        let (labels, face_labels) = combine_labels(&self.labels, &self.face_labels);
+        write_faces(
+            &mut writer,
+            &self.faces,
+            &self.face_texcoords,
+            &self.face_normals,
+            &labels,
+            &face_labels,
+        )
+    }
+
+    /// The model at `ratio` along the whole sequence, `0` being the first model
+    /// and `1` the last one.
+    pub fn interpolation(&self, ratio: f64, spline: Spline) -> Model {
+        let n = self.nr_models();
+        let t = ratio * n.saturating_sub(1) as f64;
+        self.combine(&spline.weights(n, t))
+    }
+
+    /// Weighted combination of the models, one weight per model. Weights summing
+    /// to 1 give affine combinations, extrapolating if some are negative, while
+    /// other sums also scale the result. Fails if the number of weights is not
+    /// the number of models.
+    pub fn blend(&self, weights: &[f64]) -> Result<Model, CountMismatch> {
+        if weights.len() != self.nr_models() {
+            return Err(CountMismatch {
+                what: "weights",
+                expected: self.nr_models(),
+                found: weights.len(),
+            });
+        }
+        Ok(self.combine(weights))
+    }
+
+    fn combine(&self, weights: &[f64]) -> Model {
+        let combine = |p: &Vec<Vertex>| {
+            p.iter()
+                .zip(weights)
+                .fold(Vertex::new(0.0, 0.0, 0.0), |acc, (&v, &w)| acc + v * w)
+        };
+        let new_verts = self
+            .positions
+            .iter()
+            .map(|p| {
+                if self.keep_coords {
+                    let p = p.iter().zip(&self.placements).map(|(&v, pl)| pl.restore(v));
+                    combine(&p.collect())
+                } else {
+                    combine(p)
+                }
+            })
+            .collect();
+
+        let mut model = Model::new(new_verts, self.faces.clone());
+        model.colors = self.colors.iter().map(combine).collect();
+        model.texcoords = self.texcoords.clone();
+        model.face_texcoords = self.face_texcoords.clone();
+        model.normals = self
+            .normals
+            .iter()
+            .map(|n| {
+                let n = combine(n);
+                if n.len() > EPS {
+                    n.unit()
+                } else {
+                    n
+                }
+            })
+            .collect();
+        model.face_normals = self.face_normals.clone();
+        model.attributes = self
+            .attributes
+            .iter()
+            .map(|(name, values)| {
+                let a = &values[0];
+                let rows = (0..a.len()).map(|i| {
+                    let v = values
+                        .iter()
+                        .zip(weights)
+                        .map(|(b, &w)| (b.get(i), w))
+                        .collect::<Vec<_>>();
+                    attribute::mix(a.policy(), a.arity(), &v)
+                });
+                (name.clone(), a.with_rows(rows))
+            })
+            .collect();
+        // labels of the model with the largest weight
+        if !self.face_labels.is_empty() {
+            let k = (0..weights.len())
+                .max_by(|&a, &b| weights[a].total_cmp(&weights[b]))
+                .unwrap();
+            model.labels = self.labels[k].clone();
+            model.face_labels = self.face_labels.iter().map(|l| l[k]).collect();
+        }
+        model.orient();
+        model
+    }
+}
+
+impl From<MergedModel> for MultiMergedModel {
+    fn from(model: MergedModel) -> Self {
+        Self {
+            faces: model.faces,
+            positions: model.vert_pairs.iter().map(|p| vec![p.0, p.1]).collect(),
+            placements: model.placements.to_vec(),
+            keep_coords: model.keep_coords,
+            texcoords: model
+                .texcoord_pairs
+                .iter()
+                .map(|t| vec![t.0, t.1])
+                .collect(),
+            face_texcoords: model.face_texcoords,
+            colors: model.color_pairs.iter().map(|c| vec![c.0, c.1]).collect(),
+            normals: model.normal_pairs.iter().map(|n| vec![n.0, n.1]).collect(),
+            face_normals: model.face_normals,
+            attributes: model
+                .attribute_pairs
+                .into_iter()
+                .map(|(name, (a, b))| (name, vec![a, b]))
+                .collect(),
+            labels: if model.face_labels.is_empty() {
+                Vec::new()
+            } else {
+                model.labels.to_vec()
+            },
+            face_labels: model.face_labels.iter().map(|l| vec![l.0, l.1]).collect(),
+            stats: model.stats,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{cube, octahedron, temp_path};
+    use crate::{merge, merge_all};
+
+    fn merged() -> MultiMergedModel {
+        merge_all(vec![cube(), octahedron(), cube()], &[], Config::default()).unwrap()
+    }
+
+    fn assert_verts(model: &Model, verts: impl Iterator<Item = Vertex>) {
+        let mut n = 0;
+        for (v, w) in model.verts.iter().zip(verts) {
+            assert!(Vertex::dist(*v, w) < 1e-9);
+            n += 1;
+        }
+        assert_eq!(n, model.nr_verts());
+    }
+
+    #[test]
+    fn merges_all_models() {
+        let merged = merged();
+        assert_eq!(merged.nr_models(), 3);
+        assert!(merged.positions.iter().all(|p| p.len() == 3));
+        assert!(merged
+            .faces
+            .iter()
+            .flatten()
+            .all(|&i| i < merged.nr_verts()));
+        // the first and last models are the same
+        assert!(merged
+            .positions
+            .iter()
+            .all(|p| Vertex::dist(p[0], p[2]) < 1e-6));
+    }
+
+    #[test]
+    fn interpolation_passes_through_models() {
+        let merged = merged();
+        for spline in [Spline::Linear, Spline::CatmullRom, Spline::BSpline] {
+            for (ratio, k) in [(0.0, 0), (0.5, 1), (1.0, 2)] {
+                let model = merged.interpolation(ratio, spline);
+                assert_verts(&model, merged.positions.iter().map(|p| p[k]));
+            }
+        }
+    }
+
+    #[test]
+    fn two_models_interpolate_as_merged_model() {
+        let merged = merge(cube(), octahedron(), Config::default());
+        let expected = merged.interpolation(0.3);
+        let multi = MultiMergedModel::from(merged);
+        assert_eq!(multi.nr_models(), 2);
+        let model = multi.interpolation(0.3, Spline::Linear);
+        assert_verts(&model, expected.verts.iter().copied());
+        assert_eq!(model.faces, expected.faces);
+    }
+
+    #[test]
+    fn save_writes_a_block_per_model() {
+        let merged = merged();
+        let path = temp_path("multi.obj");
+        merged.save(&path).unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let count = |prefix: &str| content.lines().filter(|l| l.starts_with(prefix)).count();
+        assert_eq!(count("v "), merged.nr_verts());
+        assert_eq!(count("u "), merged.nr_verts() * 2);
+        assert_eq!(count("f "), merged.nr_faces());
+    }
+
+    #[test]
+    fn blend_checks_weight_count() {
+        let err = merged().blend(&[0.5, 0.5]).err();
+        assert_eq!(
+            err,
+            Some(CountMismatch {
+                what: "weights",
+                expected: 3,
+                found: 2
+            })
+        );
+    }
+
+    #[test]
+    fn blend_combines_models() {
+        let merged = merged();
+        let weights = [0.2, 0.3, 0.5];
+        let model = merged.blend(&weights).unwrap();
+        assert_verts(
+            &model,
+            merged
+                .positions
+                .iter()
+                .map(|p| p[0] * 0.2 + p[1] * 0.3 + p[2] * 0.5),
+        );
+        let twice = merged.blend(&[0.4, 0.6, 1.0]).unwrap();
+        assert_verts(&twice, model.verts.iter().map(|&v| v * 2.0));
+    }
+
+    #[test]
+    fn blend_takes_labels_of_largest_weight() {
+        let mut merged = merged();
+        merged.labels = ["a", "b", "c"]
+            .iter()
+            .map(|g| {
+                vec![FaceLabel {
+                    group: Some(g.to_string()),
+                    material: None,
+                }]
+            })
+            .collect();
+        merged.face_labels = vec![vec![0; 3]; merged.nr_faces()];
+        let model = merged.blend(&[0.2, 0.7, 0.1]).unwrap();
+        assert_eq!(model.face_label(0).unwrap().group.as_deref(), Some("b"));
+        // NaN weights do not panic
+        assert!(merged.blend(&[f64::NAN, 0.5, 0.5]).is_ok());
+    }
+}
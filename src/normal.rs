@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::geo::{Vertex, EPS};
+use crate::{MergedModel, Model};
+
+const NORMAL_EPS: f64 = 1e-9;
+
+/// Weight of the faces around a vertex when averaging their normals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NormalWeighting {
+    Area,
+    Angle, // angle of the face at the vertex
+}
+
+impl Model {
+    /// Computes vertex normals from the normals of the faces around each
+    /// vertex. Faces meeting at more than `crease_angle` (in radians) are not
+    /// averaged together, so that hard edges stay sharp: `0` gives flat
+    /// shading and `PI` smooth shading.
+    pub fn compute_normals(&mut self, weighting: NormalWeighting, crease_angle: f64) {
+        // normals of the faces, twice their area long
+        let face_normals = self
+            .faces
+            .iter()
+            .map(|f| {
+                let p = |i: usize| self.verts[f[i]];
+                (1..f.len().saturating_sub(1)).fold(Vertex::new(0.0, 0.0, 0.0), |acc, i| {
+                    acc + (p(i) - p(0)) * (p(i + 1) - p(0))
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut vert_faces = vec![Vec::new(); self.verts.len()];
+        for (k, f) in self.faces.iter().enumerate() {
+            for (c, &i) in f.iter().enumerate() {
+                vert_faces[i].push((k, c));
+            }
+        }
+        let weight = |k: usize, c: usize| match weighting {
+            NormalWeighting::Area => face_normals[k].len(),
+            NormalWeighting::Angle => {
+                let f = &self.faces[k];
+                let n = f.len();
+                let p = self.verts[f[c]];
+                Vertex::angle(
+                    self.verts[f[(c + 1) % n]] - p,
+                    self.verts[f[(c + n - 1) % n]] - p,
+                )
+            }
+        };
+
+        let mut normals = Vec::new();
+        let mut face_corners = Vec::with_capacity(self.faces.len());
+        let mut index = HashMap::new();
+        for (k, f) in self.faces.iter().enumerate() {
+            let mut corners = Vec::with_capacity(f.len());
+            for &i in f {
+                let nk = face_normals[k];
+                let n = vert_faces[i]
+                    .iter()
+                    .filter(|&&(g, _)| {
+                        let ng = face_normals[g];
+                        // degenerate faces take the normals of all their neighbors
+                        g == k
+                            || nk.len() <= EPS
+                            || (ng.len() > EPS && Vertex::angle(nk, ng) <= crease_angle + EPS)
+                    })
+                    .fold(Vertex::new(0.0, 0.0, 0.0), |acc, &(g, c)| {
+                        let ng = face_normals[g];
+                        if ng.len() > EPS {
+                            acc + ng.unit() * weight(g, c)
+                        } else {
+                            acc
+                        }
+                    });
+                let n = if n.len() > EPS { n.unit() } else { n };
+                let key = (i, [n.x, n.y, n.z].map(|x| (x / NORMAL_EPS).round() as i64));
+                corners.push(*index.entry(key).or_insert_with(|| {
+                    normals.push(n);
+                    normals.len() - 1
+                }));
+            }
+            face_corners.push(corners);
+        }
+        self.normals = normals;
+        self.face_normals = face_corners;
+    }
+}
+
+impl MergedModel {
+    /// Normals transferred from the inputs, blended by the ratio of their
+    /// vertex.
+    pub(crate) fn interpolated_normals(&self, ratios: &[f64]) -> Vec<Vertex> {
+        let mut normal_ratios = vec![0.0; self.normal_pairs.len()];
+        for (f, n) in self.faces.iter().zip(&self.face_normals) {
+            for (&i, &j) in f.iter().zip(n) {
+                normal_ratios[j] = ratios[i];
+            }
+        }
+        self.normal_pairs
+            .iter()
+            .zip(&normal_ratios)
+            .map(|(n, &r)| {
+                let n = n.0 + (n.1 - n.0) * r;
+                if n.len() > EPS {
+                    n.unit()
+                } else {
+                    n
+                }
+            })
+            .collect()
+    }
+}
+
+impl FromStr for NormalWeighting {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "area" => Ok(NormalWeighting::Area),
+            "angle" => Ok(NormalWeighting::Angle),
+            _ => Err(format!("Invalid normal weighting \"{}\"", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merge;
+    use crate::testing::{cube, octahedron};
+    use crate::Config;
+    use std::f64::consts::PI;
+
+    fn corner_normals(model: &Model, i: usize) -> Vec<Vertex> {
+        let mut normals = Vec::new();
+        for (f, n) in model.faces.iter().zip(&model.face_normals) {
+            for (&v, &j) in f.iter().zip(n) {
+                if v == i && !normals.contains(&model.normals[j]) {
+                    normals.push(model.normals[j]);
+                }
+            }
+        }
+        normals
+    }
+
+    #[test]
+    fn crease_angle_splits_normals() {
+        let mut model = cube();
+        model.compute_normals(NormalWeighting::Angle, 0.0);
+        // one normal per side and corner
+        assert_eq!(model.normals.len(), 24);
+        assert_eq!(model.face_normals.len(), model.nr_faces());
+        assert!(corner_normals(&model, 0).contains(&Vertex::new(-1.0, 0.0, 0.0)));
+
+        model.compute_normals(NormalWeighting::Angle, PI);
+        assert_eq!(model.normals.len(), 8);
+        for (i, &v) in model.verts.iter().enumerate() {
+            let n = corner_normals(&model, i);
+            assert_eq!(n.len(), 1);
+            assert!(Vertex::dist(n[0], v.unit()) < 1e-9);
+        }
+    }
+
+    #[test]
+    fn area_weighting_favors_large_faces() {
+        // vertex 0 is in two triangles of the bottom and front sides, but one of
+        // the left side
+        let mut model = cube();
+        model.compute_normals(NormalWeighting::Area, PI);
+        let n = corner_normals(&model, 0)[0];
+        assert!(Vertex::dist(n, Vertex::new(-1.0, -2.0, -2.0) / 3.0) < 1e-9);
+    }
+
+    #[test]
+    fn normals_are_interpolated() {
+        let mut model1 = cube();
+        model1.compute_normals(NormalWeighting::Angle, 0.0);
+        let mut model2 = octahedron();
+        model2.compute_normals(NormalWeighting::Angle, 0.0);
+        let merged = merge(model1, model2, Config::default());
+        assert_eq!(merged.face_normals.len(), merged.nr_faces());
+        for (t, end) in [(0.0, false), (1.0, true)] {
+            let normals = merged.interpolated_normals(&vec![t; merged.nr_verts()]);
+            for (n, p) in normals.iter().zip(&merged.normal_pairs) {
+                let expected = if end { p.1 } else { p.0 };
+                assert!(Vertex::dist(*n, expected.unit()) < 1e-9);
+            }
+        }
+        for n in merged.interpolated_normals(&vec![0.5; merged.nr_verts()]) {
+            assert!((n.len() - 1.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn parsing() {
+        assert_eq!("area".parse(), Ok(NormalWeighting::Area));
+        assert_eq!("angle".parse(), Ok(NormalWeighting::Angle));
+        assert!("uniform".parse::<NormalWeighting>().is_err());
+    }
+}
@@ -0,0 +1,213 @@
+use crate::geo::{Quaternion, Vertex};
+use crate::interpolate::InterpolationMethod;
+use crate::{MergedModel, Model};
+
+/// Where an input model sits in world space relative to the normalized frame
+/// of the merged model, i.e. its center, its size and its rotation (from the
+/// alignment of the sphere embeddings).
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Placement {
+    pub center: Vertex,
+    pub scale: f64,
+    pub rotation: Quaternion,
+}
+
+impl Placement {
+    pub fn identity() -> Self {
+        Self {
+            center: Vertex::new(0.0, 0.0, 0.0),
+            scale: 1.0,
+            rotation: Quaternion::identity(),
+        }
+    }
+
+    /// Maps a point of the common frame to world space.
+    pub fn apply(&self, v: Vertex) -> Vertex {
+        self.center + self.rotation.to_matrix() * v * self.scale
+    }
+
+    /// Maps a point of the input in the normalized frame back to its original
+    /// coordinates.
+    pub fn restore(&self, v: Vertex) -> Vertex {
+        self.center + v * self.scale
+    }
+
+    /// Maps a point of the input in the normalized frame to the common frame,
+    /// undoing the rotation of the alignment.
+    pub fn unrotate(&self, v: Vertex) -> Vertex {
+        self.rotation.to_matrix().transpose() * v
+    }
+
+    /// Translation lerp, scale log-lerp and rotation slerp.
+    pub fn interpolate(a: &Placement, b: &Placement, t: f64) -> Placement {
+        Placement {
+            center: a.center * (1.0 - t) + b.center * t,
+            scale: (a.scale.ln() * (1.0 - t) + b.scale.ln() * t).exp(),
+            rotation: Quaternion::slerp(a.rotation, b.rotation, t),
+        }
+    }
+}
+
+impl Default for Placement {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl MergedModel {
+    pub fn placements(&self) -> &[Placement; 2] {
+        &self.placements
+    }
+
+    /// Moves interpolated vertices back to the original coordinates if the model
+    /// keeps them, by the interpolated center and scale of the inputs.
+    pub(crate) fn restore_coords(&self, verts: &mut [Vertex], ratios: &[f64]) {
+        if !self.keep_coords {
+            return;
+        }
+        let [p1, p2] = &self.placements;
+        for (v, &t) in verts.iter_mut().zip(ratios) {
+            let center = p1.center * (1.0 - t) + p2.center * t;
+            *v = center + *v * (p1.scale * (1.0 - t) + p2.scale * t);
+        }
+    }
+
+    pub fn world_interpolation(&self, ratio: f64) -> Model {
+        self.world_interpolation_with_method(
+            &vec![ratio; self.vert_pairs.len()],
+            InterpolationMethod::Linear,
+        )
+    }
+
+    /// Interpolation in world space: the shapes are blended in the common
+    /// frame, then moved by the interpolated placement of the inputs.
+    pub fn world_interpolation_with_method(
+        &self,
+        ratios: &[f64],
+        method: InterpolationMethod,
+    ) -> Model {
+        let [p1, p2] = &self.placements;
+        let common = MergedModel {
+            faces: self.faces.clone(),
+            vert_pairs: self
+                .vert_pairs
+                .iter()
+                .map(|p| (p1.unrotate(p.0), p2.unrotate(p.1)))
+                .collect(),
+            placements: [Placement::identity(); 2],
+            keep_coords: false,
+            texcoord_pairs: self.texcoord_pairs.clone(),
+            face_texcoords: self.face_texcoords.clone(),
+            color_pairs: self.color_pairs.clone(),
+            normal_pairs: self
+                .normal_pairs
+                .iter()
+                .map(|n| (p1.unrotate(n.0), p2.unrotate(n.1)))
+                .collect(),
+            face_normals: self.face_normals.clone(),
+            attribute_pairs: self.attribute_pairs.clone(),
+            labels: self.labels.clone(),
+            face_labels: self.face_labels.clone(),
+            provenance: self.provenance.clone(),
+            stats: self.stats.clone(),
+        };
+        let mut model = common.interpolation_with_method(ratios, method);
+        for (v, &t) in model.verts.iter_mut().zip(ratios) {
+            *v = Placement::interpolate(p1, p2, t).apply(*v);
+        }
+        // normals only turn with the rotation
+        let mut normal_ratios = vec![0.0; model.normals.len()];
+        for (f, n) in model.faces.iter().zip(&model.face_normals) {
+            for (&i, &j) in f.iter().zip(n) {
+                normal_ratios[j] = ratios[i];
+            }
+        }
+        for (n, &t) in model.normals.iter_mut().zip(&normal_ratios) {
+            *n = Quaternion::slerp(p1.rotation, p2.rotation, t).to_matrix() * *n;
+        }
+        model
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merge;
+    use crate::testing::{cube, octahedron};
+
+    fn quarter_turn() -> Quaternion {
+        let s = std::f64::consts::FRAC_1_SQRT_2;
+        Quaternion::new(s, Vertex::new(0.0, 0.0, s))
+    }
+
+    fn assert_close(a: Vertex, b: Vertex) {
+        assert!(Vertex::dist(a, b) < 1e-9, "{:?} {:?}", a, b);
+    }
+
+    fn moved(model: Model, center: Vertex, scale: f64) -> Model {
+        let verts = model.verts.iter().map(|&v| center + v * scale).collect();
+        Model::new(verts, model.faces)
+    }
+
+    #[test]
+    fn apply_moves_to_world_space() {
+        let p = Placement {
+            center: Vertex::new(1.0, 2.0, 3.0),
+            scale: 2.0,
+            rotation: quarter_turn(),
+        };
+        let v = Vertex::new(1.0, 0.0, 0.0);
+        assert_close(p.apply(v), Vertex::new(1.0, 4.0, 3.0));
+        assert_close(p.restore(v), Vertex::new(3.0, 2.0, 3.0));
+        assert_close(p.unrotate(Vertex::new(0.0, 1.0, 0.0)), v);
+        assert_close(Placement::identity().apply(v), v);
+    }
+
+    #[test]
+    fn interpolate_blends_each_part() {
+        let a = Placement::identity();
+        let b = Placement {
+            center: Vertex::new(4.0, 0.0, 0.0),
+            scale: 4.0,
+            rotation: quarter_turn(),
+        };
+        assert_eq!(Placement::interpolate(&a, &b, 0.0), a);
+        let end = Placement::interpolate(&a, &b, 1.0);
+        assert_close(end.center, b.center);
+        assert!((end.scale - 4.0).abs() < 1e-9);
+        let half = Placement::interpolate(&a, &b, 0.5);
+        assert_close(half.center, Vertex::new(2.0, 0.0, 0.0));
+        // the geometric mean of the scales
+        assert!((half.scale - 2.0).abs() < 1e-9);
+        // an eighth turn
+        let v = half.rotation.to_matrix() * Vertex::new(1.0, 0.0, 0.0);
+        let s = std::f64::consts::FRAC_1_SQRT_2;
+        assert_close(v, Vertex::new(s, s, 0.0));
+    }
+
+    #[test]
+    fn world_interpolation_moves_between_inputs() {
+        let center1 = Vertex::new(5.0, 0.0, 0.0);
+        let center2 = Vertex::new(0.0, -3.0, 1.0);
+        let merged = merge(
+            moved(cube(), center1, 2.0),
+            moved(octahedron(), center2, 0.5),
+            Default::default(),
+        );
+        let [p1, p2] = merged.placements();
+        assert_close(p1.center, center1);
+        assert_close(p2.center, center2);
+
+        let bbox = |ratio| Vertex::bounding_box(&merged.world_interpolation(ratio).verts);
+        let (min, max) = bbox(0.0);
+        assert_close(min, center1 + Vertex::new(-2.0, -2.0, -2.0));
+        assert_close(max, center1 + Vertex::new(2.0, 2.0, 2.0));
+        let (min, max) = bbox(1.0);
+        assert_close(min, center2 + Vertex::new(-0.5, -0.5, -0.5));
+        assert_close(max, center2 + Vertex::new(0.5, 0.5, 0.5));
+        // halfway the model is centered between both inputs
+        let (min, max) = bbox(0.5);
+        assert_close((min + max) / 2.0, (center1 + center2) / 2.0);
+    }
+}
@@ -0,0 +1,423 @@
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::{self, prelude::*, BufReader};
+
+use crate::attribute::{Attribute, AttributePolicy, AttributeValues};
+use crate::geo::Vertex;
+use crate::Model;
+
+#[derive(Debug, Clone, Copy)]
+enum Format {
+    Ascii,
+    LittleEndian,
+    BigEndian,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Scalar {
+    Char,
+    UChar,
+    Short,
+    UShort,
+    Int,
+    UInt,
+    Float,
+    Double,
+}
+
+#[derive(Debug)]
+struct Property {
+    name: String,
+    scalar: Scalar,
+    list: Option<Scalar>, // type of the length of list properties
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+impl Scalar {
+    fn parse(s: &str) -> io::Result<Self> {
+        match s {
+            "char" | "int8" => Ok(Scalar::Char),
+            "uchar" | "uint8" => Ok(Scalar::UChar),
+            "short" | "int16" => Ok(Scalar::Short),
+            "ushort" | "uint16" => Ok(Scalar::UShort),
+            "int" | "int32" => Ok(Scalar::Int),
+            "uint" | "uint32" => Ok(Scalar::UInt),
+            "float" | "float32" => Ok(Scalar::Float),
+            "double" | "float64" => Ok(Scalar::Double),
+            _ => Err(invalid(format!("Invalid PLY type \"{}\"", s))),
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::Char | Scalar::UChar => 1,
+            Scalar::Short | Scalar::UShort => 2,
+            Scalar::Int | Scalar::UInt | Scalar::Float => 4,
+            Scalar::Double => 8,
+        }
+    }
+
+    /// Largest value of integer types, used to normalize colors.
+    fn max(self) -> Option<f64> {
+        match self {
+            Scalar::Char => Some(i8::MAX as f64),
+            Scalar::UChar => Some(u8::MAX as f64),
+            Scalar::Short => Some(i16::MAX as f64),
+            Scalar::UShort => Some(u16::MAX as f64),
+            Scalar::Int => Some(i32::MAX as f64),
+            Scalar::UInt => Some(u32::MAX as f64),
+            Scalar::Float | Scalar::Double => None,
+        }
+    }
+
+    fn decode(self, bytes: &[u8], format: Format) -> f64 {
+        macro_rules! decode {
+            ($t:ty) => {{
+                let b = bytes.try_into().unwrap();
+                (match format {
+                    Format::BigEndian => <$t>::from_be_bytes(b),
+                    _ => <$t>::from_le_bytes(b),
+                }) as f64
+            }};
+        }
+        match self {
+            Scalar::Char => decode!(i8),
+            Scalar::UChar => decode!(u8),
+            Scalar::Short => decode!(i16),
+            Scalar::UShort => decode!(u16),
+            Scalar::Int => decode!(i32),
+            Scalar::UInt => decode!(u32),
+            Scalar::Float => decode!(f32),
+            Scalar::Double => decode!(f64),
+        }
+    }
+}
+
+/// Reads the values of an element, ASCII tokens or binary scalars.
+struct Values<R> {
+    reader: R,
+    format: Format,
+    tokens: Vec<String>,
+}
+
+impl<R: BufRead> Values<R> {
+    fn next(&mut self, scalar: Scalar) -> io::Result<f64> {
+        match self.format {
+            Format::Ascii => {
+                while self.tokens.is_empty() {
+                    let mut line = String::new();
+                    if self.reader.read_line(&mut line)? == 0 {
+                        return Err(invalid("Unexpected end of PLY file".to_string()));
+                    }
+                    self.tokens = line.split_whitespace().rev().map(String::from).collect();
+                }
+                let token = self.tokens.pop().unwrap();
+                token
+                    .parse()
+                    .map_err(|_| invalid(format!("Invalid PLY value \"{}\"", token)))
+            }
+            format => {
+                let mut bytes = [0; 8];
+                let bytes = &mut bytes[..scalar.size()];
+                self.reader.read_exact(bytes)?;
+                Ok(scalar.decode(bytes, format))
+            }
+        }
+    }
+
+    fn read(&mut self, property: &Property) -> io::Result<Vec<f64>> {
+        match property.list {
+            Some(len) => {
+                let n = self.next(len)? as usize;
+                (0..n).map(|_| self.next(property.scalar)).collect()
+            }
+            None => Ok(vec![self.next(property.scalar)?]),
+        }
+    }
+}
+
+impl Model {
+    /// Loads a PLY file (ASCII or binary) with `x y z` and optionally
+    /// `red green blue` vertex properties. Other scalar vertex properties are
+    /// loaded as attributes, blended if they are floating point and nearest
+    /// otherwise. Polygons are split into fans of triangles.
+    pub fn load_ply(filename: &str) -> io::Result<Self> {
+        let file = File::open(filename)?;
+        let mut reader = BufReader::new(file);
+
+        let mut format = None;
+        let mut elements: Vec<Element> = Vec::new();
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 {
+                return Err(invalid("Missing end of PLY header".to_string()));
+            }
+            let vals = line.split_whitespace().collect::<Vec<_>>();
+            match vals.as_slice() {
+                ["format", "ascii", _] => format = Some(Format::Ascii),
+                ["format", "binary_little_endian", _] => format = Some(Format::LittleEndian),
+                ["format", "binary_big_endian", _] => format = Some(Format::BigEndian),
+                ["element", name, count] => elements.push(Element {
+                    name: name.to_string(),
+                    count: count
+                        .parse()
+                        .map_err(|_| invalid(format!("Invalid PLY count \"{}\"", count)))?,
+                    properties: Vec::new(),
+                }),
+                ["property", "list", len, scalar, name] => {
+                    let property = Property {
+                        name: name.to_string(),
+                        scalar: Scalar::parse(scalar)?,
+                        list: Some(Scalar::parse(len)?),
+                    };
+                    match elements.last_mut() {
+                        Some(e) => e.properties.push(property),
+                        None => return Err(invalid("PLY property before elements".to_string())),
+                    }
+                }
+                ["property", scalar, name] => {
+                    let property = Property {
+                        name: name.to_string(),
+                        scalar: Scalar::parse(scalar)?,
+                        list: None,
+                    };
+                    match elements.last_mut() {
+                        Some(e) => e.properties.push(property),
+                        None => return Err(invalid("PLY property before elements".to_string())),
+                    }
+                }
+                ["end_header"] => break,
+                _ => {}
+            }
+        }
+        let format = format.ok_or_else(|| invalid("Missing PLY format".to_string()))?;
+
+        let mut values = Values {
+            reader,
+            format,
+            tokens: Vec::new(),
+        };
+        let mut verts = Vec::new();
+        let mut colors = Vec::new();
+        let mut attributes = Vec::new();
+        let mut faces = Vec::new();
+        for element in &elements {
+            let find = |name: &str| element.properties.iter().position(|p| p.name == name);
+            let coords = [find("x"), find("y"), find("z")];
+            let rgb = [find("red"), find("green"), find("blue")];
+            let indices = find("vertex_indices").or_else(|| find("vertex_index"));
+            if element.name == "vertex" {
+                attributes = element
+                    .properties
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, p)| {
+                        p.list.is_none()
+                            && !["x", "y", "z", "red", "green", "blue"].contains(&p.name.as_str())
+                    })
+                    .map(|(i, p)| (i, p, Vec::new()))
+                    .collect::<Vec<_>>();
+            }
+            for _ in 0..element.count {
+                let row = element
+                    .properties
+                    .iter()
+                    .map(|p| values.read(p))
+                    .collect::<io::Result<Vec<_>>>()?;
+                match element.name.as_str() {
+                    "vertex" => {
+                        let get = |i: Option<usize>| i.map_or(0.0, |i| row[i][0]);
+                        verts.push(Vertex::new(get(coords[0]), get(coords[1]), get(coords[2])));
+                        if let [Some(r), Some(g), Some(b)] = rgb {
+                            let c = |i: usize| {
+                                let max = element.properties[i].scalar.max().unwrap_or(1.0);
+                                row[i][0] / max
+                            };
+                            colors.push(Vertex::new(c(r), c(g), c(b)));
+                        }
+                        for (i, _, values) in &mut attributes {
+                            values.push(row[*i][0]);
+                        }
+                    }
+                    "face" => {
+                        if let Some(i) = indices {
+                            let f = row[i]
+                                .iter()
+                                .map(|&x| {
+                                    if x < 0.0 || x.fract() != 0.0 {
+                                        Err(invalid(format!("Invalid PLY vertex index {}", x)))
+                                    } else {
+                                        Ok(x as usize)
+                                    }
+                                })
+                                .collect::<io::Result<Vec<_>>>()?;
+                            for k in 1..f.len().saturating_sub(1) {
+                                faces.push(vec![f[0], f[k], f[k + 1]]);
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        if let Some(i) = faces.iter().flatten().find(|&&i| i >= verts.len()) {
+            return Err(invalid(format!("PLY vertex index {} out of range", i)));
+        }
+
+        let mut model = Self::new(verts, faces);
+        if colors.len() == model.verts.len() {
+            model.colors = colors;
+        }
+        for (_, property, values) in attributes {
+            let (values, policy) = match property.scalar {
+                Scalar::Float => (
+                    AttributeValues::F32(values.iter().map(|&x| x as f32).collect()),
+                    AttributePolicy::Blend,
+                ),
+                Scalar::Double => (AttributeValues::F64(values), AttributePolicy::Blend),
+                _ => (AttributeValues::F64(values), AttributePolicy::Nearest),
+            };
+            model.set_attribute(&property.name, Attribute::new(1, values, policy));
+        }
+        Ok(model)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::temp_path;
+
+    const HEADER: &str = "element vertex 4\n\
+        property float x\n\
+        property float y\n\
+        property float z\n\
+        property uchar red\n\
+        property uchar green\n\
+        property uchar blue\n\
+        property float quality\n\
+        element face 1\n\
+        property list uchar int vertex_indices\n\
+        end_header\n";
+
+    const VERTS: [[f64; 3]; 4] = [
+        [0.0, 0.0, 0.0],
+        [1.0, 0.0, 0.0],
+        [1.0, 1.0, 0.0],
+        [0.0, 1.0, 0.0],
+    ];
+
+    fn load(name: &str, content: &[u8]) -> io::Result<Model> {
+        let path = temp_path(name);
+        std::fs::write(&path, content).unwrap();
+        let model = Model::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        model
+    }
+
+    fn ascii(face: &str) -> Vec<u8> {
+        format!(
+            "ply\nformat ascii 1.0\n{}\
+             0 0 0 255 0 0 0.5\n\
+             1 0 0 0 255 0 1.5\n\
+             1 1 0 0 0 255 2.5\n\
+             0 1 0 255 255 255 3.5\n\
+             {}\n",
+            HEADER, face
+        )
+        .into_bytes()
+    }
+
+    fn binary(format: &str, big_endian: bool, indices: &[i32]) -> Vec<u8> {
+        let mut content = format!("ply\nformat {} 1.0\n{}", format, HEADER).into_bytes();
+        for (k, v) in VERTS.iter().enumerate() {
+            for &x in v {
+                let x = x as f32;
+                content.extend(if big_endian {
+                    x.to_be_bytes()
+                } else {
+                    x.to_le_bytes()
+                });
+            }
+            content.extend([255, 0, 0]);
+            let quality = k as f32 + 0.5;
+            content.extend(if big_endian {
+                quality.to_be_bytes()
+            } else {
+                quality.to_le_bytes()
+            });
+        }
+        content.push(indices.len() as u8);
+        for i in indices {
+            content.extend(if big_endian {
+                i.to_be_bytes()
+            } else {
+                i.to_le_bytes()
+            });
+        }
+        content
+    }
+
+    fn assert_quad(model: &Model) {
+        let verts = VERTS.iter().map(|&v| v.into()).collect::<Vec<Vertex>>();
+        assert_eq!(model.verts, verts);
+        // split into a fan
+        assert_eq!(model.faces, [vec![0, 1, 2], vec![0, 2, 3]]);
+        assert_eq!(model.colors[0], Vertex::new(1.0, 0.0, 0.0));
+        let quality = model.attribute("quality").unwrap();
+        assert_eq!(quality.policy(), AttributePolicy::Blend);
+        assert_eq!(quality.get(3), [3.5]);
+    }
+
+    #[test]
+    fn loads_ascii() {
+        let model = load("ascii.ply", &ascii("4 0 1 2 3")).unwrap();
+        assert_quad(&model);
+        assert_eq!(model.colors[3], Vertex::new(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn loads_binary() {
+        let indices = [0, 1, 2, 3];
+        let little = binary("binary_little_endian", false, &indices);
+        assert_quad(&load("little.ply", &little).unwrap());
+        let big = binary("binary_big_endian", true, &indices);
+        assert_quad(&load("big.ply", &big).unwrap());
+    }
+
+    #[test]
+    fn rejects_invalid_indices() {
+        for face in ["3 0 1 -1", "3 0 1 4", "3 0 1 1.5", "3 0 1 x", "4 0 1 2"] {
+            let err = load("invalid.ply", &ascii(face)).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{}", face);
+        }
+        let content = binary("binary_little_endian", false, &[0, -2, 3]);
+        let err = load("invalid.ply", &content).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rejects_invalid_headers() {
+        for header in [
+            "ply\nelement vertex 0\nend_header\n",
+            "ply\nformat ascii 1.0\nelement vertex x\nend_header\n",
+            "ply\nformat ascii 1.0\nproperty float x\nend_header\n",
+            "ply\nformat ascii 1.0\nelement vertex 0\nproperty quad x\nend_header\n",
+            "ply\nformat ascii 1.0\nelement vertex 0\n",
+        ] {
+            let err = load("header.ply", header.as_bytes()).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{}", header);
+        }
+    }
+}
@@ -0,0 +1,230 @@
+use std::fs::File;
+use std::io::{self, prelude::*, BufReader, BufWriter};
+
+use crate::MergedModel;
+
+/// Where a merged vertex comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum VertexSource {
+    Model1(usize), // index of the vertex on model 1
+    Model2(usize), // index of the vertex on model 2
+    Intersection,  // crossing of an arc of each model
+}
+
+/// A point on a source model, as barycentric coordinates on one of its faces.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SourceLocation {
+    pub face: usize,
+    pub barycentric: [f64; 3],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VertexProvenance {
+    pub source: VertexSource,
+    pub locations: [SourceLocation; 2], // on model 1 and model 2
+}
+
+/// Provenance of the merged vertices, and the face of each model containing
+/// every merged face.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Provenance {
+    pub vertices: Vec<VertexProvenance>,
+    pub faces: Vec<[usize; 2]>,
+}
+
+fn invalid(line: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Invalid provenance line \"{}\"", line),
+    )
+}
+
+impl Provenance {
+    pub fn is_empty(&self) -> bool {
+        self.vertices.is_empty() && self.faces.is_empty()
+    }
+
+    /// Saves as lines `v <source> <face1> <w1> <face2> <w2>` for the vertices,
+    /// the source being `1 <index>`, `2 <index>` or `x` and `w` the three
+    /// barycentric coordinates, followed by lines `f <face1> <face2>` for the
+    /// faces. Indices are 1-based as in OBJ files.
+    pub fn save(&self, filename: &str) -> io::Result<()> {
+        let file = File::create(filename)?;
+        let mut writer = BufWriter::new(file);
+
+        for v in &self.vertices {
+            let mut line = match v.source {
+                VertexSource::Model1(i) => format!("v 1 {}", i + 1),
+                VertexSource::Model2(i) => format!("v 2 {}", i + 1),
+                VertexSource::Intersection => "v x".to_string(),
+            };
+            for l in &v.locations {
+                let w = l.barycentric;
+                line += &format!(" {} {} {} {}", l.face + 1, w[0], w[1], w[2]);
+            }
+            writeln!(writer, "{}", line)?;
+        }
+        for f in &self.faces {
+            writeln!(writer, "f {} {}", f[0] + 1, f[1] + 1)?;
+        }
+        Ok(())
+    }
+
+    pub fn load(filename: &str) -> io::Result<Self> {
+        let file = File::open(filename)?;
+        let reader = BufReader::new(file);
+
+        let mut provenance = Self::default();
+        for line in reader.lines() {
+            let line = line?;
+            let vals = line.split_whitespace().collect::<Vec<_>>();
+            let index = |s: &str| match s.parse::<usize>() {
+                Ok(i) if i > 0 => Ok(i - 1),
+                _ => Err(invalid(&line)),
+            };
+            let value = |s: &str| s.parse::<f64>().map_err(|_| invalid(&line));
+            match vals.as_slice() {
+                ["v", rest @ ..] => {
+                    let (source, rest) = match rest {
+                        ["1", i, rest @ ..] => (VertexSource::Model1(index(i)?), rest),
+                        ["2", i, rest @ ..] => (VertexSource::Model2(index(i)?), rest),
+                        ["x", rest @ ..] => (VertexSource::Intersection, rest),
+                        _ => return Err(invalid(&line)),
+                    };
+                    if rest.len() != 8 {
+                        return Err(invalid(&line));
+                    }
+                    let location = |l: &[&str]| -> io::Result<SourceLocation> {
+                        Ok(SourceLocation {
+                            face: index(l[0])?,
+                            barycentric: [value(l[1])?, value(l[2])?, value(l[3])?],
+                        })
+                    };
+                    provenance.vertices.push(VertexProvenance {
+                        source,
+                        locations: [location(&rest[..4])?, location(&rest[4..])?],
+                    });
+                }
+                ["f", f1, f2] => provenance.faces.push([index(f1)?, index(f2)?]),
+                [] => {}
+                _ => return Err(invalid(&line)),
+            }
+        }
+        Ok(provenance)
+    }
+}
+
+impl MergedModel {
+    /// Provenance of the merged vertices and faces, empty for simplified,
+    /// subdivided or remeshed models.
+    pub fn provenance(&self) -> &Provenance {
+        &self.provenance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::label::FaceLabel;
+    use crate::testing::{cube, octahedron, temp_path};
+    use crate::{merge, Config, Model};
+
+    fn location(face: usize, barycentric: [f64; 3]) -> SourceLocation {
+        SourceLocation { face, barycentric }
+    }
+
+    /// The model with its faces alternating between two labels.
+    fn labeled(mut model: Model, name: &str) -> Model {
+        model.labels = (0..2)
+            .map(|i| FaceLabel {
+                group: Some(format!("{}{}", name, i)),
+                material: Some(format!("m{}", i)).filter(|_| i == 1),
+            })
+            .collect();
+        model.face_labels = (0..model.nr_faces()).map(|k| k % 2).collect();
+        model
+    }
+
+    #[test]
+    fn saves_and_loads() {
+        let provenance = Provenance {
+            vertices: vec![
+                VertexProvenance {
+                    source: VertexSource::Model1(3),
+                    locations: [location(0, [1.0, 0.0, 0.0]), location(5, [0.1, 0.2, 0.7])],
+                },
+                VertexProvenance {
+                    source: VertexSource::Intersection,
+                    locations: [location(2, [1.0 / 3.0; 3]), location(0, [0.0, 0.5, 0.5])],
+                },
+            ],
+            faces: vec![[0, 5], [2, 0]],
+        };
+        let path = temp_path("saved.prov");
+        provenance.save(&path).unwrap();
+        let loaded = Provenance::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), provenance);
+    }
+
+    #[test]
+    fn rejects_invalid_lines() {
+        let path = temp_path("invalid.prov");
+        for line in [
+            "v 3 1 1 1 0 0 1 1 0 0",
+            "v x 1 1 0 0",
+            "v x 0 1 0 0 1 1 0 0",
+            "f 1 x",
+            "e 1",
+        ] {
+            std::fs::write(&path, line).unwrap();
+            let err = Provenance::load(&path).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{}", line);
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn saved_faces_match_their_provenance() {
+        let merged = merge(
+            labeled(cube(), "c"),
+            labeled(octahedron(), "o"),
+            Config::default(),
+        );
+        let (model1, model2) = (labeled(cube(), "c"), labeled(octahedron(), "o"));
+        let provenance = merged.provenance();
+        assert_eq!(provenance.vertices.len(), merged.nr_verts());
+        assert_eq!(provenance.faces.len(), merged.nr_faces());
+
+        let path = temp_path("provenance.obj");
+        let prov_path = temp_path("provenance.prov");
+        merged.save(&path).unwrap();
+        let saved = Model::load(&path).unwrap();
+        let loaded = Provenance::load(&prov_path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&prov_path).unwrap();
+        assert_eq!(&loaded, provenance);
+        assert_eq!(saved.faces(), merged.faces());
+        for (k, &[f1, f2]) in loaded.faces.iter().enumerate() {
+            let (l1, l2) = (
+                model1.face_label(f1).unwrap(),
+                model2.face_label(f2).unwrap(),
+            );
+            let label = saved.face_label(k).unwrap();
+            let group = format!(
+                "{} {}",
+                l1.group.as_ref().unwrap(),
+                l2.group.as_ref().unwrap()
+            );
+            assert_eq!(label.group.as_ref(), Some(&group));
+            assert_eq!(
+                label.material,
+                l1.material.clone().or_else(|| l2.material.clone())
+            );
+        }
+    }
+}
@@ -0,0 +1,808 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::f64::consts::PI;
+
+use crate::attribute::Attribute;
+use crate::geo::{Face, Triangle, Vertex, EPS};
+use crate::locate::Adjacency;
+use crate::model::CornerValues;
+use crate::provenance::Provenance;
+use crate::stats::MergeStats;
+use crate::MergedModel;
+
+const FEATURE_ANGLE: f64 = PI / 6.0;
+const SMOOTH_FACTOR: f64 = 0.5;
+
+type VertPair = (Vertex, Vertex);
+
+fn edge_key(a: usize, b: usize) -> (usize, usize) {
+    (a.min(b), a.max(b))
+}
+
+fn pair_add(p: VertPair, q: VertPair) -> VertPair {
+    (p.0 + q.0, p.1 + q.1)
+}
+
+fn pair_mul(p: VertPair, k: f64) -> VertPair {
+    (p.0 * k, p.1 * k)
+}
+
+fn pair_len(p: VertPair, q: VertPair) -> f64 {
+    (Vertex::dist(p.0, q.0) + Vertex::dist(p.1, q.1)) / 2.0
+}
+
+/// Splits each face into four of the values of a per corner attribute, the
+/// new corners getting the average of the edge ends.
+fn subdivide_corners(values: &[VertPair], faces: &[Face]) -> (Vec<VertPair>, Vec<Face>) {
+    let mut new_values = values.to_vec();
+    let mut new_faces = Vec::with_capacity(faces.len() * 4);
+    let mut index = BTreeMap::new();
+    for t in faces {
+        let mut m = |i: usize| {
+            let (a, b) = edge_key(t[i], t[(i + 1) % 3]);
+            *index.entry((a, b)).or_insert_with(|| {
+                new_values.push(pair_mul(pair_add(values[a], values[b]), 0.5));
+                new_values.len() - 1
+            })
+        };
+        let (ab, bc, ca) = (m(0), m(1), m(2));
+        new_faces.push(vec![t[0], ab, ca]);
+        new_faces.push(vec![t[1], bc, ab]);
+        new_faces.push(vec![t[2], ca, bc]);
+        new_faces.push(vec![ab, bc, ca]);
+    }
+    (new_values, new_faces)
+}
+
+/// Locates directions in the sphere domain of a merged model, which is the
+/// central projection of the first surface.
+pub(crate) struct Locator<'a> {
+    model: &'a MergedModel,
+    dirs: Vec<Vertex>,
+    adjacency: Adjacency,
+}
+
+impl<'a> Locator<'a> {
+    pub(crate) fn new(model: &'a MergedModel) -> Self {
+        let dirs = model.vert_pairs.iter().map(|p| p.0.unit()).collect();
+        Self {
+            model,
+            dirs,
+            adjacency: Adjacency::new(&model.faces),
+        }
+    }
+
+    fn barycentric(&self, k: usize, dir: Vertex) -> Option<[f64; 3]> {
+        let f = &self.model.faces[k];
+        Triangle::new(self.dirs[f[0]], self.dirs[f[1]], self.dirs[f[2]]).central_barycentric(dir)
+    }
+
+    /// Walks from face `hint` to the face containing `dir`, falling back to
+    /// the first corner of `hint` if `dir` cannot be located on any face.
+    pub(crate) fn locate(&self, dir: Vertex, hint: usize) -> (usize, [f64; 3]) {
+        self.adjacency
+            .locate(hint, |k| self.barycentric(k, dir))
+            .unwrap_or((hint, [1.0, 0.0, 0.0]))
+    }
+
+    fn position(&self, k: usize, w: [f64; 3]) -> VertPair {
+        self.blend(&self.model.vert_pairs, k, w)
+    }
+
+    fn color(&self, k: usize, w: [f64; 3]) -> VertPair {
+        self.blend(&self.model.color_pairs, k, w)
+    }
+
+    fn attribute(&self, attribute: &Attribute, k: usize, w: [f64; 3]) -> Vec<f64> {
+        let f = &self.model.faces[k];
+        attribute.mix(&[(f[0], w[0]), (f[1], w[1]), (f[2], w[2])])
+    }
+
+    fn blend(&self, values: &[VertPair], k: usize, w: [f64; 3]) -> VertPair {
+        blend(values, &self.model.faces[k], w)
+    }
+
+    /// Adds the texture coordinates and normals of the corners of a new face
+    /// `face` with directions `dirs`, blended on the face `k` of the model
+    /// containing the new face, so that seams and creases are kept.
+    pub(crate) fn push_corners(
+        &self,
+        k: usize,
+        face: &[usize],
+        dirs: &[Vertex],
+        texcoords: &mut CornerValues,
+        normals: &mut CornerValues,
+    ) {
+        let model = self.model;
+        let corners = |values: &[VertPair], faces: &[Face]| {
+            face.iter()
+                .zip(dirs)
+                .map(|(&i, &dir)| {
+                    let w = self.barycentric(k, dir).unwrap_or([1.0, 0.0, 0.0]);
+                    let p = blend(values, &faces[k], w);
+                    (i, vec![p.0, p.1])
+                })
+                .collect::<Vec<_>>()
+        };
+        if !model.face_texcoords.is_empty() {
+            texcoords.push_face(corners(&model.texcoord_pairs, &model.face_texcoords));
+        }
+        if !model.face_normals.is_empty() {
+            let mut c = corners(&model.normal_pairs, &model.face_normals);
+            for n in c.iter_mut().flat_map(|(_, n)| n.iter_mut()) {
+                if n.len() > EPS {
+                    *n = n.unit();
+                }
+            }
+            normals.push_face(c);
+        }
+    }
+}
+
+fn blend(values: &[VertPair], f: &[usize], w: [f64; 3]) -> VertPair {
+    let mut p = pair_mul(values[f[0]], w[0]);
+    p = pair_add(p, pair_mul(values[f[1]], w[1]));
+    pair_add(p, pair_mul(values[f[2]], w[2]))
+}
+
+/// Values and faces of per corner attributes gathered by
+/// `Locator::push_corners`.
+pub(crate) fn corner_pairs(corners: CornerValues) -> (Vec<VertPair>, Vec<Face>) {
+    let values = corners.values.iter().map(|v| (v[0], v[1])).collect();
+    (values, corners.faces)
+}
+
+/// Isotropic remeshing in the sphere domain. Vertices are re-projected onto
+/// both input surfaces through the faces of the original merged model.
+struct Remesher<'a> {
+    locator: Locator<'a>,
+    dirs: Vec<Vertex>,
+    pairs: Vec<VertPair>,
+    hints: Vec<usize>,
+    locked: Vec<bool>,
+    faces: Vec<Option<[usize; 3]>>,
+    vert_faces: Vec<Vec<usize>>,
+    features: BTreeSet<(usize, usize)>,
+}
+
+impl<'a> Remesher<'a> {
+    fn new(model: &'a MergedModel) -> Self {
+        let locator = Locator::new(model);
+        let n = model.vert_pairs.len();
+        let mut hints = vec![0; n];
+        let mut vert_faces = vec![Vec::new(); n];
+        let mut faces = Vec::new();
+        for (k, f) in model.faces.iter().enumerate() {
+            for &i in f {
+                hints[i] = k;
+                vert_faces[i].push(k);
+            }
+            faces.push(Some([f[0], f[1], f[2]]));
+        }
+
+        // sharp edges on either surface are kept in place
+        let mut features = BTreeSet::new();
+        let mut locked = vec![false; n];
+        for (k, f) in model.faces.iter().enumerate() {
+            for i in 0..3 {
+                let (a, b) = (f[i], f[(i + 1) % 3]);
+                let other = locator.adjacency.across(k, (i + 2) % 3);
+                if other.is_none_or(|o| Self::dihedral(model, k, o) > FEATURE_ANGLE) {
+                    features.insert(edge_key(a, b));
+                    locked[a] = true;
+                    locked[b] = true;
+                }
+            }
+        }
+
+        Self {
+            dirs: locator.dirs.clone(),
+            pairs: model.vert_pairs.clone(),
+            locator,
+            hints,
+            locked,
+            faces,
+            vert_faces,
+            features,
+        }
+    }
+
+    fn dihedral(model: &MergedModel, k1: usize, k2: usize) -> f64 {
+        let norm = |k: usize, side: usize| {
+            let p = |i: usize| {
+                let pair = model.vert_pairs[model.faces[k][i]];
+                if side == 0 {
+                    pair.0
+                } else {
+                    pair.1
+                }
+            };
+            (p(1) - p(0)) * (p(2) - p(0))
+        };
+        (0..2)
+            .map(|side| {
+                let (n1, n2) = (norm(k1, side), norm(k2, side));
+                if n1.len() < EPS || n2.len() < EPS {
+                    0.0
+                } else {
+                    Vertex::angle(n1, n2)
+                }
+            })
+            .fold(0.0, f64::max)
+    }
+
+    fn add_vertex(&mut self, dir: Vertex, hint: usize, locked: bool) -> usize {
+        let (k, w) = self.locator.locate(dir, hint);
+        self.dirs.push(dir);
+        self.pairs.push(self.locator.position(k, w));
+        self.hints.push(k);
+        self.locked.push(locked);
+        self.vert_faces.push(Vec::new());
+        self.dirs.len() - 1
+    }
+
+    fn move_vertex(&mut self, i: usize, dir: Vertex) {
+        let (k, w) = self.locator.locate(dir, self.hints[i]);
+        self.dirs[i] = dir;
+        self.pairs[i] = self.locator.position(k, w);
+        self.hints[i] = k;
+    }
+
+    fn set_face(&mut self, k: usize, face: Option<[usize; 3]>) {
+        if let Some(old) = self.faces[k] {
+            for &i in &old {
+                self.vert_faces[i].retain(|&x| x != k);
+            }
+        }
+        if let Some(new) = face {
+            for &i in &new {
+                self.vert_faces[i].push(k);
+            }
+        }
+        self.faces[k] = face;
+    }
+
+    fn add_face(&mut self, face: [usize; 3]) {
+        self.faces.push(None);
+        self.set_face(self.faces.len() - 1, Some(face));
+    }
+
+    fn neighbors(&self, a: usize) -> Vec<usize> {
+        let mut res = Vec::new();
+        for &k in &self.vert_faces[a] {
+            for &i in &self.faces[k].unwrap() {
+                if i != a && !res.contains(&i) {
+                    res.push(i);
+                }
+            }
+        }
+        res
+    }
+
+    fn edge_faces(&self, a: usize, b: usize) -> Vec<usize> {
+        self.vert_faces[a]
+            .iter()
+            .copied()
+            .filter(|&k| self.faces[k].unwrap().contains(&b))
+            .collect()
+    }
+
+    fn edges(&self) -> Vec<(usize, usize)> {
+        let mut edges = BTreeSet::new();
+        for f in self.faces.iter().flatten() {
+            for i in 0..3 {
+                edges.insert(edge_key(f[i], f[(i + 1) % 3]));
+            }
+        }
+        edges.into_iter().collect()
+    }
+
+    fn is_ccw(&self, f: [usize; 3]) -> bool {
+        Vertex::det(self.dirs[f[0]], self.dirs[f[1]], self.dirs[f[2]]) > 0.0
+    }
+
+    /// Opposite vertices of an interior edge and the faces `(a, b, c)` and `(b, a, d)`.
+    fn diamond(&self, a: usize, b: usize) -> Option<(usize, usize, usize, usize)> {
+        let fs = self.edge_faces(a, b);
+        if fs.len() != 2 {
+            return None;
+        }
+        let (mut c, mut d) = (None, None);
+        for &k in &fs {
+            let f = self.faces[k].unwrap();
+            let i = (0..3).find(|&i| f[i] == a).unwrap();
+            let other = f[0] + f[1] + f[2] - a - b;
+            if f[(i + 1) % 3] == b {
+                c = Some((other, k));
+            } else {
+                d = Some((other, k));
+            }
+        }
+        match (c, d) {
+            (Some((c, k1)), Some((d, k2))) => Some((c, d, k1, k2)),
+            _ => None,
+        }
+    }
+
+    fn split_long_edges(&mut self, high: f64) {
+        let mut edges = self
+            .edges()
+            .into_iter()
+            .map(|(a, b)| (pair_len(self.pairs[a], self.pairs[b]), a, b))
+            .filter(|e| e.0 > high)
+            .collect::<Vec<_>>();
+        edges.sort_by(|x, y| y.0.partial_cmp(&x.0).unwrap());
+
+        for (_, a, b) in edges {
+            let fs = self.edge_faces(a, b);
+            if fs.is_empty() {
+                continue;
+            }
+            let feature = self.features.remove(&edge_key(a, b));
+            let m = self.add_vertex((self.dirs[a] + self.dirs[b]).unit(), self.hints[a], feature);
+            if feature {
+                self.features.insert(edge_key(a, m));
+                self.features.insert(edge_key(m, b));
+            }
+            for k in fs {
+                let f = self.faces[k].unwrap();
+                let i = (0..3)
+                    .find(|&i| edge_key(f[i], f[(i + 1) % 3]) == edge_key(a, b))
+                    .unwrap();
+                let (p, q, r) = (f[i], f[(i + 1) % 3], f[(i + 2) % 3]);
+                self.set_face(k, Some([p, m, r]));
+                self.add_face([m, q, r]);
+            }
+        }
+    }
+
+    fn collapse_short_edges(&mut self, low: f64, high: f64) {
+        let mut edges = self
+            .edges()
+            .into_iter()
+            .map(|(a, b)| (pair_len(self.pairs[a], self.pairs[b]), a, b))
+            .filter(|e| e.0 < low)
+            .collect::<Vec<_>>();
+        edges.sort_by(|x, y| x.0.partial_cmp(&y.0).unwrap());
+
+        for (_, a, b) in edges {
+            if self.locked[a] || self.locked[b] {
+                continue;
+            }
+            let (c, d, k1, k2) = match self.diamond(a, b) {
+                Some(x) => x,
+                None => continue,
+            };
+            let (na, nb) = (self.neighbors(a), self.neighbors(b));
+            if nb.iter().filter(|i| na.contains(i)).count() != 2
+                || self.neighbors(c).len() <= 3
+                || self.neighbors(d).len() <= 3
+            {
+                continue;
+            }
+
+            let dir = (self.dirs[a] + self.dirs[b]).unit();
+            let (k, w) = self.locator.locate(dir, self.hints[a]);
+            let p = self.locator.position(k, w);
+            if na
+                .iter()
+                .chain(&nb)
+                .any(|&i| pair_len(p, self.pairs[i]) > high)
+            {
+                continue;
+            }
+
+            let old_dir = self.dirs[a];
+            self.dirs[a] = dir;
+            let ok = self.vert_faces[a]
+                .iter()
+                .chain(&self.vert_faces[b])
+                .filter(|&&f| f != k1 && f != k2)
+                .all(|&f| {
+                    let mut face = self.faces[f].unwrap();
+                    for i in &mut face {
+                        if *i == b {
+                            *i = a;
+                        }
+                    }
+                    self.is_ccw(face)
+                });
+            if !ok {
+                self.dirs[a] = old_dir;
+                continue;
+            }
+
+            self.set_face(k1, None);
+            self.set_face(k2, None);
+            for f in self.vert_faces[b].clone() {
+                let mut face = self.faces[f].unwrap();
+                for i in &mut face {
+                    if *i == b {
+                        *i = a;
+                    }
+                }
+                self.set_face(f, Some(face));
+            }
+            self.pairs[a] = p;
+            self.hints[a] = k;
+        }
+    }
+
+    fn flip_edges(&mut self) {
+        for (a, b) in self.edges() {
+            if self.features.contains(&(a, b)) {
+                continue;
+            }
+            let (c, d, k1, k2) = match self.diamond(a, b) {
+                Some(x) => x,
+                None => continue,
+            };
+            if self.neighbors(c).contains(&d) {
+                continue;
+            }
+            let valence = |i: usize| self.neighbors(i).len() as i64;
+            let dev = |v: [i64; 4]| v.iter().map(|x| (x - 6).abs()).sum::<i64>();
+            let (va, vb, vc, vd) = (valence(a), valence(b), valence(c), valence(d));
+            let before = dev([va, vb, vc, vd]);
+            let after = dev([va - 1, vb - 1, vc + 1, vd + 1]);
+            let (f1, f2) = ([a, d, c], [d, b, c]);
+            if after < before && self.is_ccw(f1) && self.is_ccw(f2) {
+                self.set_face(k1, Some(f1));
+                self.set_face(k2, Some(f2));
+            }
+        }
+    }
+
+    fn smooth(&mut self) {
+        for i in 0..self.dirs.len() {
+            if self.locked[i] || self.vert_faces[i].is_empty() {
+                continue;
+            }
+            let neighbors = self.neighbors(i);
+            let mut center = Vertex::new(0.0, 0.0, 0.0);
+            for &j in &neighbors {
+                center += self.dirs[j];
+            }
+            let center = (center / neighbors.len() as f64).unit();
+            let old = self.dirs[i];
+            self.dirs[i] = (old + (center - old) * SMOOTH_FACTOR).unit();
+            if self.vert_faces[i]
+                .iter()
+                .all(|&k| self.is_ccw(self.faces[k].unwrap()))
+            {
+                let dir = self.dirs[i];
+                self.move_vertex(i, dir);
+            } else {
+                self.dirs[i] = old;
+            }
+        }
+    }
+
+    fn finish(self) -> MergedModel {
+        let model = self.locator.model;
+        let mut index_map = vec![usize::MAX; self.pairs.len()];
+        let mut vert_pairs = Vec::new();
+        let mut color_pairs = Vec::new();
+        let mut attribute_rows = vec![(Vec::new(), Vec::new()); model.attribute_pairs.len()];
+        let mut faces = Vec::new();
+        let mut face_labels = Vec::new();
+        let mut texcoords = CornerValues::default();
+        let mut normals = CornerValues::default();
+        for f in self.faces.iter().flatten() {
+            // the old face containing the center of the face
+            let dir = f
+                .iter()
+                .fold(Vertex::new(0.0, 0.0, 0.0), |acc, &i| acc + self.dirs[i]);
+            let (source, _) = self.locator.locate(dir.unit(), self.hints[f[0]]);
+            if !model.face_labels.is_empty() {
+                face_labels.push(model.face_labels[source]);
+            }
+            let mut face = Vec::with_capacity(3);
+            for &i in f {
+                if index_map[i] == usize::MAX {
+                    index_map[i] = vert_pairs.len();
+                    vert_pairs.push(self.pairs[i]);
+                    // colors and attributes are located like positions
+                    let k = self.hints[i];
+                    let w = self
+                        .locator
+                        .barycentric(k, self.dirs[i])
+                        .unwrap_or([1.0, 0.0, 0.0]);
+                    if !model.color_pairs.is_empty() {
+                        color_pairs.push(self.locator.color(k, w));
+                    }
+                    for ((a, b), rows) in model.attribute_pairs.values().zip(&mut attribute_rows) {
+                        rows.0.push(self.locator.attribute(a, k, w));
+                        rows.1.push(self.locator.attribute(b, k, w));
+                    }
+                }
+                face.push(index_map[i]);
+            }
+            let dirs = f.map(|i| self.dirs[i]);
+            self.locator
+                .push_corners(source, &face, &dirs, &mut texcoords, &mut normals);
+            faces.push(face);
+        }
+        let (texcoord_pairs, face_texcoords) = corner_pairs(texcoords);
+        let (normal_pairs, face_normals) = corner_pairs(normals);
+        let attribute_pairs = model
+            .attribute_pairs
+            .iter()
+            .zip(attribute_rows)
+            .map(|((name, (a, b)), rows)| {
+                (name.clone(), (a.with_rows(rows.0), b.with_rows(rows.1)))
+            })
+            .collect();
+        MergedModel {
+            faces,
+            vert_pairs,
+            placements: model.placements,
+            keep_coords: model.keep_coords,
+            texcoord_pairs,
+            face_texcoords,
+            color_pairs,
+            normal_pairs,
+            face_normals,
+            attribute_pairs,
+            labels: model.labels.clone(),
+            face_labels,
+            provenance: Provenance::default(),
+            stats: MergeStats::default(),
+        }
+    }
+}
+
+impl MergedModel {
+    /// Loop subdivision, applied `levels` times. New vertices are placed by the
+    /// Loop stencils in the sphere domain and re-projected onto both surfaces
+    /// through the faces of this model, so that every in-between shape is
+    /// refined consistently. Existing vertices are kept in place.
+    pub fn subdivide(&self, levels: usize) -> MergedModel {
+        let locator = Locator::new(self);
+        let mut dirs = locator.dirs.clone();
+        let mut hints = vec![0; self.vert_pairs.len()];
+        for (k, f) in self.faces.iter().enumerate() {
+            for &i in f {
+                hints[i] = k;
+            }
+        }
+        let mut model = MergedModel {
+            faces: self.faces.clone(),
+            vert_pairs: self.vert_pairs.clone(),
+            placements: self.placements,
+            keep_coords: self.keep_coords,
+            texcoord_pairs: self.texcoord_pairs.clone(),
+            face_texcoords: self.face_texcoords.clone(),
+            color_pairs: self.color_pairs.clone(),
+            normal_pairs: self.normal_pairs.clone(),
+            face_normals: self.face_normals.clone(),
+            attribute_pairs: self.attribute_pairs.clone(),
+            labels: self.labels.clone(),
+            face_labels: self.face_labels.clone(),
+            provenance: self.provenance.clone(),
+            stats: self.stats.clone(),
+        };
+        for _ in 0..levels {
+            model = model.loop_subdivide(&locator, &mut dirs, &mut hints);
+        }
+        model
+    }
+
+    /// One level of subdivision, `dirs` and `hints` being the directions of
+    /// the vertices in the sphere domain of `locator` and the faces to start
+    /// locating them from.
+    fn loop_subdivide(
+        &self,
+        locator: &Locator,
+        dirs: &mut Vec<Vertex>,
+        hints: &mut Vec<usize>,
+    ) -> MergedModel {
+        let n = self.vert_pairs.len();
+        let mut edges = BTreeMap::<(usize, usize), Vec<usize>>::new();
+        for f in &self.faces {
+            for i in 0..3 {
+                edges
+                    .entry(edge_key(f[i], f[(i + 1) % 3]))
+                    .or_default()
+                    .push(f[(i + 2) % 3]);
+            }
+        }
+
+        // odd vertices, with colors and attributes located like positions
+        let mut vert_pairs = self.vert_pairs.clone();
+        let mut color_pairs = self.color_pairs.clone();
+        let mut attribute_rows = vec![(Vec::new(), Vec::new()); self.attribute_pairs.len()];
+        let mut edge_index = BTreeMap::new();
+        for (&(a, b), opposite) in &edges {
+            let mid = dirs[a] + dirs[b];
+            let dir = if opposite.len() == 2 {
+                (mid * (3.0 / 8.0) + (dirs[opposite[0]] + dirs[opposite[1]]) * (1.0 / 8.0)).unit()
+            } else {
+                mid.unit()
+            };
+            let (k, w) = locator.locate(dir, hints[a]);
+            edge_index.insert((a, b), vert_pairs.len());
+            vert_pairs.push(locator.position(k, w));
+            dirs.push(dir);
+            hints.push(k);
+            if !self.color_pairs.is_empty() {
+                color_pairs.push(locator.color(k, w));
+            }
+            for ((a, b), rows) in locator
+                .model
+                .attribute_pairs
+                .values()
+                .zip(&mut attribute_rows)
+            {
+                rows.0.push(locator.attribute(a, k, w));
+                rows.1.push(locator.attribute(b, k, w));
+            }
+        }
+
+        let mut faces = Vec::new();
+        for f in &self.faces {
+            let m = |i: usize| edge_index[&edge_key(f[i], f[(i + 1) % 3])];
+            let (ab, bc, ca) = (m(0), m(1), m(2));
+            faces.push(vec![f[0], ab, ca]);
+            faces.push(vec![f[1], bc, ab]);
+            faces.push(vec![f[2], ca, bc]);
+            faces.push(vec![ab, bc, ca]);
+        }
+        let face_labels = self
+            .face_labels
+            .iter()
+            .flat_map(|&l| [l; 4])
+            .collect::<Vec<_>>();
+        let attribute_pairs = self
+            .attribute_pairs
+            .iter()
+            .zip(attribute_rows)
+            .map(|((name, (a, b)), rows)| {
+                let split = |a: &Attribute, odd: Vec<Vec<f64>>| {
+                    a.with_rows((0..n).map(|i| a.get(i)).chain(odd))
+                };
+                (name.clone(), (split(a, rows.0), split(b, rows.1)))
+            })
+            .collect();
+
+        // texture coordinates and normals are subdivided linearly
+        let (texcoord_pairs, face_texcoords) =
+            subdivide_corners(&self.texcoord_pairs, &self.face_texcoords);
+        let (mut normal_pairs, face_normals) =
+            subdivide_corners(&self.normal_pairs, &self.face_normals);
+        let unit = |n: Vertex| if n.len() > EPS { n.unit() } else { n };
+        for n in &mut normal_pairs[self.normal_pairs.len()..] {
+            *n = (unit(n.0), unit(n.1));
+        }
+        MergedModel {
+            faces,
+            vert_pairs,
+            placements: self.placements,
+            keep_coords: self.keep_coords,
+            texcoord_pairs,
+            face_texcoords,
+            color_pairs,
+            normal_pairs,
+            face_normals,
+            attribute_pairs,
+            labels: self.labels.clone(),
+            face_labels,
+            provenance: Provenance::default(),
+            stats: MergeStats::default(),
+        }
+    }
+
+    /// Isotropic remeshing towards the given edge length (averaged over both
+    /// surfaces). New and relaxed vertices are re-projected onto both input
+    /// surfaces through the correspondence, and sharp edges are preserved.
+    /// Provenance is not kept. Panics if `edge_length` is not positive.
+    pub fn remesh(&self, edge_length: f64, iterations: usize) -> MergedModel {
+        assert!(edge_length > 0.0, "Remeshing edge length must be positive");
+        let mut remesher = Remesher::new(self);
+        for _ in 0..iterations {
+            remesher.split_long_edges(edge_length * 4.0 / 3.0);
+            remesher.collapse_short_edges(edge_length * 4.0 / 5.0, edge_length * 4.0 / 3.0);
+            remesher.flip_edges();
+            remesher.smooth();
+        }
+        remesher.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{cube, octahedron};
+    use crate::{merge, Config};
+
+    fn merged() -> MergedModel {
+        merge(cube(), octahedron(), Config::default())
+    }
+
+    /// The largest coordinate and the largest sum of coordinates, which are
+    /// the same for all points of the cube and the octahedron surfaces.
+    fn cube_size(v: Vertex) -> f64 {
+        v.x.abs().max(v.y.abs()).max(v.z.abs())
+    }
+
+    fn octahedron_size(v: Vertex) -> f64 {
+        v.x.abs() + v.y.abs() + v.z.abs()
+    }
+
+    /// Checks that all vertices lie on the surfaces of the merged cube and
+    /// octahedron, and that every edge is shared by two faces.
+    fn check_surfaces(model: &MergedModel, merged: &MergedModel) {
+        let (s1, s2) = merged.vert_pairs()[0];
+        for &(p1, p2) in model.vert_pairs() {
+            assert!((cube_size(p1) - cube_size(s1)).abs() < 1e-6);
+            assert!((octahedron_size(p2) - octahedron_size(s2)).abs() < 1e-6);
+        }
+        let mut edges = BTreeMap::new();
+        for f in model.faces() {
+            for i in 0..3 {
+                *edges.entry((f[i], f[(i + 1) % 3])).or_insert(0) += 1;
+            }
+        }
+        assert!(edges
+            .iter()
+            .all(|(&(a, b), &n)| n == 1 && edges.get(&(b, a)) == Some(&1)));
+    }
+
+    #[test]
+    fn subdivision_splits_every_face() {
+        let merged = merged();
+        assert_eq!(merged.subdivide(0).faces(), merged.faces());
+
+        let nr_edges = merged.nr_faces() * 3 / 2;
+        let subdivided = merged.subdivide(1);
+        assert_eq!(subdivided.nr_faces(), merged.nr_faces() * 4);
+        assert_eq!(subdivided.nr_verts(), merged.nr_verts() + nr_edges);
+        assert_eq!(
+            &subdivided.vert_pairs()[..merged.nr_verts()],
+            merged.vert_pairs()
+        );
+        check_surfaces(&subdivided, &merged);
+
+        let subdivided = merged.subdivide(2);
+        assert_eq!(subdivided.nr_faces(), merged.nr_faces() * 16);
+        check_surfaces(&subdivided, &merged);
+    }
+
+    #[test]
+    fn remeshing_approaches_edge_length() {
+        let merged = merged();
+        let edge_length = 0.2;
+        let remeshed = merged.remesh(edge_length, 5);
+        check_surfaces(&remeshed, &merged);
+        let lengths = remeshed
+            .faces()
+            .iter()
+            .flat_map(|f| (0..3).map(move |i| (f[i], f[(i + 1) % 3])))
+            .map(|(a, b)| pair_len(remeshed.vert_pairs()[a], remeshed.vert_pairs()[b]))
+            .collect::<Vec<_>>();
+        let mean = lengths.iter().sum::<f64>() / lengths.len() as f64;
+        assert!((mean - edge_length).abs() < edge_length * 0.25);
+        assert!(remeshed.nr_faces() > merged.nr_faces());
+    }
+
+    #[test]
+    #[should_panic(expected = "must be positive")]
+    fn remeshing_rejects_non_positive_lengths() {
+        merged().remesh(0.0, 1);
+    }
+
+    #[test]
+    fn locator_finds_vertex_directions() {
+        let merged = merged();
+        let locator = Locator::new(&merged);
+        for (i, dir) in locator.dirs.iter().enumerate() {
+            let (k, w) = locator.locate(*dir, 0);
+            let corner = merged.faces()[k].iter().position(|&j| j == i);
+            match corner {
+                Some(c) => assert!((w[c] - 1.0).abs() < 1e-6),
+                None => assert!(w.iter().any(|&x| (x - 1.0).abs() < 1e-6)),
+            }
+            let p = locator.position(k, w);
+            assert!(Vertex::dist(p.0, merged.vert_pairs()[i].0) < 1e-6);
+        }
+    }
+}
@@ -0,0 +1,431 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::ops;
+
+use crate::attribute::Attribute;
+use crate::geo::{Matrix3, Vertex, EPS};
+use crate::model::CornerValues;
+use crate::provenance::Provenance;
+use crate::refine::{corner_pairs, Locator};
+use crate::stats::MergeStats;
+use crate::MergedModel;
+
+/// Error quadric `x^T A x + 2 b^T x + c` of a set of planes.
+#[derive(Debug, Clone, Copy)]
+struct Quadric {
+    a: Matrix3,
+    b: Vertex,
+    c: f64,
+}
+
+impl Quadric {
+    fn zero() -> Self {
+        Self {
+            a: Matrix3::zero(),
+            b: Vertex::new(0.0, 0.0, 0.0),
+            c: 0.0,
+        }
+    }
+
+    /// Quadric of the plane through `a`, `b`, `c`, weighted by the triangle area.
+    fn from_triangle(a: Vertex, b: Vertex, c: Vertex) -> Self {
+        let norm = (b - a) * (c - a);
+        let area = norm.len() / 2.0;
+        if area < EPS {
+            return Self::zero();
+        }
+        let n = norm.unit();
+        let d = -n.dot(a);
+        Self {
+            a: Matrix3::outer(n, n) * area,
+            b: n * (d * area),
+            c: d * d * area,
+        }
+    }
+
+    fn error(&self, v: Vertex) -> f64 {
+        (v.dot(self.a * v) + 2.0 * self.b.dot(v) + self.c).max(0.0)
+    }
+
+    /// The position minimizing the error, falling back to the best of the
+    /// endpoints and the midpoint if the quadric is singular.
+    fn optimal(&self, u: Vertex, v: Vertex) -> Vertex {
+        if let Some(inv) = self.a.inverse() {
+            let p = inv * -self.b;
+            if Vertex::dist(p, (u + v) / 2.0) < 2.0 * Vertex::dist(u, v) + EPS {
+                return p;
+            }
+        }
+        let mid = (u + v) / 2.0;
+        [u, v, mid]
+            .iter()
+            .copied()
+            .min_by(|&p, &q| {
+                self.error(p)
+                    .partial_cmp(&self.error(q))
+                    .unwrap_or(Ordering::Equal)
+            })
+            .unwrap()
+    }
+}
+
+impl ops::Add for Quadric {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self {
+            a: self.a + rhs.a,
+            b: self.b + rhs.b,
+            c: self.c + rhs.c,
+        }
+    }
+}
+
+#[derive(PartialEq)]
+struct Collapse {
+    cost: f64,
+    u: usize,
+    v: usize,
+    stamp: (usize, usize),
+}
+
+impl Eq for Collapse {}
+
+impl Ord for Collapse {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .cost
+            .partial_cmp(&self.cost)
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+struct Simplifier {
+    vert_pairs: Vec<(Vertex, Vertex)>,
+    faces: Vec<[usize; 3]>,
+    face_alive: Vec<bool>,
+    vert_faces: Vec<Vec<usize>>,
+    quadrics: Vec<(Quadric, Quadric)>,
+    stamps: Vec<usize>,
+    heap: BinaryHeap<Collapse>,
+    nr_faces: usize,
+}
+
+impl Simplifier {
+    fn new(model: &MergedModel) -> Self {
+        let n = model.vert_pairs.len();
+        let faces = model
+            .faces
+            .iter()
+            .map(|f| [f[0], f[1], f[2]])
+            .collect::<Vec<_>>();
+        let mut vert_faces = vec![Vec::new(); n];
+        let mut quadrics = vec![(Quadric::zero(), Quadric::zero()); n];
+        for (k, f) in faces.iter().enumerate() {
+            let p = |i: usize| model.vert_pairs[f[i]];
+            let q1 = Quadric::from_triangle(p(0).0, p(1).0, p(2).0);
+            let q2 = Quadric::from_triangle(p(0).1, p(1).1, p(2).1);
+            for &i in f {
+                vert_faces[i].push(k);
+                quadrics[i].0 = quadrics[i].0 + q1;
+                quadrics[i].1 = quadrics[i].1 + q2;
+            }
+        }
+        let nr_faces = faces.len();
+        let mut s = Self {
+            vert_pairs: model.vert_pairs.clone(),
+            face_alive: vec![true; nr_faces],
+            faces,
+            vert_faces,
+            quadrics,
+            stamps: vec![0; n],
+            heap: BinaryHeap::new(),
+            nr_faces,
+        };
+        for u in 0..n {
+            s.push_edges(u);
+        }
+        s
+    }
+
+    fn neighbors(&self, u: usize) -> Vec<usize> {
+        let mut res = Vec::new();
+        for &k in &self.vert_faces[u] {
+            for &w in &self.faces[k] {
+                if w != u && !res.contains(&w) {
+                    res.push(w);
+                }
+            }
+        }
+        res
+    }
+
+    fn target(&self, u: usize, v: usize) -> ((Vertex, Vertex), f64) {
+        let q1 = self.quadrics[u].0 + self.quadrics[v].0;
+        let q2 = self.quadrics[u].1 + self.quadrics[v].1;
+        let (pu, pv) = (self.vert_pairs[u], self.vert_pairs[v]);
+        let p = (q1.optimal(pu.0, pv.0), q2.optimal(pu.1, pv.1));
+        (p, q1.error(p.0) + q2.error(p.1))
+    }
+
+    fn push_edges(&mut self, u: usize) {
+        for v in self.neighbors(u) {
+            let (_, cost) = self.target(u, v);
+            self.heap.push(Collapse {
+                cost,
+                u,
+                v,
+                stamp: (self.stamps[u], self.stamps[v]),
+            });
+        }
+    }
+
+    /// Whether collapsing `v` into `u` at `p` keeps the mesh manifold and no
+    /// face flips on either surface.
+    fn can_collapse(&self, u: usize, v: usize, p: (Vertex, Vertex)) -> bool {
+        let shared = self.vert_faces[u]
+            .iter()
+            .filter(|k| self.vert_faces[v].contains(k))
+            .count();
+        let nu = self.neighbors(u);
+        let common = self.neighbors(v).iter().filter(|w| nu.contains(w)).count();
+        if common != shared {
+            return false;
+        }
+
+        for &k in self.vert_faces[u].iter().chain(&self.vert_faces[v]) {
+            let f = self.faces[k];
+            if f.contains(&u) && f.contains(&v) {
+                continue;
+            }
+            for side in 0..2 {
+                let get = |pair: (Vertex, Vertex)| if side == 0 { pair.0 } else { pair.1 };
+                let old = |i: usize| get(self.vert_pairs[f[i]]);
+                let new = |i: usize| {
+                    if f[i] == u || f[i] == v {
+                        get(p)
+                    } else {
+                        old(i)
+                    }
+                };
+                // faces must not flip, nor turn inwards as seen from the center
+                let n_old = (old(1) - old(0)) * (old(2) - old(0));
+                let n_new = (new(1) - new(0)) * (new(2) - new(0));
+                let c_old = old(0) + old(1) + old(2);
+                let c_new = new(0) + new(1) + new(2);
+                let outward = n_old.len() < EPS || n_old.dot(c_old) > 0.0;
+                if n_old.dot(n_new) < 0.0 || (outward && n_new.dot(c_new) <= 0.0) {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    fn collapse(&mut self, u: usize, v: usize, p: (Vertex, Vertex)) {
+        for k in self.vert_faces[v].clone() {
+            if self.faces[k].contains(&u) {
+                self.face_alive[k] = false;
+                self.nr_faces -= 1;
+                for &w in &self.faces[k] {
+                    self.vert_faces[w].retain(|&x| x != k);
+                }
+            } else {
+                for w in &mut self.faces[k] {
+                    if *w == v {
+                        *w = u;
+                    }
+                }
+                self.vert_faces[u].push(k);
+            }
+        }
+        self.vert_faces[v].clear();
+        self.vert_pairs[u] = p;
+        self.quadrics[u] = (
+            self.quadrics[u].0 + self.quadrics[v].0,
+            self.quadrics[u].1 + self.quadrics[v].1,
+        );
+        self.stamps[u] += 1;
+        self.stamps[v] += 1;
+        self.push_edges(u);
+    }
+
+    fn run(&mut self, target_faces: usize, max_error: f64) {
+        while self.nr_faces > target_faces {
+            let c = match self.heap.pop() {
+                Some(c) => c,
+                None => break,
+            };
+            if c.stamp != (self.stamps[c.u], self.stamps[c.v]) {
+                continue;
+            }
+            if c.cost > max_error {
+                break;
+            }
+            let (p, _) = self.target(c.u, c.v);
+            if self.can_collapse(c.u, c.v, p) {
+                self.collapse(c.u, c.v, p);
+            }
+        }
+    }
+
+    /// The simplified model, with the colors and attributes of the remaining
+    /// vertices of `model` and the labels of its remaining faces. Texture
+    /// coordinates and normals are taken from the face of `model` containing
+    /// each face.
+    fn finish(self, model: &MergedModel) -> MergedModel {
+        let locator = Locator::new(model);
+        let mut index_map = vec![usize::MAX; self.vert_pairs.len()];
+        let mut vert_pairs = Vec::new();
+        let mut color_pairs = Vec::new();
+        let mut faces = Vec::new();
+        let mut face_labels = Vec::new();
+        let mut texcoords = CornerValues::default();
+        let mut normals = CornerValues::default();
+        for (k, f) in self.faces.iter().enumerate() {
+            if !self.face_alive[k] {
+                continue;
+            }
+            if let Some(&l) = model.face_labels.get(k) {
+                face_labels.push(l);
+            }
+            let mut face = Vec::with_capacity(3);
+            for &i in f {
+                if index_map[i] == usize::MAX {
+                    index_map[i] = vert_pairs.len();
+                    vert_pairs.push(self.vert_pairs[i]);
+                    if let Some(&c) = model.color_pairs.get(i) {
+                        color_pairs.push(c);
+                    }
+                }
+                face.push(index_map[i]);
+            }
+            if !model.face_texcoords.is_empty() || !model.face_normals.is_empty() {
+                let dirs = f.map(|i| self.vert_pairs[i].0.unit());
+                let center = dirs
+                    .iter()
+                    .fold(Vertex::new(0.0, 0.0, 0.0), |acc, &d| acc + d);
+                let (source, _) = locator.locate(center.unit(), k);
+                locator.push_corners(source, &face, &dirs, &mut texcoords, &mut normals);
+            }
+            faces.push(face);
+        }
+        let (texcoord_pairs, face_texcoords) = corner_pairs(texcoords);
+        let (normal_pairs, face_normals) = corner_pairs(normals);
+        let mut kept = vec![0; vert_pairs.len()];
+        for (i, &j) in index_map.iter().enumerate() {
+            if j != usize::MAX {
+                kept[j] = i;
+            }
+        }
+        let attribute_pairs = model
+            .attribute_pairs
+            .iter()
+            .map(|(name, (a, b))| {
+                let select = |a: &Attribute| a.with_rows(kept.iter().map(|&i| a.get(i)));
+                (name.clone(), (select(a), select(b)))
+            })
+            .collect();
+        MergedModel {
+            faces,
+            vert_pairs,
+            placements: model.placements,
+            keep_coords: model.keep_coords,
+            texcoord_pairs,
+            face_texcoords,
+            color_pairs,
+            normal_pairs,
+            face_normals,
+            attribute_pairs,
+            labels: model.labels.clone(),
+            face_labels,
+            provenance: Provenance::default(),
+            stats: MergeStats::default(),
+        }
+    }
+}
+
+impl MergedModel {
+    /// Simplifies the merged model by quadric error edge collapses, measuring the
+    /// error on both surfaces at once so that they stay in correspondence. Stops
+    /// when at most `target_faces` faces are left or the next collapse would
+    /// exceed `max_error`. Provenance is not kept.
+    pub fn simplify(&self, target_faces: usize, max_error: f64) -> MergedModel {
+        let mut simplifier = Simplifier::new(self);
+        simplifier.run(target_faces, max_error);
+        simplifier.finish(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::sample;
+    use crate::{merge, Config, Face, MergedModel};
+
+    fn merged() -> MergedModel {
+        merge(sample("cube.obj"), sample("egg.obj"), Config::default())
+    }
+
+    /// Whether every edge of the faces is shared by exactly two faces, in
+    /// opposite directions.
+    fn is_closed(faces: &[Face]) -> bool {
+        let edges = faces
+            .iter()
+            .flat_map(|f| (0..3).map(move |i| (f[i], f[(i + 1) % 3])))
+            .collect::<Vec<_>>();
+        edges
+            .iter()
+            .all(|&(a, b)| edges.iter().filter(|&&e| e == (b, a)).count() == 1)
+    }
+
+    #[test]
+    fn reaches_target_faces() {
+        let merged = merged();
+        let target = merged.nr_faces() / 4;
+        let simplified = merged.simplify(target, f64::INFINITY);
+        assert!(simplified.nr_faces() <= target);
+        assert!(simplified.nr_faces() >= target - 2);
+        assert!(is_closed(simplified.faces()));
+        assert!(simplified
+            .faces()
+            .iter()
+            .flatten()
+            .all(|&i| i < simplified.nr_verts()));
+    }
+
+    /// Number of faces pointing towards the origin on either surface.
+    fn nr_inverted(model: &MergedModel) -> usize {
+        model
+            .triangle_pairs()
+            .flat_map(|(t1, t2)| [t1, t2])
+            .filter(|t| ((t[1] - t[0]) * (t[2] - t[0])).dot(t[0] + t[1] + t[2]) < 0.0)
+            .count()
+    }
+
+    #[test]
+    fn keeps_both_surfaces_oriented() {
+        // both models are convex and centered, so faces point away from the
+        // origin unless they were already inverted by the merge
+        let merged = merged();
+        for target in [200, 50] {
+            let simplified = merged.simplify(target, f64::INFINITY);
+            assert!(nr_inverted(&simplified) <= nr_inverted(&merged));
+        }
+    }
+
+    #[test]
+    fn stops_at_max_error() {
+        let merged = merged();
+        let fine = merged.simplify(0, 1e-6);
+        let coarse = merged.simplify(0, 1e-3);
+        assert!(fine.nr_faces() < merged.nr_faces());
+        assert!(coarse.nr_faces() < fine.nr_faces());
+        assert!(coarse.nr_faces() > 4);
+    }
+}
@@ -0,0 +1,344 @@
+use std::collections::VecDeque;
+
+use crate::geo::{Face, Vertex, EPS};
+
+const CG_MAX_ITERATIONS: usize = 1000;
+const MAX_COTANGENT: f64 = 1e3;
+
+/// Square sparse matrix stored by rows, acting on one vertex per row.
+#[derive(Debug, Clone)]
+pub(crate) struct SparseMatrix {
+    rows: Vec<Vec<(usize, f64)>>,
+}
+
+fn dot(a: &[Vertex], b: &[Vertex]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| x.dot(*y)).sum()
+}
+
+impl SparseMatrix {
+    pub fn new(n: usize) -> Self {
+        Self {
+            rows: vec![Vec::new(); n],
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Cotangent Laplacian `L = D - W` of a triangle mesh. Negative and huge
+    /// weights of badly shaped triangles are clamped so that `L` stays
+    /// positive semidefinite.
+    pub fn cotangent_laplacian(verts: &[Vertex], faces: &[Face]) -> Self {
+        let mut laplacian = Self::new(verts.len());
+        for f in faces.iter().filter(|f| f.len() == 3) {
+            for k in 0..3 {
+                let (a, i, j) = (f[k], f[(k + 1) % 3], f[(k + 2) % 3]);
+                let (u, v) = (verts[i] - verts[a], verts[j] - verts[a]);
+                let cot = u.dot(v) / (u * v).len().max(EPS);
+                laplacian.add_edge(i, j, cot.clamp(0.0, MAX_COTANGENT) / 2.0);
+            }
+        }
+        laplacian
+    }
+
+    pub fn add(&mut self, i: usize, j: usize, value: f64) {
+        match self.rows[i].iter_mut().find(|e| e.0 == j) {
+            Some(e) => e.1 += value,
+            None => self.rows[i].push((j, value)),
+        }
+    }
+
+    /// Adds `weight` to the graph Laplacian entries of the edge `ij`.
+    pub fn add_edge(&mut self, i: usize, j: usize, weight: f64) {
+        self.add(i, i, weight);
+        self.add(j, j, weight);
+        self.add(i, j, -weight);
+        self.add(j, i, -weight);
+    }
+
+    pub fn mul(&self, x: &[Vertex]) -> Vec<Vertex> {
+        self.rows
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .fold(Vertex::new(0.0, 0.0, 0.0), |acc, &(j, a)| acc + x[j] * a)
+            })
+            .collect()
+    }
+
+    pub fn diagonal(&self) -> Vec<f64> {
+        self.rows
+            .iter()
+            .enumerate()
+            .map(|(i, row)| row.iter().find(|e| e.0 == i).map_or(0.0, |e| e.1))
+            .collect()
+    }
+
+    /// Solves `A x = b` for a symmetric positive semidefinite `A` by Jacobi
+    /// preconditioned conjugate gradients, starting from `x`.
+    pub fn solve_cg(&self, b: &[Vertex], mut x: Vec<Vertex>) -> Vec<Vertex> {
+        let inv_diag = self
+            .diagonal()
+            .iter()
+            .map(|&d| if d.abs() > EPS { 1.0 / d } else { 1.0 })
+            .collect::<Vec<_>>();
+        let precondition = |r: &[Vertex]| {
+            r.iter()
+                .zip(&inv_diag)
+                .map(|(&v, &d)| v * d)
+                .collect::<Vec<_>>()
+        };
+
+        let ax = self.mul(&x);
+        let mut r = b.iter().zip(&ax).map(|(&b, &a)| b - a).collect::<Vec<_>>();
+        let mut z = precondition(&r);
+        let mut p = z.clone();
+        let mut rz = dot(&r, &z);
+        let tolerance = dot(b, b).max(EPS) * EPS * EPS;
+        for _ in 0..CG_MAX_ITERATIONS {
+            if dot(&r, &r) < tolerance {
+                break;
+            }
+            let ap = self.mul(&p);
+            let pap = dot(&p, &ap);
+            if pap.abs() < EPS * EPS {
+                break;
+            }
+            let alpha = rz / pap;
+            for i in 0..x.len() {
+                x[i] += p[i] * alpha;
+                r[i] -= ap[i] * alpha;
+            }
+            z = precondition(&r);
+            let rz_new = dot(&r, &z);
+            let beta = rz_new / rz;
+            rz = rz_new;
+            for i in 0..p.len() {
+                p[i] = z[i] + p[i] * beta;
+            }
+        }
+        x
+    }
+
+    /// Fill-reducing reverse Cuthill-McKee ordering of the rows.
+    fn rcm_order(&self) -> Vec<usize> {
+        let n = self.len();
+        let degree = |i: usize| self.rows[i].len();
+        let mut starts = (0..n).collect::<Vec<_>>();
+        starts.sort_by_key(|&i| degree(i));
+        let mut visited = vec![false; n];
+        let mut order = Vec::with_capacity(n);
+        for s in starts {
+            if visited[s] {
+                continue;
+            }
+            visited[s] = true;
+            let mut queue = VecDeque::new();
+            queue.push_back(s);
+            while let Some(i) = queue.pop_front() {
+                order.push(i);
+                let mut next = self.rows[i]
+                    .iter()
+                    .map(|e| e.0)
+                    .filter(|&j| !visited[j])
+                    .collect::<Vec<_>>();
+                next.sort_by_key(|&j| degree(j));
+                next.dedup();
+                for j in next {
+                    visited[j] = true;
+                    queue.push_back(j);
+                }
+            }
+        }
+        order.reverse();
+        order
+    }
+
+    /// Cholesky factorization of a symmetric positive definite matrix, `None`
+    /// if it is not positive definite.
+    pub fn cholesky(&self) -> Option<Cholesky> {
+        let n = self.len();
+        let order = self.rcm_order();
+        let mut position = vec![0; n];
+        for (k, &i) in order.iter().enumerate() {
+            position[i] = k;
+        }
+
+        // rows of the lower triangle, from the first nonzero to the diagonal
+        let mut first = (0..n).collect::<Vec<_>>();
+        for (i, row) in self.rows.iter().enumerate() {
+            for &(j, _) in row {
+                let (pi, pj) = (position[i], position[j]);
+                first[pi] = first[pi].min(pj);
+            }
+        }
+        let mut offset = Vec::with_capacity(n + 1);
+        offset.push(0);
+        for k in 0..n {
+            offset.push(offset[k] + k - first[k] + 1);
+        }
+        let mut values = vec![0.0; offset[n]];
+        for (i, row) in self.rows.iter().enumerate() {
+            for &(j, a) in row {
+                let (pi, pj) = (position[i], position[j]);
+                if pj <= pi {
+                    values[offset[pi] + pj - first[pi]] = a;
+                }
+            }
+        }
+
+        for i in 0..n {
+            // pivots tiny compared to the diagonal are rounding errors of zeros
+            let min_pivot = (values[offset[i] + i - first[i]] * EPS).max(EPS * EPS);
+            for j in first[i]..=i {
+                let start = first[i].max(first[j]);
+                let mut s = values[offset[i] + j - first[i]];
+                for k in start..j {
+                    s -= values[offset[i] + k - first[i]] * values[offset[j] + k - first[j]];
+                }
+                if j < i {
+                    values[offset[i] + j - first[i]] = s / values[offset[j] + j - first[j]];
+                } else if s > min_pivot {
+                    values[offset[i] + i - first[i]] = s.sqrt();
+                } else {
+                    return None;
+                }
+            }
+        }
+        Some(Cholesky {
+            order,
+            first,
+            offset,
+            values,
+        })
+    }
+}
+
+/// Factor `P A P^T = L L^T` with `L` stored by rows within its envelope.
+pub(crate) struct Cholesky {
+    order: Vec<usize>,
+    first: Vec<usize>,
+    offset: Vec<usize>,
+    values: Vec<f64>,
+}
+
+impl Cholesky {
+    fn get(&self, i: usize, j: usize) -> f64 {
+        self.values[self.offset[i] + j - self.first[i]]
+    }
+
+    pub fn solve(&self, b: &[Vertex]) -> Vec<Vertex> {
+        let n = self.order.len();
+        let mut y = self.order.iter().map(|&i| b[i]).collect::<Vec<_>>();
+        for i in 0..n {
+            for k in self.first[i]..i {
+                let l = self.get(i, k);
+                y[i] = y[i] - y[k] * l;
+            }
+            y[i] /= self.get(i, i);
+        }
+        for i in (0..n).rev() {
+            y[i] /= self.get(i, i);
+            for k in self.first[i]..i {
+                let l = self.get(i, k);
+                y[k] = y[k] - y[i] * l;
+            }
+        }
+        let mut x = vec![Vertex::new(0.0, 0.0, 0.0); n];
+        for (k, &i) in self.order.iter().enumerate() {
+            x[i] = y[k];
+        }
+        x
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Laplacian of a path of `n` vertices plus the identity, which is
+    /// positive definite.
+    fn path(n: usize) -> SparseMatrix {
+        let mut a = SparseMatrix::new(n);
+        for i in 0..n {
+            a.add(i, i, 1.0);
+            if i + 1 < n {
+                a.add_edge(i, i + 1, 1.0 + i as f64);
+            }
+        }
+        a
+    }
+
+    fn rhs(n: usize) -> Vec<Vertex> {
+        (0..n)
+            .map(|i| Vertex::new(i as f64, 1.0, (i * i) as f64 / 10.0))
+            .collect()
+    }
+
+    fn assert_close(a: &[Vertex], b: &[Vertex]) {
+        assert_eq!(a.len(), b.len());
+        for (x, y) in a.iter().zip(b) {
+            assert!(
+                Vertex::dist(*x, *y) < 1e-6 * y.len().max(1.0),
+                "{:?} {:?}",
+                x,
+                y
+            );
+        }
+    }
+
+    #[test]
+    fn conjugate_gradients_solve() {
+        let a = path(20);
+        let b = rhs(20);
+        let x = a.solve_cg(&b, vec![Vertex::new(0.0, 0.0, 0.0); 20]);
+        assert_close(&a.mul(&x), &b);
+        // starting from the solution
+        assert_close(&a.solve_cg(&b, x.clone()), &x);
+    }
+
+    #[test]
+    fn edges_add_laplacian_entries() {
+        let mut a = SparseMatrix::new(3);
+        a.add_edge(0, 1, 2.0);
+        a.add_edge(1, 2, 1.0);
+        assert_eq!(a.diagonal(), [2.0, 3.0, 1.0]);
+        let ones = vec![Vertex::new(1.0, 1.0, 1.0); 3];
+        assert_close(&a.mul(&ones), &[Vertex::new(0.0, 0.0, 0.0); 3]);
+    }
+
+    #[test]
+    fn cholesky_agrees_with_conjugate_gradients() {
+        let model = crate::testing::sample("egg.obj");
+        let mut laplacian = SparseMatrix::cotangent_laplacian(&model.verts, &model.faces);
+        for i in 0..laplacian.len() {
+            laplacian.add(i, i, 0.1);
+        }
+        let b = rhs(laplacian.len());
+        let cg = laplacian.solve_cg(&b, vec![Vertex::new(0.0, 0.0, 0.0); laplacian.len()]);
+        let cholesky = laplacian.cholesky().unwrap().solve(&b);
+        assert_close(&cholesky, &cg);
+        assert_close(&laplacian.mul(&cholesky), &b);
+
+        let a = path(30);
+        let b = rhs(30);
+        assert_close(&a.cholesky().unwrap().solve(&b), &a.solve_cg(&b, b.clone()));
+    }
+
+    #[test]
+    fn cholesky_fails_on_singular_matrices() {
+        let model = crate::testing::cube();
+        let laplacian = SparseMatrix::cotangent_laplacian(&model.verts, &model.faces);
+        assert!(laplacian.cholesky().is_none());
+    }
+
+    #[test]
+    fn rcm_order_is_a_permutation() {
+        let model = crate::testing::sample("egg.obj");
+        let laplacian = SparseMatrix::cotangent_laplacian(&model.verts, &model.faces);
+        let mut order = laplacian.rcm_order();
+        order.sort_unstable();
+        assert_eq!(order, (0..laplacian.len()).collect::<Vec<_>>());
+    }
+}
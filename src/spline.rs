@@ -0,0 +1,175 @@
+use std::str::FromStr;
+
+/// Curve through a sequence of keyshapes at uniform parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Spline {
+    Linear,
+    CatmullRom, // C1, each segment only depends on its four nearest keyshapes
+    BSpline,    // C2, interpolating cubic B-spline with natural end conditions
+}
+
+impl Spline {
+    /// Weights of `n` keyshapes for the point at `t` in `[0, n - 1]`.
+    pub fn weights(&self, n: usize, t: f64) -> Vec<f64> {
+        let mut w = vec![0.0; n];
+        if n == 1 {
+            w[0] = 1.0;
+        }
+        if n <= 1 {
+            return w;
+        }
+        let i = (t.floor().max(0.0) as usize).min(n - 2);
+        let u = t - i as f64;
+        match self {
+            Spline::Linear => {
+                w[i] += 1.0 - u;
+                w[i + 1] += u;
+            }
+            Spline::CatmullRom => {
+                let (u2, u3) = (u * u, u * u * u);
+                let coefs = [
+                    (-u3 + 2.0 * u2 - u) / 2.0,
+                    (3.0 * u3 - 5.0 * u2 + 2.0) / 2.0,
+                    (-3.0 * u3 + 4.0 * u2 + u) / 2.0,
+                    (u3 - u2) / 2.0,
+                ];
+                for (k, c) in coefs.iter().enumerate() {
+                    // repeat the end keyshapes
+                    let j = (i + k).max(1).min(n) - 1;
+                    w[j] += c;
+                }
+            }
+            Spline::BSpline => {
+                for (k, wk) in w.iter_mut().enumerate() {
+                    let mut y = vec![0.0; n];
+                    y[k] = 1.0;
+                    let m = second_derivatives(&y);
+                    let v = 1.0 - u;
+                    *wk = v * y[i]
+                        + u * y[i + 1]
+                        + ((v * v * v - v) * m[i] + (u * u * u - u) * m[i + 1]) / 6.0;
+                }
+            }
+        }
+        w
+    }
+}
+
+/// Second derivatives of the natural cubic spline through `y` at unit spacing.
+fn second_derivatives(y: &[f64]) -> Vec<f64> {
+    let n = y.len();
+    let mut m = vec![0.0; n];
+    if n < 3 {
+        return m;
+    }
+    // tridiagonal system `m[i - 1] + 4 m[i] + m[i + 1] = 6 (y[i + 1] - 2 y[i] + y[i - 1])`
+    let mut diag = vec![4.0; n];
+    let mut rhs = (0..n)
+        .map(|i| {
+            if i == 0 || i == n - 1 {
+                0.0
+            } else {
+                6.0 * (y[i + 1] - 2.0 * y[i] + y[i - 1])
+            }
+        })
+        .collect::<Vec<_>>();
+    for i in 2..n - 1 {
+        let f = 1.0 / diag[i - 1];
+        diag[i] -= f;
+        rhs[i] -= f * rhs[i - 1];
+    }
+    for i in (1..n - 1).rev() {
+        m[i] = (rhs[i] - m[i + 1]) / diag[i];
+    }
+    m
+}
+
+impl FromStr for Spline {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "linear" => Ok(Spline::Linear),
+            "catmull-rom" => Ok(Spline::CatmullRom),
+            "b-spline" => Ok(Spline::BSpline),
+            _ => Err(format!("Invalid spline \"{}\"", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPLINES: [Spline; 3] = [Spline::Linear, Spline::CatmullRom, Spline::BSpline];
+
+    #[test]
+    fn weights_sum_to_one() {
+        for spline in SPLINES {
+            for n in 1..6 {
+                for k in 0..=40 {
+                    let t = k as f64 / 40.0 * (n - 1) as f64;
+                    let sum = spline.weights(n, t).iter().sum::<f64>();
+                    assert!((sum - 1.0).abs() < 1e-9, "{:?} {} {}", spline, n, t);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn weights_interpolate_keys() {
+        for spline in SPLINES {
+            for n in 1..6 {
+                for i in 0..n {
+                    let w = spline.weights(n, i as f64);
+                    for (j, &wj) in w.iter().enumerate() {
+                        let expected = if i == j { 1.0 } else { 0.0 };
+                        assert!((wj - expected).abs() < 1e-9, "{:?} {} {}", spline, n, i);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn weights_reproduce_lines() {
+        // keys on a line give points on the line, away from the repeated ends
+        // of Catmull-Rom
+        for spline in SPLINES {
+            let n = 5;
+            let (start, end) = match spline {
+                Spline::CatmullRom => (1.0, 3.0),
+                _ => (0.0, 4.0),
+            };
+            for k in 0..=20 {
+                let t = start + (end - start) * k as f64 / 20.0;
+                let w = spline.weights(n, t);
+                let p = w.iter().enumerate().map(|(i, w)| i as f64 * w).sum::<f64>();
+                assert!((p - t).abs() < 1e-9, "{:?} {}", spline, t);
+            }
+        }
+        assert_eq!(Spline::Linear.weights(3, 1.25), [0.0, 0.75, 0.25]);
+    }
+
+    #[test]
+    fn b_spline_is_smooth_at_keys() {
+        // second differences of the curve through 0, 1, 0, 2, 1 on both sides
+        // of key 2
+        let keys = [0.0, 1.0, 0.0, 2.0, 1.0];
+        let curve = |t: f64| {
+            let w = Spline::BSpline.weights(keys.len(), t);
+            w.iter().zip(&keys).map(|(w, y)| w * y).sum::<f64>()
+        };
+        let h = 1e-3;
+        let second = |t: f64| (curve(t + h) - 2.0 * curve(t) + curve(t - h)) / (h * h);
+        assert!((second(2.0 - 2.0 * h) - second(2.0 + 2.0 * h)).abs() < 0.1);
+    }
+
+    #[test]
+    fn parsing() {
+        assert_eq!("linear".parse(), Ok(Spline::Linear));
+        assert_eq!("catmull-rom".parse(), Ok(Spline::CatmullRom));
+        assert_eq!("b-spline".parse(), Ok(Spline::BSpline));
+        assert!("bezier".parse::<Spline>().is_err());
+    }
+}
@@ -0,0 +1,164 @@
+use std::fmt;
+use std::time::Duration;
+
+use crate::merger::Stage;
+use crate::{MergedModel, MultiMergedModel};
+
+/// Number of arc pairs of each kind of `ArcIntersectionResult` met while
+/// overlaying the models.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IntersectionCounts {
+    pub i: usize,  // co-planar arcs
+    pub t1: usize, // endpoint of an existing arc on the new arc
+    pub t2: usize, // endpoint of the new arc on an existing arc
+    pub l: usize,  // shared endpoints
+    pub x: usize,  // crossing arcs, adding a vertex
+    pub n: usize,  // no intersection
+    pub s: usize,  // same arcs
+}
+
+impl IntersectionCounts {
+    fn kinds(&self) -> [(&'static str, usize); 7] {
+        [
+            ("I", self.i),
+            ("T1", self.t1),
+            ("T2", self.t2),
+            ("L", self.l),
+            ("X", self.x),
+            ("N", self.n),
+            ("S", self.s),
+        ]
+    }
+}
+
+/// Statistics of a merge.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MergeStats {
+    pub input_verts: Vec<usize>, // of each model
+    pub input_faces: Vec<usize>,
+    pub output_verts: usize,
+    pub output_faces: usize,
+    pub intersections: IntersectionCounts,
+    pub timings: Vec<(Stage, Duration)>,
+    pub snapped_verts: usize,
+    pub dropped_faces: usize, // traced faces without area and duplicate triangles
+}
+
+impl MergeStats {
+    pub fn to_json(&self) -> String {
+        let list = |v: &[usize]| {
+            v.iter()
+                .map(|x| x.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let intersections = self
+            .intersections
+            .kinds()
+            .iter()
+            .map(|(k, n)| format!("\"{}\": {}", k, n))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let timings = self
+            .timings
+            .iter()
+            .map(|(s, t)| format!("\"{}\": {}", s, t.as_secs_f64()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!(
+            "{{\"input_verts\": [{}], \"input_faces\": [{}], \"output_verts\": {}, \
+             \"output_faces\": {}, \"intersections\": {{{}}}, \"timings\": {{{}}}, \
+             \"snapped_verts\": {}, \"dropped_faces\": {}}}",
+            list(&self.input_verts),
+            list(&self.input_faces),
+            self.output_verts,
+            self.output_faces,
+            intersections,
+            timings,
+            self.snapped_verts,
+            self.dropped_faces
+        )
+    }
+}
+
+/// A table of two columns.
+impl fmt::Display for MergeStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut rows = Vec::new();
+        for (k, (v, n)) in self.input_verts.iter().zip(&self.input_faces).enumerate() {
+            rows.push((
+                format!("model {}", k + 1),
+                format!("{} verts, {} faces", v, n),
+            ));
+        }
+        rows.push((
+            "merged".to_string(),
+            format!("{} verts, {} faces", self.output_verts, self.output_faces),
+        ));
+        for (k, n) in self.intersections.kinds().iter() {
+            rows.push((format!("intersections {}", k), n.to_string()));
+        }
+        for (s, t) in &self.timings {
+            rows.push((format!("time {}", s), format!("{:.3} s", t.as_secs_f64())));
+        }
+        rows.push(("snapped verts".to_string(), self.snapped_verts.to_string()));
+        rows.push(("dropped faces".to_string(), self.dropped_faces.to_string()));
+
+        let width = rows.iter().map(|r| r.0.len()).max().unwrap_or(0);
+        for (name, value) in rows {
+            writeln!(f, "{:width$}  {}", name, value, width = width)?;
+        }
+        Ok(())
+    }
+}
+
+impl MergedModel {
+    /// Statistics of the merge, empty for simplified, subdivided, remeshed or
+    /// loaded models.
+    pub fn stats(&self) -> &MergeStats {
+        &self.stats
+    }
+}
+
+impl MultiMergedModel {
+    pub fn stats(&self) -> &MergeStats {
+        &self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_parses() {
+        let stats = MergeStats {
+            input_verts: vec![8, 42],
+            input_faces: vec![12, 80],
+            output_verts: 120,
+            output_faces: 236,
+            intersections: IntersectionCounts {
+                x: 30,
+                n: 900,
+                ..Default::default()
+            },
+            timings: vec![
+                (Stage::Projection, Duration::from_millis(5)),
+                (Stage::BackProjection, Duration::from_micros(1500)),
+            ],
+            snapped_verts: 2,
+            dropped_faces: 1,
+        };
+        let json: serde_json::Value = serde_json::from_str(&stats.to_json()).unwrap();
+        assert_eq!(json["input_verts"], serde_json::json!([8, 42]));
+        assert_eq!(json["output_faces"], 236);
+        assert_eq!(json["intersections"]["X"], 30);
+        assert_eq!(json["timings"]["back projection"], 0.0015);
+        assert_eq!(json["dropped_faces"], 1);
+
+        let empty = MergeStats::default().to_json();
+        assert!(serde_json::from_str::<serde_json::Value>(&empty).is_ok());
+    }
+}
@@ -0,0 +1,67 @@
+//! Models shared by the tests.
+
+use crate::Model;
+
+/// Cube of side 2 centered at the origin, with outward facing triangles.
+pub(crate) fn cube() -> Model {
+    let verts = [
+        [-1.0, -1.0, -1.0],
+        [1.0, -1.0, -1.0],
+        [1.0, 1.0, -1.0],
+        [-1.0, 1.0, -1.0],
+        [-1.0, -1.0, 1.0],
+        [1.0, -1.0, 1.0],
+        [1.0, 1.0, 1.0],
+        [-1.0, 1.0, 1.0],
+    ];
+    let faces = [
+        [0, 2, 1],
+        [0, 3, 2],
+        [4, 5, 6],
+        [4, 6, 7],
+        [0, 1, 5],
+        [0, 5, 4],
+        [1, 2, 6],
+        [1, 6, 5],
+        [2, 3, 7],
+        [2, 7, 6],
+        [3, 0, 4],
+        [3, 4, 7],
+    ];
+    Model::from_slices(&verts, &faces)
+}
+
+/// Octahedron with unit vertices on the axes, with outward facing triangles.
+pub(crate) fn octahedron() -> Model {
+    let verts = [
+        [1.0, 0.0, 0.0],
+        [-1.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0],
+        [0.0, -1.0, 0.0],
+        [0.0, 0.0, 1.0],
+        [0.0, 0.0, -1.0],
+    ];
+    let faces = [
+        [0, 2, 4],
+        [2, 1, 4],
+        [1, 3, 4],
+        [3, 0, 4],
+        [2, 0, 5],
+        [1, 2, 5],
+        [3, 1, 5],
+        [0, 3, 5],
+    ];
+    Model::from_slices(&verts, &faces)
+}
+
+/// A model of the `models` directory.
+pub(crate) fn sample(name: &str) -> Model {
+    let path = format!("{}/models/{}", env!("CARGO_MANIFEST_DIR"), name);
+    Model::load(&path).unwrap()
+}
+
+/// A path in the temporary directory, unique to this process.
+pub(crate) fn temp_path(name: &str) -> String {
+    let path = std::env::temp_dir().join(format!("morphing-{}-{}", std::process::id(), name));
+    path.to_string_lossy().into_owned()
+}